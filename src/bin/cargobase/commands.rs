@@ -0,0 +1,476 @@
+use std::path::Path;
+
+use serde_json::Value;
+
+use cargobase::{CsvOptions, Database, DatabaseError, ImportReport, SqlOutput, Table, View};
+
+use crate::output::Output;
+use crate::{Command, Format};
+
+pub async fn run<W: Output>(
+    db: &mut Database,
+    command: Command,
+    out: &mut W,
+) -> Result<(), DatabaseError> {
+    match command {
+        Command::Tables => {
+            let mut names = db.list_tables();
+            names.sort();
+            for name in names {
+                writeln!(out, "{}\t{}", name, db.count_rows(&name)?)?;
+            }
+        }
+        Command::Schema { table } => {
+            let schema = table_ref(db, &table)?.json_schema();
+            writeln!(out, "{}", serde_json::to_string_pretty(&schema)?)?;
+        }
+        Command::Count { table } => {
+            writeln!(out, "{}", table_ref(db, &table)?.rows.len())?;
+        }
+        Command::Get {
+            table,
+            filter: (column, value),
+        } => {
+            let table = table_ref(db, &table)?;
+            let rows: Vec<&Value> = matching_ids(table, &column, &value)
+                .iter()
+                .map(|id| &table.rows[id].data)
+                .collect();
+            writeln!(out, "{}", serde_json::to_string_pretty(&rows)?)?;
+        }
+        Command::Insert { table, json } => {
+            let rows = match serde_json::from_str(&json)? {
+                Value::Array(rows) => rows,
+                row => vec![row],
+            };
+            let mut lines = String::new();
+            for row in &rows {
+                lines.push_str(&serde_json::to_string(row)?);
+                lines.push('\n');
+            }
+
+            let report = db.import_ndjson(&table, lines.as_bytes()).await?;
+            writeln!(out, "Inserted {} row(s)", report.imported)?;
+            check_report(&report, "row")?;
+        }
+        Command::Delete {
+            table,
+            filter: (column, value),
+        } => {
+            let ids = matching_ids(table_ref(db, &table)?, &column, &value);
+            for id in &ids {
+                db.delete_single()
+                    .from(&table)
+                    .where_key::<Value>(id)
+                    .await?;
+            }
+            writeln!(out, "Deleted {} row(s)", ids.len())?;
+        }
+        Command::Update {
+            table,
+            filter: (column, value),
+            json,
+        } => {
+            let data: Value = serde_json::from_str(&json)?;
+            if !data.is_object() {
+                return Err(DatabaseError::InvalidData(
+                    "update data must be a JSON object".to_string(),
+                ));
+            }
+            let ids = matching_ids(table_ref(db, &table)?, &column, &value);
+            for id in &ids {
+                db.update_row()
+                    .from(&table)
+                    .data(data.clone())
+                    .where_key::<Value>(id)
+                    .await?;
+            }
+            writeln!(out, "Updated {} row(s)", ids.len())?;
+        }
+        Command::Export {
+            format,
+            table,
+            output,
+        } => export(db, format, table.as_deref(), output.as_deref(), out).await?,
+        Command::Import {
+            format,
+            table,
+            file,
+        } => {
+            let report = import(db, format, table.as_deref(), &file).await?;
+            writeln!(out, "Imported {} row(s)", report.imported)?;
+            check_report(&report, "line")?;
+        }
+        Command::View { table, format } => {
+            let view = View::new(db).format(format);
+            match table {
+                Some(table) => view.write_table(&mut *out, table_ref(db, &table)?)?,
+                None => view.write(&mut *out)?,
+            }
+        }
+        Command::Sql { statement } => match db.execute_sql(&statement).await? {
+            SqlOutput::Rows(rows) => writeln!(out, "{}", serde_json::to_string_pretty(&rows)?)?,
+            SqlOutput::Affected(count) => writeln!(out, "{} row(s) affected", count)?,
+        },
+        // the shell, the browser and the server are started from main
+        Command::Shell { .. } => unreachable!("the shell is started from main"),
+        #[cfg(feature = "tui")]
+        Command::Tui { .. } => unreachable!("the browser is started from main"),
+        #[cfg(feature = "server")]
+        Command::Serve { .. } => unreachable!("the server is started from main"),
+    }
+    Ok(())
+}
+
+fn table_ref<'a>(db: &'a Database, table: &str) -> Result<&'a Table, DatabaseError> {
+    db.get_table(table)
+        .ok_or_else(|| DatabaseError::TableNotFound(table.to_string()))
+}
+
+// keys of the rows whose `column` is `value`, sorted; strings are compared
+// as they are and other values as JSON, so `age=42` matches 42
+fn matching_ids(table: &Table, column: &str, value: &str) -> Vec<String> {
+    let mut ids: Vec<String> = table
+        .rows
+        .iter()
+        .filter(|(_, row)| match row.data.get(column) {
+            Some(Value::String(s)) => s == value,
+            Some(other) => serde_json::from_str::<Value>(value).is_ok_and(|v| v == *other),
+            None => false,
+        })
+        .map(|(id, _)| id.clone())
+        .collect();
+    ids.sort();
+    ids
+}
+
+fn required_table(table: Option<&str>) -> Result<&str, DatabaseError> {
+    table.ok_or_else(|| {
+        DatabaseError::InvalidData("--table is required for ndjson and csv".to_string())
+    })
+}
+
+async fn export<W: Output>(
+    db: &Database,
+    format: Format,
+    table: Option<&str>,
+    output: Option<&Path>,
+    out: &mut W,
+) -> Result<(), DatabaseError> {
+    match format {
+        Format::Ndjson => {
+            let table = required_table(table)?;
+            match output {
+                Some(path) => {
+                    let file = tokio::fs::File::create(path).await?;
+                    db.export_ndjson(table, file).await?;
+                }
+                None => {
+                    db.export_ndjson(table, out).await?;
+                }
+            }
+        }
+        Format::Csv => {
+            let table = table_ref(db, required_table(table)?)?;
+            match output {
+                Some(path) => table.export_csv(std::fs::File::create(path)?)?,
+                None => table.export_csv(out)?,
+            };
+        }
+        Format::Sql => {
+            if table.is_some() {
+                return Err(DatabaseError::InvalidData(
+                    "sql exports the whole database, --table is not used".to_string(),
+                ));
+            }
+            match output {
+                Some(path) => db.export_sql(std::fs::File::create(path)?)?,
+                None => db.export_sql(out)?,
+            };
+        }
+    }
+    Ok(())
+}
+
+async fn import(
+    db: &mut Database,
+    format: Format,
+    table: Option<&str>,
+    file: &Path,
+) -> Result<ImportReport, DatabaseError> {
+    let stdin = file == Path::new("-");
+    match format {
+        Format::Ndjson => {
+            let table = required_table(table)?;
+            if stdin {
+                let reader = tokio::io::BufReader::new(tokio::io::stdin());
+                db.import_ndjson(table, reader).await
+            } else {
+                let reader = tokio::io::BufReader::new(tokio::fs::File::open(file).await?);
+                db.import_ndjson(table, reader).await
+            }
+        }
+        Format::Csv => {
+            let table = table_ref(db, required_table(table)?)?.clone();
+            if stdin {
+                table
+                    .import_csv(db, std::io::stdin(), CsvOptions::default())
+                    .await
+            } else {
+                let reader = std::fs::File::open(file)?;
+                table.import_csv(db, reader, CsvOptions::default()).await
+            }
+        }
+        Format::Sql => {
+            let sql = if stdin {
+                std::io::read_to_string(std::io::stdin())?
+            } else {
+                tokio::fs::read_to_string(file).await?
+            };
+            let report = db.import_sql(&sql).await?;
+            Ok(ImportReport {
+                imported: report.rows,
+                errors: Vec::new(),
+            })
+        }
+    }
+}
+
+// rejected records are listed and make the command fail
+fn check_report(report: &ImportReport, unit: &str) -> Result<(), DatabaseError> {
+    if report.is_clean() {
+        return Ok(());
+    }
+    let errors: Vec<String> = report
+        .errors
+        .iter()
+        .map(|error| format!("{} {}: {}", unit, error.line, error.message))
+        .collect();
+    Err(DatabaseError::InvalidData(errors.join("; ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use cargobase::{Column, Columns, ViewFormat};
+
+    // the database lives in the returned directory as `db.json`
+    async fn setup_users() -> (tempfile::TempDir, Database) {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Database::new(dir.path().join("db").to_str().unwrap()).await;
+        let mut users = Table::new(
+            "users".to_string(),
+            Columns::new(vec![
+                Column::new("id", true),
+                Column::new("name", true),
+                Column::new("age", false),
+            ]),
+        );
+        db.add_table(&mut users).await.unwrap();
+        users
+            .add_row(
+                &mut db,
+                json!([
+                    {"id": "1", "name": "John", "age": 42},
+                    {"id": "2", "name": "Jane", "age": 37},
+                ]),
+            )
+            .await;
+        (dir, db)
+    }
+
+    async fn run_to_string(db: &mut Database, command: Command) -> String {
+        let mut out = Vec::new();
+        run(db, command, &mut out).await.unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_tables_and_count() {
+        let (_dir, mut db) = setup_users().await;
+
+        assert_eq!(run_to_string(&mut db, Command::Tables).await, "users\t2\n");
+        let count = Command::Count {
+            table: "users".to_string(),
+        };
+        assert_eq!(run_to_string(&mut db, count).await, "2\n");
+    }
+
+    #[tokio::test]
+    async fn test_get_matches_non_string_values() {
+        let (_dir, mut db) = setup_users().await;
+        let get = Command::Get {
+            table: "users".to_string(),
+            filter: ("age".to_string(), "42".to_string()),
+        };
+
+        let rows: Value = serde_json::from_str(&run_to_string(&mut db, get).await).unwrap();
+        assert_eq!(rows, json!([{"id": "1", "name": "John", "age": 42}]));
+    }
+
+    #[tokio::test]
+    async fn test_insert_update_delete() {
+        let (dir, mut db) = setup_users().await;
+        let table = || "users".to_string();
+
+        let insert = Command::Insert {
+            table: table(),
+            json: r#"{"id": "3", "name": "Jim"}"#.to_string(),
+        };
+        assert_eq!(run_to_string(&mut db, insert).await, "Inserted 1 row(s)\n");
+
+        let duplicate = Command::Insert {
+            table: table(),
+            json: r#"[{"id": "3", "name": "Jim"}]"#.to_string(),
+        };
+        let mut out = Vec::new();
+        let result = run(&mut db, duplicate, &mut out).await;
+        assert!(matches!(result, Err(DatabaseError::InvalidData(m)) if m.contains("row 1")));
+
+        let update = Command::Update {
+            table: table(),
+            filter: ("name".to_string(), "Jim".to_string()),
+            json: r#"{"age": 7}"#.to_string(),
+        };
+        assert_eq!(run_to_string(&mut db, update).await, "Updated 1 row(s)\n");
+
+        let mut db = Database::open(dir.path().join("db.json")).await.unwrap();
+        assert_eq!(
+            db.get_table("users").unwrap().rows["3"].data["age"],
+            json!(7)
+        );
+
+        let delete = Command::Delete {
+            table: table(),
+            filter: ("age".to_string(), "7".to_string()),
+        };
+        assert_eq!(run_to_string(&mut db, delete).await, "Deleted 1 row(s)\n");
+        let db = Database::open(dir.path().join("db.json")).await.unwrap();
+        assert_eq!(db.count_rows("users").unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_update_delete_by_table_key() {
+        let (dir, mut db) = setup_users().await;
+        let mut orders = Table::new(
+            "orders".to_string(),
+            Columns::new(vec![
+                Column::new("region", true),
+                Column::new("number", true),
+            ]),
+        )
+        .with_primary_key(&["region", "number"]);
+        db.add_table(&mut orders).await.unwrap();
+        orders
+            .add_row(&mut db, json!({"region": "eu", "number": 7}))
+            .await;
+
+        let update = Command::Update {
+            table: "orders".to_string(),
+            filter: ("number".to_string(), "7".to_string()),
+            json: r#"{"item": "pen"}"#.to_string(),
+        };
+        assert_eq!(run_to_string(&mut db, update).await, "Updated 1 row(s)\n");
+        let mut db = Database::open(dir.path().join("db.json")).await.unwrap();
+        assert_eq!(
+            db.get_table("orders").unwrap().rows[r#"["eu",7]"#].data["item"],
+            json!("pen")
+        );
+
+        let delete = Command::Delete {
+            table: "orders".to_string(),
+            filter: ("region".to_string(), "eu".to_string()),
+        };
+        assert_eq!(run_to_string(&mut db, delete).await, "Deleted 1 row(s)\n");
+        let db = Database::open(dir.path().join("db.json")).await.unwrap();
+        assert_eq!(db.count_rows("orders").unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_export_and_import() {
+        let (dir, mut db) = setup_users().await;
+        let path = dir.path().join("users.csv");
+
+        let export = Command::Export {
+            format: Format::Csv,
+            table: Some("users".to_string()),
+            output: Some(path.clone()),
+        };
+        run_to_string(&mut db, export).await;
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "id,name,age\n1,John,42\n2,Jane,37\n"
+        );
+
+        let ndjson = Command::Export {
+            format: Format::Ndjson,
+            table: Some("users".to_string()),
+            output: None,
+        };
+        assert!(run_to_string(&mut db, ndjson)
+            .await
+            .starts_with(r#"{"age":42,"id":"1","name":"John"}"#));
+
+        db.drop_table("users").await.unwrap();
+        let mut users = Table::new(
+            "users".to_string(),
+            Columns::new(vec![
+                Column::new("id", true),
+                Column::new("name", true),
+                Column::new("age", false),
+            ]),
+        );
+        db.add_table(&mut users).await.unwrap();
+        let import = Command::Import {
+            format: Format::Csv,
+            table: Some("users".to_string()),
+            file: path,
+        };
+        assert_eq!(run_to_string(&mut db, import).await, "Imported 2 row(s)\n");
+    }
+
+    #[tokio::test]
+    async fn test_view() {
+        let (_dir, mut db) = setup_users().await;
+        let view = Command::View {
+            table: Some("users".to_string()),
+            format: ViewFormat::Csv,
+        };
+
+        assert_eq!(
+            run_to_string(&mut db, view).await,
+            "users\nid,name,age\n1,John,42\n2,Jane,37\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sql() {
+        let (_dir, mut db) = setup_users().await;
+        let sql = |statement: &str| Command::Sql {
+            statement: statement.to_string(),
+        };
+
+        let deleted = run_to_string(&mut db, sql("DELETE FROM users WHERE age > 40")).await;
+        assert_eq!(deleted, "1 row(s) affected\n");
+
+        let rows = run_to_string(&mut db, sql("SELECT name FROM users")).await;
+        let rows: Value = serde_json::from_str(&rows).unwrap();
+        assert_eq!(rows, json!([{"name": "Jane"}]));
+    }
+
+    #[tokio::test]
+    async fn test_export_requires_table() {
+        let (_dir, mut db) = setup_users().await;
+        let export = Command::Export {
+            format: Format::Csv,
+            table: None,
+            output: None,
+        };
+
+        let mut out = Vec::new();
+        let result = run(&mut db, export, &mut out).await;
+        assert!(matches!(result, Err(DatabaseError::InvalidData(_))));
+    }
+}
@@ -0,0 +1,187 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+
+use cargobase::{Database, ViewFormat};
+
+mod commands;
+mod output;
+mod shell;
+#[cfg(feature = "tui")]
+mod tui;
+
+/// Inspect and edit cargobase databases.
+#[derive(Debug, Parser)]
+#[command(name = "cargobase", version)]
+struct Cli {
+    /// Path of the database `.json` file or directory
+    #[arg(long, global = true)]
+    db: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// List the tables and their row counts
+    Tables,
+    /// Print the JSON Schema of a table
+    Schema { table: String },
+    /// Print the number of rows in a table
+    Count { table: String },
+    /// Print the rows where COLUMN equals VALUE, as a JSON array
+    Get {
+        table: String,
+        #[arg(value_name = "COLUMN=VALUE", value_parser = parse_filter)]
+        filter: (String, String),
+    },
+    /// Insert a JSON row, or an array of rows
+    Insert { table: String, json: String },
+    /// Delete the rows where COLUMN equals VALUE
+    Delete {
+        table: String,
+        #[arg(value_name = "COLUMN=VALUE", value_parser = parse_filter)]
+        filter: (String, String),
+    },
+    /// Set the fields of a JSON object on the rows where COLUMN equals VALUE
+    Update {
+        table: String,
+        #[arg(value_name = "COLUMN=VALUE", value_parser = parse_filter)]
+        filter: (String, String),
+        json: String,
+    },
+    /// Write a table, or the whole database as SQL, to a file or stdout
+    Export {
+        #[arg(long, value_enum, default_value_t = Format::Ndjson)]
+        format: Format,
+        /// Table to export, required for ndjson and csv
+        #[arg(long)]
+        table: Option<String>,
+        /// File to write instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Read rows from a file, or `-` for stdin
+    Import {
+        #[arg(long, value_enum, default_value_t = Format::Ndjson)]
+        format: Format,
+        /// Table to import into, required for ndjson and csv
+        #[arg(long)]
+        table: Option<String>,
+        file: PathBuf,
+    },
+    /// Print a table, or every table
+    View {
+        table: Option<String>,
+        /// grid, markdown, csv, html or json
+        #[arg(long, default_value = "grid")]
+        format: ViewFormat,
+    },
+    /// Run one SQL statement: SELECT, INSERT, UPDATE or DELETE
+    Sql { statement: String },
+    /// Start an interactive shell on the database at PATH, or at `--db`
+    Shell { path: Option<PathBuf> },
+    /// Browse the database at PATH, or at `--db`, full screen
+    #[cfg(feature = "tui")]
+    Tui { path: Option<PathBuf> },
+    /// Serve the database over HTTP
+    #[cfg(feature = "server")]
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:3000")]
+        addr: std::net::SocketAddr,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Ndjson,
+    Csv,
+    Sql,
+}
+
+fn parse_filter(filter: &str) -> Result<(String, String), String> {
+    match filter.split_once('=') {
+        Some((column, value)) if !column.is_empty() => Ok((column.to_string(), value.to_string())),
+        _ => Err(format!("expected COLUMN=VALUE, got `{}`", filter)),
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let path = match &cli.command {
+        Command::Shell { path: Some(path) } => Some(path.clone()),
+        #[cfg(feature = "tui")]
+        Command::Tui { path: Some(path) } => Some(path.clone()),
+        _ => cli.db.clone(),
+    };
+    let Some(path) = path else {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "the database path is required, pass it with `--db <DB>`",
+            )
+            .exit();
+    };
+
+    let result = match cli.command {
+        Command::Shell { .. } => shell::run(&path).await,
+        #[cfg(feature = "tui")]
+        Command::Tui { .. } => tui::run(&path).await,
+        #[cfg(feature = "server")]
+        Command::Serve { addr } => cargobase::server::serve(path, addr).await,
+        command => match Database::open(&path).await {
+            Ok(mut db) => commands::run(&mut db, command, &mut output::Stdout).await,
+            Err(e) => Err(e),
+        },
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_parse_filter() {
+        assert_eq!(
+            parse_filter("name=John=Doe"),
+            Ok(("name".to_string(), "John=Doe".to_string()))
+        );
+        assert_eq!(
+            parse_filter("name="),
+            Ok(("name".to_string(), String::new()))
+        );
+        assert!(parse_filter("=John").is_err());
+        assert!(parse_filter("name").is_err());
+    }
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_shell_path() {
+        let cli = Cli::try_parse_from(["cargobase", "shell", "db.json"]).unwrap();
+        assert!(cli.db.is_none());
+        assert!(
+            matches!(cli.command, Command::Shell { path: Some(p) } if p == Path::new("db.json"))
+        );
+
+        let cli = Cli::try_parse_from(["cargobase", "tables", "--db", "db.json"]).unwrap();
+        assert_eq!(cli.db, Some(PathBuf::from("db.json")));
+    }
+}
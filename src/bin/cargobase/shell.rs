@@ -0,0 +1,515 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use serde_json::Value;
+
+use cargobase::{Database, DatabaseError, FilterOp, Query, View, ViewOptions};
+
+const HISTORY_FILE: &str = ".cargobase_history";
+
+const COMMANDS: &[&str] = &[
+    "help", "tables", "columns", "count", "view", "from", "filter", "select", "order", "limit",
+    "show", "run", "reset", "reload", "exit", "quit",
+];
+
+const HELP: &str = "\
+tables                      list the tables
+columns <table>             list the columns of a table
+count <table>               count the rows of a table
+view <table>                print a whole table
+from <table>                start a query on a table
+filter <column> <op> <value>
+                            keep rows where the column compares to the value;
+                            op is one of = != < <= > >= contains
+select <column>, ...        keep only these columns (`select *` for all)
+order <column> [asc|desc]   sort the rows
+limit <n>                   return at most n rows
+show                        print the current query
+run                         run the current query
+reset                       clear the current query
+reload                      reread the database from disk
+exit                        leave the shell";
+
+/// What the shell does after a line has run.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Continue,
+    Exit,
+}
+
+/// State of an interactive session: the open database and the query being
+/// built up by `from`, `filter`, `select`, `order` and `limit`.
+pub struct Session {
+    path: PathBuf,
+    db: Database,
+    query: Option<Query>,
+}
+
+impl Session {
+    pub async fn open(path: &Path) -> Result<Self, DatabaseError> {
+        Ok(Session {
+            path: path.to_path_buf(),
+            db: Database::open(path).await?,
+            query: None,
+        })
+    }
+
+    /// Column names per table, for completion.
+    fn columns(&self) -> BTreeMap<String, Vec<String>> {
+        self.db
+            .list_tables()
+            .into_iter()
+            .filter_map(|name| {
+                let table = self.db.get_table(&name)?;
+                let columns = table.columns.0.iter().map(|c| c.name.clone()).collect();
+                Some((name, columns))
+            })
+            .collect()
+    }
+
+    fn current_table(&self) -> Option<&str> {
+        self.query.as_ref()?.table_name.as_deref()
+    }
+
+    pub async fn execute<W: Write>(
+        &mut self,
+        line: &str,
+        out: &mut W,
+    ) -> Result<Outcome, DatabaseError> {
+        let line = line.trim();
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+
+        match command.to_ascii_lowercase().as_str() {
+            "" => {}
+            "help" => writeln!(out, "{}", HELP)?,
+            "exit" | "quit" => return Ok(Outcome::Exit),
+            "tables" => {
+                for name in self.columns().keys() {
+                    writeln!(out, "{}", name)?;
+                }
+            }
+            "columns" => {
+                let table = self.table(rest)?;
+                for column in &table.columns.0 {
+                    let required = if column.required {
+                        "required"
+                    } else {
+                        "optional"
+                    };
+                    writeln!(
+                        out,
+                        "{}\t{:?}\t{}",
+                        column.name, column.column_type, required
+                    )?;
+                }
+            }
+            "count" => writeln!(out, "{}", self.table(rest)?.rows.len())?,
+            "view" => {
+                let table = self.table(rest)?;
+                let columns: Vec<String> = table.columns.0.iter().map(|c| c.name.clone()).collect();
+                let mut ids: Vec<&String> = table.rows.keys().collect();
+                ids.sort();
+                let rows: Vec<Value> = ids
+                    .into_iter()
+                    .map(|id| table.rows[id].data.clone())
+                    .collect();
+                View::write_rows(out, &columns, &rows, &ViewOptions::new().plain_strings())?;
+            }
+            "from" => {
+                self.table(rest)?;
+                self.query = Some(self.db.get_rows().from(rest));
+            }
+            "filter" => {
+                let (column, op, value) = parse_filter(rest)?;
+                let query = self.take_query()?;
+                self.query = Some(query.filter(&column, op, value));
+            }
+            "select" => {
+                let columns: Vec<&str> = rest
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|c| !c.is_empty())
+                    .collect();
+                if columns.is_empty() {
+                    return Err(usage("select <column>, ..."));
+                }
+                let query = self.take_query()?;
+                self.query = Some(if columns == ["*"] {
+                    Query {
+                        columns: None,
+                        ..query
+                    }
+                } else {
+                    query.select(&columns)
+                });
+            }
+            "order" => {
+                let query = self.take_query()?;
+                self.query = Some(match rest.split_whitespace().collect::<Vec<_>>()[..] {
+                    [column] | [column, "asc"] => query.order_by(column),
+                    [column, "desc"] => query.order_by_desc(column),
+                    _ => {
+                        self.query = Some(query);
+                        return Err(usage("order <column> [asc|desc]"));
+                    }
+                });
+            }
+            "limit" => {
+                let limit = rest.parse().map_err(|_| usage("limit <n>"))?;
+                let query = self.take_query()?;
+                self.query = Some(query.limit(limit));
+            }
+            "show" => match &self.query {
+                Some(query) => writeln!(out, "{}", describe(query))?,
+                None => writeln!(out, "no query, start one with `from <table>`")?,
+            },
+            "run" => {
+                let query = self.query.as_ref().ok_or_else(no_query)?;
+                let rows = query.rows().await?;
+                let columns = match &query.columns {
+                    Some(columns) => columns.clone(),
+                    None => self
+                        .columns()
+                        .remove(self.current_table().unwrap_or_default())
+                        .unwrap_or_default(),
+                };
+                View::write_rows(out, &columns, &rows, &ViewOptions::new().plain_strings())?;
+                writeln!(out, "({} row(s))", rows.len())?;
+            }
+            "reset" => self.query = None,
+            "reload" => self.db = Database::open(&self.path).await?,
+            other => {
+                return Err(DatabaseError::InvalidOperation(format!(
+                    "unknown command `{}`, try `help`",
+                    other
+                )))
+            }
+        }
+        Ok(Outcome::Continue)
+    }
+
+    fn table(&self, name: &str) -> Result<&cargobase::Table, DatabaseError> {
+        if name.is_empty() {
+            return Err(usage("<table> is missing"));
+        }
+        self.db
+            .get_table(name)
+            .ok_or_else(|| DatabaseError::TableNotFound(name.to_string()))
+    }
+
+    fn take_query(&mut self) -> Result<Query, DatabaseError> {
+        self.query.take().ok_or_else(no_query)
+    }
+}
+
+fn usage(message: &str) -> DatabaseError {
+    DatabaseError::InvalidOperation(format!("usage: {}", message))
+}
+
+fn no_query() -> DatabaseError {
+    DatabaseError::InvalidOperation("no query, start one with `from <table>`".to_string())
+}
+
+// `<column> <op> <value>`, where the value is read as JSON when it parses
+// and as a string otherwise
+pub(crate) fn parse_filter(input: &str) -> Result<(String, FilterOp, Value), DatabaseError> {
+    let mut parts = input.splitn(3, char::is_whitespace);
+    let (Some(column), Some(op), Some(value)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(usage("filter <column> <op> <value>"));
+    };
+    let op = match op.to_ascii_lowercase().as_str() {
+        "=" | "==" => FilterOp::Eq,
+        "!=" | "<>" => FilterOp::Ne,
+        "<" => FilterOp::Lt,
+        "<=" => FilterOp::Le,
+        ">" => FilterOp::Gt,
+        ">=" => FilterOp::Ge,
+        "contains" => FilterOp::Contains,
+        other => {
+            return Err(DatabaseError::InvalidOperation(format!(
+                "unknown operator `{}`",
+                other
+            )))
+        }
+    };
+    let value = value.trim();
+    let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()));
+    Ok((column.to_string(), op, value))
+}
+
+fn describe(query: &Query) -> String {
+    let mut parts = vec![format!(
+        "from {}",
+        query.table_name.as_deref().unwrap_or("?")
+    )];
+    for filter in &query.filters {
+        parts.push(format!(
+            "filter {} {:?} {}",
+            filter.column, filter.op, filter.value
+        ));
+    }
+    if let Some(columns) = &query.columns {
+        parts.push(format!("select {}", columns.join(", ")));
+    }
+    if let Some(order) = &query.order {
+        let direction = if order.descending { "desc" } else { "asc" };
+        parts.push(format!("order {} {}", order.column, direction));
+    }
+    if let Some(limit) = query.limit {
+        parts.push(format!("limit {}", limit));
+    }
+    parts.join("\n")
+}
+
+/// Completes command names, table names and the columns of the table the
+/// current query reads.
+struct ShellHelper {
+    columns: BTreeMap<String, Vec<String>>,
+    current_table: Option<String>,
+}
+
+impl ShellHelper {
+    fn candidates(&self, line: &str) -> (usize, Vec<String>) {
+        let start = line
+            .rfind(|c: char| c.is_whitespace() || c == ',')
+            .map_or(0, |i| i + 1);
+        let word = &line[start..];
+        let previous: Vec<&str> = line[..start].split_whitespace().collect();
+
+        let options: Vec<String> = match previous.as_slice() {
+            [] => COMMANDS.iter().map(|c| c.to_string()).collect(),
+            [command] if ["from", "columns", "count", "view"].contains(command) => {
+                self.columns.keys().cloned().collect()
+            }
+            [command] if ["filter", "order"].contains(command) => self.current_columns(),
+            ["select", ..] => self.current_columns(),
+            ["filter", _] => ["=", "!=", "<", "<=", ">", ">=", "contains"]
+                .iter()
+                .map(|op| op.to_string())
+                .collect(),
+            ["order", _] => vec!["asc".to_string(), "desc".to_string()],
+            _ => Vec::new(),
+        };
+
+        let matches = options
+            .into_iter()
+            .filter(|option| option.starts_with(word))
+            .collect();
+        (start, matches)
+    }
+
+    fn current_columns(&self) -> Vec<String> {
+        self.current_table
+            .as_ref()
+            .and_then(|table| self.columns.get(table))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+impl Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.candidates(&line[..pos]))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+/// Run the interactive shell on the database at `path` until `exit` or
+/// end of input.
+pub async fn run(path: &Path) -> Result<(), DatabaseError> {
+    let mut session = Session::open(path).await?;
+    let readline_error = |e: ReadlineError| DatabaseError::InvalidOperation(e.to_string());
+
+    let mut editor: Editor<ShellHelper, DefaultHistory> = Editor::new().map_err(readline_error)?;
+    let history = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE));
+    if let Some(history) = &history {
+        // there is no history yet on the first run
+        let _ = editor.load_history(history);
+    }
+
+    let mut stdout = std::io::stdout();
+    loop {
+        editor.set_helper(Some(ShellHelper {
+            columns: session.columns(),
+            current_table: session.current_table().map(str::to_string),
+        }));
+        let prompt = match session.current_table() {
+            Some(table) => format!("cargobase:{}> ", table),
+            None => "cargobase> ".to_string(),
+        };
+
+        let line = match editor.readline(&prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(readline_error(e)),
+        };
+        if !line.trim().is_empty() {
+            let _ = editor.add_history_entry(line.as_str());
+        }
+
+        match session.execute(&line, &mut stdout).await {
+            Ok(Outcome::Exit) => break,
+            Ok(Outcome::Continue) => {}
+            Err(e) => eprintln!("error: {}", e),
+        }
+    }
+
+    if let Some(history) = &history {
+        if let Err(e) = editor.save_history(history) {
+            eprintln!("error: could not save history: {}", e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use cargobase::{Column, Columns, Table};
+
+    async fn setup_session() -> (tempfile::TempDir, Session) {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Database::new(dir.path().join("db").to_str().unwrap()).await;
+        let mut users = Table::new(
+            "users".to_string(),
+            Columns::new(vec![
+                Column::new("id", true),
+                Column::new("name", true),
+                Column::new("age", false),
+            ]),
+        );
+        db.add_table(&mut users).await.unwrap();
+        users
+            .add_row(
+                &mut db,
+                json!([
+                    {"id": "1", "name": "John", "age": 42},
+                    {"id": "2", "name": "Jane", "age": 37},
+                    {"id": "3", "name": "Jim", "age": 7},
+                ]),
+            )
+            .await;
+
+        let session = Session::open(&dir.path().join("db.json")).await.unwrap();
+        (dir, session)
+    }
+
+    async fn execute(session: &mut Session, line: &str) -> String {
+        let mut out = Vec::new();
+        session.execute(line, &mut out).await.unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_query_commands() {
+        let (_dir, mut session) = setup_session().await;
+
+        for line in [
+            "from users",
+            "filter age > 10",
+            "select name, age",
+            "order age asc",
+            "limit 5",
+        ] {
+            execute(&mut session, line).await;
+        }
+
+        assert_eq!(
+            execute(&mut session, "run").await,
+            "name | age\n-----+----\nJane | 37 \nJohn | 42 \n(2 row(s))\n"
+        );
+        assert_eq!(
+            execute(&mut session, "show").await,
+            "from users\nfilter age Gt 10\nselect name, age\norder age asc\nlimit 5\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_command_errors() {
+        let (_dir, mut session) = setup_session().await;
+        let mut out = Vec::new();
+
+        for line in ["filter age > 1", "from nobody", "bogus", "limit x"] {
+            assert!(session.execute(line, &mut out).await.is_err(), "{}", line);
+        }
+
+        // a bad clause leaves the query being built alone
+        execute(&mut session, "from users").await;
+        execute(&mut session, "limit 5").await;
+        for line in ["select", "select ,", "order"] {
+            assert!(session.execute(line, &mut out).await.is_err(), "{}", line);
+        }
+        assert_eq!(execute(&mut session, "show").await, "from users\nlimit 5\n");
+        assert_eq!(
+            session.execute("exit", &mut out).await.unwrap(),
+            Outcome::Exit
+        );
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        assert_eq!(
+            parse_filter("name = John Doe").unwrap(),
+            ("name".to_string(), FilterOp::Eq, json!("John Doe"))
+        );
+        assert_eq!(
+            parse_filter("age >= 18").unwrap(),
+            ("age".to_string(), FilterOp::Ge, json!(18))
+        );
+        assert!(parse_filter("age ~ 18").is_err());
+        assert!(parse_filter("age >").is_err());
+    }
+
+    #[test]
+    fn test_completion() {
+        let helper = ShellHelper {
+            columns: BTreeMap::from([
+                (
+                    "users".to_string(),
+                    vec!["id".to_string(), "name".to_string()],
+                ),
+                ("posts".to_string(), vec!["title".to_string()]),
+            ]),
+            current_table: Some("users".to_string()),
+        };
+
+        assert_eq!(helper.candidates("fi"), (0, vec!["filter".to_string()]));
+        assert_eq!(helper.candidates("from u"), (5, vec!["users".to_string()]));
+        assert_eq!(
+            helper.candidates("select id, n"),
+            (11, vec!["name".to_string()])
+        );
+        assert_eq!(
+            helper.candidates("order name d"),
+            (11, vec!["desc".to_string()])
+        );
+    }
+}
@@ -0,0 +1,571 @@
+use std::path::{Path, PathBuf};
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Style, Stylize};
+use ratatui::widgets::{Block, List, ListState, Paragraph, Row, Table, TableState, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use serde_json::Value;
+
+use cargobase::database_components::timestamps::{CREATED_AT, UPDATED_AT};
+use cargobase::{Database, DatabaseError, View, ViewOptions};
+
+use crate::shell::parse_filter;
+
+// cells wider than this are cut short in the grid, the detail pane shows
+// them whole
+const MAX_CELL_WIDTH: usize = 30;
+
+const HELP: &str =
+    "q quit  tab switch pane  ←→ scroll columns  / filter  e edit  d delete  r reload";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Tables,
+    Rows,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Normal,
+    /// Typing a `<column> <op> <value>` filter, as in the shell.
+    Filter,
+    /// Typing the JSON fields to set on the selected row.
+    Edit,
+    ConfirmDelete,
+}
+
+/// State of the browser: the open database, the rows of the selected table
+/// that pass the filter, and what is being typed.
+pub struct App {
+    path: PathBuf,
+    db: Database,
+    tables: Vec<String>,
+    table_state: ListState,
+    columns: Vec<String>,
+    rows: Vec<Value>,
+    row_state: TableState,
+    // columns scrolled off to the left
+    column_offset: usize,
+    focus: Focus,
+    mode: Mode,
+    // the filter bar while typing a filter or an edit
+    input: String,
+    filter: String,
+    status: String,
+    quit: bool,
+}
+
+impl App {
+    pub async fn open(path: &Path) -> Result<Self, DatabaseError> {
+        let mut app = App {
+            path: path.to_path_buf(),
+            db: Database::open(path).await?,
+            tables: Vec::new(),
+            table_state: ListState::default(),
+            columns: Vec::new(),
+            rows: Vec::new(),
+            row_state: TableState::default(),
+            column_offset: 0,
+            focus: Focus::Tables,
+            mode: Mode::Normal,
+            input: String::new(),
+            filter: String::new(),
+            status: HELP.to_string(),
+            quit: false,
+        };
+        app.reload().await?;
+        Ok(app)
+    }
+
+    fn current_table(&self) -> Option<&str> {
+        self.table_state
+            .selected()
+            .and_then(|i| self.tables.get(i))
+            .map(String::as_str)
+    }
+
+    fn selected_row(&self) -> Option<&Value> {
+        self.row_state.selected().and_then(|i| self.rows.get(i))
+    }
+
+    // reread the database, keeping the selection where it still fits
+    async fn reload(&mut self) -> Result<(), DatabaseError> {
+        self.db = Database::open(&self.path).await?;
+        self.tables = self.db.list_tables();
+        self.tables.sort();
+        let selected = self.table_state.selected().unwrap_or(0);
+        self.table_state
+            .select((!self.tables.is_empty()).then(|| selected.min(self.tables.len() - 1)));
+        self.load_rows().await
+    }
+
+    // the rows of the selected table that pass the filter, through `Query`
+    async fn load_rows(&mut self) -> Result<(), DatabaseError> {
+        let Some(table_name) = self.current_table().map(str::to_string) else {
+            self.columns.clear();
+            self.rows.clear();
+            self.row_state.select(None);
+            return Ok(());
+        };
+
+        let mut query = self.db.get_rows().from(&table_name);
+        if !self.filter.trim().is_empty() {
+            let (column, op, value) = parse_filter(&self.filter)?;
+            query = query.filter(&column, op, value);
+        }
+        self.rows = query.rows().await?;
+
+        self.columns = match self.db.get_table(&table_name) {
+            Some(table) if !table.columns.0.is_empty() => {
+                table.columns.0.iter().map(|c| c.name.clone()).collect()
+            }
+            // without declared columns, show every field that appears
+            _ => {
+                let mut names: Vec<String> = self
+                    .rows
+                    .iter()
+                    .filter_map(Value::as_object)
+                    .flat_map(|row| row.keys().cloned())
+                    .collect();
+                names.sort();
+                names.dedup();
+                names
+            }
+        };
+        self.column_offset = self.column_offset.min(self.columns.len().saturating_sub(1));
+
+        let selected = self.row_state.selected().unwrap_or(0);
+        self.row_state
+            .select((!self.rows.is_empty()).then(|| selected.min(self.rows.len() - 1)));
+        Ok(())
+    }
+
+    pub async fn handle_key(&mut self, key: KeyEvent) {
+        let result = match self.mode {
+            Mode::Normal => self.normal_key(key).await,
+            Mode::Filter | Mode::Edit => self.input_key(key).await,
+            Mode::ConfirmDelete => {
+                self.mode = Mode::Normal;
+                if key.code == KeyCode::Char('y') {
+                    self.delete_row().await
+                } else {
+                    self.status = "Delete cancelled".to_string();
+                    Ok(())
+                }
+            }
+        };
+        if let Err(e) = result {
+            self.status = format!("error: {}", e);
+        }
+    }
+
+    async fn normal_key(&mut self, key: KeyEvent) -> Result<(), DatabaseError> {
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Tab | KeyCode::BackTab => {
+                self.focus = match self.focus {
+                    Focus::Tables => Focus::Rows,
+                    Focus::Rows => Focus::Tables,
+                }
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1).await?,
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1).await?,
+            KeyCode::PageUp => self.move_selection(-10).await?,
+            KeyCode::PageDown => self.move_selection(10).await?,
+            KeyCode::Left | KeyCode::Char('h') => {
+                self.column_offset = self.column_offset.saturating_sub(1)
+            }
+            KeyCode::Right | KeyCode::Char('l') if self.column_offset + 1 < self.columns.len() => {
+                self.column_offset += 1
+            }
+            KeyCode::Char('/') => {
+                self.input = self.filter.clone();
+                self.mode = Mode::Filter;
+                self.status =
+                    "Filter: <column> <op> <value>, enter to apply, esc to cancel".to_string();
+            }
+            KeyCode::Char('e') => {
+                let Some(row) = self.editable_fields() else {
+                    self.status = "No row selected".to_string();
+                    return Ok(());
+                };
+                self.input = serde_json::to_string(&row)?;
+                self.mode = Mode::Edit;
+                self.status =
+                    "Edit: JSON fields to set, enter to save, ctrl-u to clear, esc to cancel"
+                        .to_string();
+            }
+            KeyCode::Char('d') if self.selected_row().is_some() => {
+                self.mode = Mode::ConfirmDelete;
+                self.status = "Delete the selected row? y/n".to_string();
+            }
+            KeyCode::Char('r') => {
+                self.reload().await?;
+                self.status = "Reloaded".to_string();
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    async fn move_selection(&mut self, by: isize) -> Result<(), DatabaseError> {
+        let (state, len) = match self.focus {
+            Focus::Tables => (self.table_state.selected(), self.tables.len()),
+            Focus::Rows => (self.row_state.selected(), self.rows.len()),
+        };
+        if len == 0 {
+            return Ok(());
+        }
+        let next = state.unwrap_or(0).saturating_add_signed(by).min(len - 1);
+
+        match self.focus {
+            Focus::Tables => {
+                if Some(next) != state {
+                    self.table_state.select(Some(next));
+                    // a filter rarely fits another table
+                    self.filter.clear();
+                    self.column_offset = 0;
+                    self.row_state.select(Some(0));
+                    self.load_rows().await?;
+                }
+            }
+            Focus::Rows => self.row_state.select(Some(next)),
+        }
+        Ok(())
+    }
+
+    async fn input_key(&mut self, key: KeyEvent) -> Result<(), DatabaseError> {
+        match key.code {
+            KeyCode::Esc => {
+                self.mode = Mode::Normal;
+                self.status = HELP.to_string();
+            }
+            KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.input.clear()
+            }
+            KeyCode::Char(c) => self.input.push(c),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Enter if self.mode == Mode::Filter => {
+                let previous = std::mem::replace(&mut self.filter, self.input.trim().to_string());
+                if let Err(e) = self.load_rows().await {
+                    // stay in the filter bar to fix the typo
+                    self.filter = previous;
+                    return Err(e);
+                }
+                self.mode = Mode::Normal;
+                self.status = format!("{} row(s)", self.rows.len());
+            }
+            KeyCode::Enter => {
+                // errors keep the edit open, so nothing typed is lost
+                self.update_row().await?;
+                self.mode = Mode::Normal;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // updates go through `Query`, so columns and schemas are checked as usual
+    async fn update_row(&mut self) -> Result<(), DatabaseError> {
+        let (Some(table), Some(id)) = (self.current_table(), self.selected_id()) else {
+            return Ok(());
+        };
+        let table = table.to_string();
+
+        // the query refuses edits to the row's key
+        let data: Value = serde_json::from_str(&self.input)?;
+        if !data.is_object() {
+            return Err(DatabaseError::InvalidData(
+                "the edit must be a JSON object".to_string(),
+            ));
+        }
+
+        self.db
+            .update_row()
+            .from(&table)
+            .data(data)
+            .where_key::<Value>(&id)
+            .await?;
+        self.reload().await?;
+        self.status = format!("Row `{}` updated", id);
+        Ok(())
+    }
+
+    async fn delete_row(&mut self) -> Result<(), DatabaseError> {
+        let (Some(table), Some(id)) = (self.current_table(), self.selected_id()) else {
+            return Ok(());
+        };
+        let table = table.to_string();
+
+        self.db
+            .delete_single()
+            .from(&table)
+            .where_key::<Value>(&id)
+            .await?;
+        self.reload().await?;
+        self.status = format!("Row `{}` deleted", id);
+        Ok(())
+    }
+
+    // the key the selected row is stored under, see `Table::row_key`
+    // the selected row without the fields an update may not change or that
+    // are kept for it: key columns and timestamps
+    fn editable_fields(&self) -> Option<Value> {
+        let mut row = self.selected_row()?.clone();
+        if let (Some(table), Some(fields)) = (
+            self.current_table()
+                .and_then(|name| self.db.get_table(name)),
+            row.as_object_mut(),
+        ) {
+            let timestamp =
+                |column: &str| table.has_timestamps() && [CREATED_AT, UPDATED_AT].contains(&column);
+            fields.retain(|column, _| !table.is_key_column(column) && !timestamp(column));
+        }
+        Some(row)
+    }
+
+    fn selected_id(&self) -> Option<String> {
+        let table = self.db.get_table(self.current_table()?)?;
+        table.row_key(self.selected_row()?)
+    }
+}
+
+fn draw(frame: &mut Frame, app: &mut App) {
+    let [main, bar, status] = Layout::vertical([
+        Constraint::Min(0),
+        Constraint::Length(3),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [left, right] =
+        Layout::horizontal([Constraint::Length(24), Constraint::Min(0)]).areas(main);
+    let [grid_area, detail_area] =
+        Layout::vertical([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(right);
+
+    let focused = |focus: Focus| match app.focus == focus {
+        true => Style::new().bold(),
+        false => Style::new(),
+    };
+    let tables = List::new(app.tables.clone())
+        .block(
+            Block::bordered()
+                .title("Tables")
+                .border_style(focused(Focus::Tables)),
+        )
+        .highlight_style(Style::new().reversed());
+    frame.render_stateful_widget(tables, left, &mut app.table_state);
+
+    // the grid is laid out as `View` would write it
+    let columns = &app.columns[app.column_offset.min(app.columns.len())..];
+    let options = ViewOptions::new().max_width(MAX_CELL_WIDTH).plain_strings();
+    let grid = View::grid(columns, &app.rows, &options);
+    let widths: Vec<Constraint> = grid
+        .widths
+        .iter()
+        .map(|&width| Constraint::Length(width as u16))
+        .collect();
+    let title = match (app.current_table(), app.filter.is_empty()) {
+        (Some(table), true) => format!("{} ({} rows)", table, app.rows.len()),
+        (Some(table), false) => format!("{} where {} ({} rows)", table, app.filter, app.rows.len()),
+        (None, _) => "No tables".to_string(),
+    };
+    let rows = Table::new(grid.rows.into_iter().map(Row::new), widths)
+        .header(Row::new(grid.header).bold())
+        .block(
+            Block::bordered()
+                .title(title)
+                .border_style(focused(Focus::Rows)),
+        )
+        .row_highlight_style(Style::new().reversed());
+    frame.render_stateful_widget(rows, grid_area, &mut app.row_state);
+
+    let detail = app
+        .selected_row()
+        .map(|row| serde_json::to_string_pretty(row).unwrap_or_default())
+        .unwrap_or_default();
+    let detail = Paragraph::new(detail)
+        .block(Block::bordered().title("Row"))
+        .wrap(Wrap { trim: false });
+    frame.render_widget(detail, detail_area);
+
+    let (bar_title, bar_text) = match app.mode {
+        Mode::Filter => ("Filter", format!("{}_", app.input)),
+        Mode::Edit => ("Edit", format!("{}_", app.input)),
+        Mode::Normal | Mode::ConfirmDelete => ("Filter", app.filter.clone()),
+    };
+    frame.render_widget(
+        Paragraph::new(bar_text).block(Block::bordered().title(bar_title)),
+        bar,
+    );
+    frame.render_widget(Paragraph::new(app.status.as_str()), status);
+}
+
+/// Browse the database at `path` full screen until `q`.
+pub async fn run(path: &Path) -> Result<(), DatabaseError> {
+    let mut app = App::open(path).await?;
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut app, &mut terminal).await;
+    ratatui::restore();
+    result
+}
+
+async fn event_loop(app: &mut App, terminal: &mut DefaultTerminal) -> Result<(), DatabaseError> {
+    while !app.quit {
+        terminal.draw(|frame| draw(frame, app))?;
+        if let Event::Key(key) = event::read()? {
+            // some terminals also report releases
+            if key.kind == KeyEventKind::Press {
+                app.handle_key(key).await;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+    use serde_json::json;
+
+    use super::*;
+    use cargobase::{Column, Columns, Table};
+
+    async fn setup_app() -> (tempfile::TempDir, App) {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Database::new(dir.path().join("db").to_str().unwrap()).await;
+        let mut users = Table::new(
+            "users".to_string(),
+            Columns::new(vec![
+                Column::new("id", true),
+                Column::new("name", true),
+                Column::new("age", false),
+            ]),
+        );
+        db.add_table(&mut users).await.unwrap();
+        users
+            .add_row(
+                &mut db,
+                json!([
+                    {"id": "1", "name": "John", "age": 42},
+                    {"id": "2", "name": "Jane", "age": 37},
+                ]),
+            )
+            .await;
+
+        let app = App::open(&dir.path().join("db.json")).await.unwrap();
+        (dir, app)
+    }
+
+    async fn press(app: &mut App, keys: &str) {
+        for c in keys.chars() {
+            let code = match c {
+                '\n' => KeyCode::Enter,
+                '\t' => KeyCode::Tab,
+                c => KeyCode::Char(c),
+            };
+            app.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+                .await;
+        }
+    }
+
+    fn screen(app: &mut App) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(80, 20)).unwrap();
+        terminal.draw(|frame| draw(frame, app)).unwrap();
+        let buffer = terminal.backend().buffer();
+        buffer
+            .content()
+            .chunks(buffer.area.width as usize)
+            .map(|line| line.iter().map(|cell| cell.symbol()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[tokio::test]
+    async fn test_draw() {
+        let (_dir, mut app) = setup_app().await;
+        let screen = screen(&mut app);
+
+        assert!(screen.contains("users (2 rows)"));
+        assert!(screen.contains("id name age"));
+        assert!(screen.contains("1  John 42"));
+        assert!(screen.contains("\"name\": \"John\""));
+    }
+
+    #[tokio::test]
+    async fn test_filter() {
+        let (_dir, mut app) = setup_app().await;
+
+        press(&mut app, "/age < 40\n").await;
+        assert_eq!(app.mode, Mode::Normal);
+        assert_eq!(
+            app.rows,
+            vec![json!({"id": "2", "name": "Jane", "age": 37})]
+        );
+
+        press(&mut app, "/").await;
+        app.handle_key(KeyEvent::new(KeyCode::Char('u'), KeyModifiers::CONTROL))
+            .await;
+        press(&mut app, "age ~ 1\n").await;
+        assert_eq!(app.mode, Mode::Filter);
+        assert!(app.status.starts_with("error:"));
+        assert_eq!(app.filter, "age < 40");
+    }
+
+    #[tokio::test]
+    async fn test_edit_and_delete() {
+        let (dir, mut app) = setup_app().await;
+
+        press(&mut app, "\tj").await;
+        assert_eq!(app.selected_id().as_deref(), Some("2"));
+
+        // the row comes up without its key, so saving it unchanged works
+        press(&mut app, "e").await;
+        assert!(!app.input.contains("\"id\""));
+        press(&mut app, "\n").await;
+        assert_eq!(app.mode, Mode::Normal);
+        assert_eq!(app.status, "Row `2` updated");
+
+        press(&mut app, "e").await;
+        app.input.clear();
+        press(&mut app, "{\"id\": \"9\"}\n").await;
+        assert_eq!(app.mode, Mode::Edit);
+        assert!(app.status.contains("cannot be updated"));
+
+        app.input.clear();
+        press(&mut app, "{\"age\": 38}\n").await;
+        assert_eq!(app.mode, Mode::Normal);
+        let db = Database::open(dir.path().join("db.json")).await.unwrap();
+        assert_eq!(db.get_table("users").unwrap().rows["2"].data["age"], 38);
+
+        press(&mut app, "dn").await;
+        assert_eq!(app.rows.len(), 2);
+        press(&mut app, "dy").await;
+        assert_eq!(app.rows.len(), 1);
+        assert_eq!(app.status, "Row `2` deleted");
+    }
+
+    #[tokio::test]
+    async fn test_edit_leaves_out_timestamps() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Database::new(dir.path().join("db").to_str().unwrap()).await;
+        let mut notes = Table::new(
+            "notes".to_string(),
+            Columns::new(vec![Column::new("id", true), Column::new("text", false)]),
+        )
+        .with_timestamps();
+        db.add_table(&mut notes).await.unwrap();
+        notes
+            .add_row(&mut db, json!({"id": "1", "text": "hello"}))
+            .await;
+
+        let mut app = App::open(&dir.path().join("db.json")).await.unwrap();
+        press(&mut app, "\te").await;
+        assert_eq!(app.input, r#"{"text":"hello"}"#);
+        press(&mut app, "\n").await;
+        assert_eq!(app.status, "Row `1` updated");
+    }
+}
@@ -0,0 +1,380 @@
+use std::collections::HashMap;
+
+use serde_json::{Map, Value};
+
+use super::timestamps::{CREATED_AT, UPDATED_AT};
+use crate::database_operations::hooks;
+use crate::{Column, Database, DatabaseError, Row, Table};
+
+impl Table {
+    /// Add `column` and store `default` in every existing row. A `null`
+    /// default falls back on the column's own default, and without one
+    /// leaves the rows without the column, which a required column only
+    /// allows on an empty table.
+    pub fn add_column(&mut self, column: Column, default: Value) -> Result<(), DatabaseError> {
+        let default = match (default, &column.default) {
+            (Value::Null, Some(declared)) => declared.clone(),
+            (default, _) => default,
+        };
+        if self.has_column(&column.name) {
+            return Err(DatabaseError::InvalidOperation(format!(
+                "column `{}` already exists",
+                column.name
+            )));
+        }
+        let store = !default.is_null() || (column.required && column.nullable);
+        if !store && column.required && !self.rows.is_empty() {
+            return Err(DatabaseError::ColumnRequiredError(format!(
+                "Column '{}' is required, existing rows need a default.",
+                column.name
+            )));
+        }
+        if !default.is_null() && !column.column_type.accepts(&default) {
+            return Err(DatabaseError::InvalidData(format!(
+                "the default of column `{}` is not of type {:?}",
+                column.name, column.column_type
+            )));
+        }
+
+        let rows = self.rewrite_rows(|data| {
+            if store {
+                data.entry(column.name.clone())
+                    .or_insert_with(|| default.clone());
+            }
+        })?;
+        self.columns.0.push(column);
+        self.rows = rows;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Remove the column `name` and its value from every row. Key and
+    /// timestamp columns cannot be dropped.
+    pub fn drop_column(&mut self, name: &str) -> Result<(), DatabaseError> {
+        if self.is_key_column(name) {
+            return Err(DatabaseError::InvalidOperation(format!(
+                "the key column `{}` cannot be dropped",
+                name
+            )));
+        }
+        self.check_alterable(name)?;
+
+        let rows = self.rewrite_rows(|data| {
+            data.remove(name);
+        })?;
+        self.columns.0.retain(|column| column.name != name);
+        self.rows = rows;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Rename the column `old` to `new` in the columns, every row and the
+    /// primary key. Timestamp columns cannot be renamed.
+    pub fn rename_column(&mut self, old: &str, new: &str) -> Result<(), DatabaseError> {
+        self.check_alterable(old)?;
+        if self.has_column(new) || (self.timestamps && [CREATED_AT, UPDATED_AT].contains(&new)) {
+            return Err(DatabaseError::InvalidOperation(format!(
+                "column `{}` already exists",
+                new
+            )));
+        }
+
+        let rows = self.rewrite_rows(|data| {
+            if let Some(value) = data.remove(old) {
+                data.insert(new.to_string(), value);
+            }
+        })?;
+        for column in self.columns.0.iter_mut().filter(|c| c.name == old) {
+            column.name = new.to_string();
+        }
+        // keys are built from the values, so rows keep theirs
+        for key in self.primary_key.iter_mut().filter(|key| *key == old) {
+            *key = new.to_string();
+        }
+        self.rows = rows;
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn has_column(&self, name: &str) -> bool {
+        self.columns.0.iter().any(|column| column.name == name)
+    }
+
+    fn check_alterable(&self, name: &str) -> Result<(), DatabaseError> {
+        if !self.has_column(name) {
+            return Err(DatabaseError::InvalidOperation(format!(
+                "column `{}` does not exist",
+                name
+            )));
+        }
+        if self.timestamps && [CREATED_AT, UPDATED_AT].contains(&name) {
+            return Err(DatabaseError::InvalidOperation(format!(
+                "the timestamp column `{}` is kept by the table",
+                name
+            )));
+        }
+        Ok(())
+    }
+
+    // the rows with `rewrite` applied, which replace the table's rows only
+    // once every one of them still matches its schema
+    fn rewrite_rows(
+        &self,
+        rewrite: impl Fn(&mut Map<String, Value>),
+    ) -> Result<HashMap<String, Row>, DatabaseError> {
+        let mut rows = self.rows.clone();
+        for (row_id, row) in rows.iter_mut() {
+            let Value::Object(data) = &mut row.data else {
+                return Err(DatabaseError::InvalidData(format!(
+                    "row `{}` is not a JSON object",
+                    row_id
+                )));
+            };
+            rewrite(data);
+            self.check_schema(&row.data)?;
+        }
+        Ok(rows)
+    }
+}
+
+impl Database {
+    /// Add `column` to `table_name`, see `Table::add_column`.
+    pub async fn add_column(
+        &mut self,
+        table_name: &str,
+        column: Column,
+        default: Value,
+    ) -> Result<(), DatabaseError> {
+        self.alter_table(table_name, |table| table.add_column(column, default))?;
+        self.persist().await
+    }
+
+    /// Drop a column of `table_name` that no foreign key references, see
+    /// `Table::drop_column`.
+    pub async fn drop_column(&mut self, table_name: &str, name: &str) -> Result<(), DatabaseError> {
+        drop_column(&mut self.tables, table_name, name)?;
+        hooks::drop_column(&self.file_name, table_name, name);
+        self.persist().await
+    }
+
+    /// Rename a column of `table_name`, along with the foreign keys that
+    /// reference it and the function behind a generated column. See
+    /// `Table::rename_column`.
+    pub async fn rename_column(
+        &mut self,
+        table_name: &str,
+        old: &str,
+        new: &str,
+    ) -> Result<(), DatabaseError> {
+        rename_column(&mut self.tables, table_name, old, new)?;
+        hooks::rename_column(&self.file_name, table_name, old, new);
+        self.persist().await
+    }
+
+    fn alter_table(
+        &mut self,
+        table_name: &str,
+        alter: impl FnOnce(&mut Table) -> Result<(), DatabaseError>,
+    ) -> Result<(), DatabaseError> {
+        let table = self
+            .get_table_mut(table_name)
+            .ok_or_else(|| DatabaseError::TableNotFound(table_name.to_string()))?;
+        alter(table)
+    }
+}
+
+/// `Database::drop_column` on `tables`, which migrations also go through.
+pub(crate) fn drop_column(
+    tables: &mut HashMap<String, Table>,
+    table_name: &str,
+    name: &str,
+) -> Result<(), DatabaseError> {
+    let referenced_by = tables.values().find(|table| {
+        table.columns.0.iter().any(|column| {
+            column
+                .references
+                .as_ref()
+                .is_some_and(|fk| fk.table == table_name && fk.column == name)
+        })
+    });
+    if let Some(table) = referenced_by {
+        return Err(DatabaseError::InvalidOperation(format!(
+            "column `{}` is referenced by a foreign key of `{}`",
+            name, table.name
+        )));
+    }
+
+    tables
+        .get_mut(table_name)
+        .ok_or_else(|| DatabaseError::TableNotFound(table_name.to_string()))?
+        .drop_column(name)
+}
+
+/// `Database::rename_column` on `tables`, which migrations also go through.
+pub(crate) fn rename_column(
+    tables: &mut HashMap<String, Table>,
+    table_name: &str,
+    old: &str,
+    new: &str,
+) -> Result<(), DatabaseError> {
+    tables
+        .get_mut(table_name)
+        .ok_or_else(|| DatabaseError::TableNotFound(table_name.to_string()))?
+        .rename_column(old, new)?;
+
+    for table in tables.values_mut() {
+        for column in &mut table.columns.0 {
+            if let Some(fk) = column
+                .references
+                .as_mut()
+                .filter(|fk| fk.table == table_name && fk.column == old)
+            {
+                fk.column = new.to_string();
+                table.dirty = true;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{setup_temp_db, ColumnType, Columns};
+
+    async fn setup_users_db() -> Database {
+        let mut db = setup_temp_db().await;
+        let mut users = Table::new(
+            "users".to_string(),
+            Columns::new(vec![Column::new("id", true), Column::new("name", true)]),
+        );
+        db.add_table(&mut users).await.unwrap();
+        users
+            .add_row(
+                &mut db,
+                json!([{"id": "1", "name": "Ann"}, {"id": "2", "name": "Bob"}]),
+            )
+            .await;
+        db
+    }
+
+    #[tokio::test]
+    async fn test_add_column() {
+        let mut db = setup_users_db().await;
+        let active = Column::new("active", true).with_type(ColumnType::Boolean);
+        db.add_column("users", active, json!(true)).await.unwrap();
+
+        let on_disk = Database::load_from_file(&db.file_name).await.unwrap();
+        let users = on_disk.get_table("users").unwrap();
+        assert_eq!(users.columns.0[2].name, "active");
+        assert_eq!(
+            users.rows["2"].data,
+            json!({"id": "2", "name": "Bob", "active": true})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_column_rejects_missing_values() {
+        let mut db = setup_users_db().await;
+        let before = db.tables.clone();
+
+        let required = db
+            .add_column("users", Column::new("email", true), Value::Null)
+            .await;
+        assert!(matches!(
+            required,
+            Err(DatabaseError::ColumnRequiredError(_))
+        ));
+        let mistyped = db
+            .add_column(
+                "users",
+                Column::new("age", false).with_type(ColumnType::Integer),
+                json!("ten"),
+            )
+            .await;
+        assert!(matches!(mistyped, Err(DatabaseError::InvalidData(_))));
+        let existing = db
+            .add_column("users", Column::new("name", false), json!(""))
+            .await;
+        assert!(existing.is_err());
+        assert_eq!(db.tables, before);
+    }
+
+    #[tokio::test]
+    async fn test_add_column_uses_declared_default() {
+        let mut db = setup_users_db().await;
+        let role = Column::new("role", true).with_default("member");
+        db.add_column("users", role, Value::Null).await.unwrap();
+
+        let users = db.get_table("users").unwrap();
+        assert_eq!(users.rows["1"].data["role"], json!("member"));
+    }
+
+    #[tokio::test]
+    async fn test_drop_column() {
+        let mut db = setup_users_db().await;
+        db.drop_column("users", "name").await.unwrap();
+
+        let users = db.get_table("users").unwrap();
+        assert_eq!(users.columns, Columns::new(vec![Column::new("id", true)]));
+        assert_eq!(users.rows["1"].data, json!({"id": "1"}));
+        assert!(db.drop_column("users", "id").await.is_err());
+        assert!(db.drop_column("users", "missing").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rename_column() {
+        let mut db = setup_users_db().await;
+        let mut posts = Table::new(
+            "posts".to_string(),
+            Columns::new(vec![
+                Column::new("id", true),
+                Column::new("author", true).references("users", "id"),
+            ]),
+        );
+        db.add_table(&mut posts).await.unwrap();
+
+        db.rename_column("users", "name", "full_name")
+            .await
+            .unwrap();
+        db.rename_column("users", "id", "user_id").await.unwrap();
+
+        let users = db.get_table("users").unwrap();
+        assert_eq!(users.primary_key(), ["user_id"]);
+        assert_eq!(
+            users.rows["1"].data,
+            json!({"user_id": "1", "full_name": "Ann"})
+        );
+        let fk = db.get_table("posts").unwrap().columns.0[1]
+            .references
+            .clone();
+        assert_eq!(fk.unwrap().column, "user_id");
+        assert!(db.drop_column("users", "user_id").await.is_err());
+        assert!(db
+            .rename_column("users", "full_name", "user_id")
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_alter_checks_schema() {
+        let mut db = setup_users_db().await;
+        db.set_table_schema("users", Some(json!({"required": ["name"]})))
+            .await
+            .unwrap();
+        let before = db.tables.clone();
+
+        let result = db.rename_column("users", "name", "full_name").await;
+        assert!(matches!(result, Err(DatabaseError::SchemaViolation(_))));
+        assert_eq!(db.tables, before);
+    }
+
+    #[test]
+    fn test_timestamp_columns_are_kept() {
+        let mut table = Table::new("notes".to_string(), Columns::new(vec![])).with_timestamps();
+        assert!(table.drop_column(CREATED_AT).is_err());
+        assert!(table.rename_column(UPDATED_AT, "changed").is_err());
+    }
+}
@@ -1,14 +1,106 @@
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use serde_reflection::{ContainerFormat, Named, Tracer, TracerConfig};
+use serde_reflection::{ContainerFormat, Format, Named, Registry, Tracer, TracerConfig};
 
 use crate::DatabaseError;
 
+/// The JSON type stored in a column. `Any` columns accept every value.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+pub enum ColumnType {
+    #[default]
+    Any,
+    Text,
+    Integer,
+    Float,
+    Boolean,
+    Array,
+    Object,
+}
+
+impl ColumnType {
+    // map a traced serde format onto the JSON value it serializes to
+    fn from_format(format: &Format, registry: &Registry) -> Self {
+        match format {
+            Format::Bool => ColumnType::Boolean,
+            Format::I8
+            | Format::I16
+            | Format::I32
+            | Format::I64
+            | Format::I128
+            | Format::U8
+            | Format::U16
+            | Format::U32
+            | Format::U64
+            | Format::U128 => ColumnType::Integer,
+            Format::F32 | Format::F64 => ColumnType::Float,
+            Format::Char | Format::Str => ColumnType::Text,
+            Format::Bytes | Format::Seq(_) | Format::Tuple(_) | Format::TupleArray { .. } => {
+                ColumnType::Array
+            }
+            Format::Map { .. } => ColumnType::Object,
+            Format::Option(inner) => ColumnType::from_format(inner, registry),
+            Format::TypeName(name) => match registry.get(name) {
+                Some(ContainerFormat::Struct(_)) => ColumnType::Object,
+                Some(ContainerFormat::TupleStruct(_)) => ColumnType::Array,
+                Some(ContainerFormat::NewTypeStruct(inner)) => {
+                    ColumnType::from_format(inner, registry)
+                }
+                _ => ColumnType::Any,
+            },
+            Format::Unit | Format::Variable(_) => ColumnType::Any,
+        }
+    }
+
+    /// Whether `value` is of this type.
+    pub(crate) fn accepts(self, value: &Value) -> bool {
+        match self {
+            ColumnType::Any => true,
+            ColumnType::Text => value.is_string(),
+            ColumnType::Integer => value.is_i64() || value.is_u64(),
+            ColumnType::Float => value.is_number(),
+            ColumnType::Boolean => value.is_boolean(),
+            ColumnType::Array => value.is_array(),
+            ColumnType::Object => value.is_object(),
+        }
+    }
+}
+
+/// A column whose values are row ids of another table.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub struct ForeignKey {
+    pub table: String,
+    pub column: String,
+}
+
+/// A column whose value a function registered on the database produces.
+/// Only the declaration is saved, the function is registered again by each
+/// program that opens the database.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+pub enum Generated {
+    /// Filled in rows inserted without it, see `Database::default_with`.
+    Default,
+    /// Derived from the rest of the row on every insert and update, see
+    /// `Database::computed_column`.
+    Computed,
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct Column {
     pub name: String,
     pub required: bool,
+    #[serde(default)]
+    pub column_type: ColumnType,
+    /// The column may hold `null` even when it is required.
+    #[serde(default)]
+    pub nullable: bool,
+    #[serde(default)]
+    pub references: Option<ForeignKey>,
+    /// Stored in rows inserted without this column.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generated: Option<Generated>,
 }
 
 impl Column {
@@ -16,8 +108,38 @@ impl Column {
         Column {
             name: name.to_string(),
             required,
+            column_type: ColumnType::Any,
+            nullable: false,
+            references: None,
+            default: None,
+            generated: None,
         }
     }
+
+    pub fn with_type(mut self, column_type: ColumnType) -> Self {
+        self.column_type = column_type;
+        self
+    }
+
+    pub fn nullable(mut self) -> Self {
+        self.nullable = true;
+        self
+    }
+
+    /// Fill this column with `value` in rows inserted without it.
+    pub fn with_default(mut self, value: impl Into<Value>) -> Self {
+        self.default = Some(value.into());
+        self
+    }
+
+    /// Declare this column as a foreign key to `column` of `table`.
+    pub fn references(mut self, table: &str, column: &str) -> Self {
+        self.references = Some(ForeignKey {
+            table: table.to_string(),
+            column: column.to_string(),
+        });
+        self
+    }
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
@@ -51,7 +173,15 @@ impl Columns {
         let columns = if let ContainerFormat::Struct(fields) = container {
             fields
                 .iter()
-                .map(|Named { name, .. }| Column::new(name, required))
+                .map(|Named { name, value }| {
+                    let column = Column::new(name, required)
+                        .with_type(ColumnType::from_format(value, &registry));
+                    if matches!(value, Format::Option(_)) {
+                        column.nullable()
+                    } else {
+                        column
+                    }
+                })
                 .collect()
         } else {
             vec![]
@@ -60,6 +190,19 @@ impl Columns {
         Columns(columns)
     }
 
+    /// Fill the columns `row` is missing with their defaults.
+    pub fn fill_defaults(&self, row: &mut Value) {
+        if let Value::Object(data) = row {
+            for column in &self.0 {
+                if let Some(default) = &column.default {
+                    if !data.contains_key(&column.name) {
+                        data.insert(column.name.clone(), default.clone());
+                    }
+                }
+            }
+        }
+    }
+
     // validate the columns
     pub fn validate(&self, row_data: Value) -> Result<(), DatabaseError> {
         if let Value::Object(data) = row_data {
@@ -227,6 +370,81 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_column_with_type() {
+        let column = Column::new("age", false).with_type(ColumnType::Integer);
+        assert_eq!(column.column_type, ColumnType::Integer);
+        assert_eq!(Column::new("age", false).column_type, ColumnType::Any);
+    }
+
+    #[test]
+    fn test_column_references() {
+        let column = Column::new("user_id", true).references("users", "id");
+        assert_eq!(
+            column.references,
+            Some(ForeignKey {
+                table: "users".to_string(),
+                column: "id".to_string()
+            })
+        );
+        assert!(!column.nullable);
+    }
+
+    #[test]
+    fn test_fill_defaults() {
+        let columns = Columns::new(vec![
+            Column::new("id", true),
+            Column::new("status", true).with_default("active"),
+            Column::new("tags", false).with_default(json!([])),
+        ]);
+
+        let mut row = json!({"id": "1", "tags": null});
+        columns.fill_defaults(&mut row);
+        assert_eq!(row, json!({"id": "1", "status": "active", "tags": null}));
+        assert!(columns.validate(row).is_ok());
+    }
+
+    #[test]
+    fn test_columns_from_struct_types() {
+        #[derive(Serialize, Deserialize, Default)]
+        struct Address {
+            city: String,
+        }
+
+        #[derive(Serialize, Deserialize, Default)]
+        struct TestData {
+            id: String,
+            age: u8,
+            score: f64,
+            active: bool,
+            nickname: Option<String>,
+            tags: Vec<String>,
+            address: Address,
+        }
+
+        let columns = Columns::from_struct::<TestData>(true);
+        let types: Vec<ColumnType> = columns.0.iter().map(|c| c.column_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                ColumnType::Text,
+                ColumnType::Integer,
+                ColumnType::Float,
+                ColumnType::Boolean,
+                ColumnType::Text,
+                ColumnType::Array,
+                ColumnType::Object,
+            ]
+        );
+        let nullable: Vec<&str> = columns
+            .0
+            .iter()
+            .filter(|c| c.nullable)
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(nullable, vec!["nickname"]);
+    }
+
     #[test]
     fn test_columns_from_struct() {
         #[derive(Serialize, Deserialize, Default)]
@@ -0,0 +1,358 @@
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+
+use serde_json::{json, Map, Value};
+
+use crate::{Column, ColumnType, Database, DatabaseError, Table};
+
+const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// One way a row fails a table's JSON Schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+    /// JSON pointer to the offending value, `""` for the row itself.
+    pub pointer: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.pointer, self.message)
+    }
+}
+
+// `Table.schema` compiled on first use; clones of a table share it
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CompiledSchema(Arc<OnceLock<Result<jsonschema::Validator, String>>>);
+
+fn compile(schema: &Value) -> Result<jsonschema::Validator, String> {
+    jsonschema::validator_for(schema).map_err(|e| format!("Invalid JSON Schema: {}", e))
+}
+
+impl Table {
+    /// A JSON Schema (draft 2020-12) describing one row of this table: an
+    /// object with a property per column, the required columns listed in
+    /// `required` and no other properties allowed.
+    pub fn json_schema(&self) -> Value {
+        let mut schema = self.row_schema();
+        schema["$schema"] = json!(DIALECT);
+        schema
+    }
+
+    /// Require every row added or updated from now on to match `schema`, on
+    /// top of `Columns::validate`. Rows already in the table are not checked.
+    pub fn with_schema(mut self, schema: Value) -> Result<Self, DatabaseError> {
+        self.set_schema(Some(schema))?;
+        Ok(self)
+    }
+
+    /// The JSON Schema attached with `with_schema`, if any.
+    pub fn schema(&self) -> Option<&Value> {
+        self.schema.as_ref()
+    }
+
+    pub(crate) fn set_schema(&mut self, schema: Option<Value>) -> Result<(), DatabaseError> {
+        let compiled = CompiledSchema::default();
+        if let Some(schema) = &schema {
+            let validator = compile(schema).map_err(DatabaseError::InvalidData)?;
+            let _ = compiled.0.set(Ok(validator));
+        }
+        self.schema = schema;
+        self.compiled_schema = compiled;
+        Ok(())
+    }
+
+    /// Check `data` against the table's JSON Schema, collecting every
+    /// violation rather than stopping at the first.
+    pub(crate) fn check_schema(&self, data: &Value) -> Result<(), DatabaseError> {
+        let Some(schema) = &self.schema else {
+            return Ok(());
+        };
+        let validator = self
+            .compiled_schema
+            .0
+            .get_or_init(|| compile(schema))
+            .as_ref()
+            .map_err(|e| DatabaseError::InvalidData(e.clone()))?;
+
+        let violations: Vec<SchemaViolation> = validator
+            .iter_errors(data)
+            .map(|error| SchemaViolation {
+                pointer: error.instance_path.as_str().to_string(),
+                message: error.to_string(),
+            })
+            .collect();
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(DatabaseError::SchemaViolation(violations))
+        }
+    }
+
+    fn row_schema(&self) -> Value {
+        let properties: Map<String, Value> = self
+            .columns
+            .0
+            .iter()
+            .map(|column| (column.name.clone(), column_schema(column)))
+            .collect();
+        let required: Vec<&str> = self
+            .columns
+            .0
+            .iter()
+            .filter(|column| column.required)
+            .map(|column| column.name.as_str())
+            .collect();
+
+        json!({
+            "title": self.name,
+            "type": "object",
+            "properties": properties,
+            "required": required,
+            "additionalProperties": false,
+        })
+    }
+}
+
+impl Database {
+    /// A JSON Schema for the whole database, as an object with an array of
+    /// rows per table. Each table's row schema is under `$defs`.
+    pub fn json_schema(&self) -> Value {
+        let mut defs = Map::new();
+        let mut properties = Map::new();
+        for (name, table) in &self.tables {
+            defs.insert(name.clone(), table.row_schema());
+            properties.insert(
+                name.clone(),
+                json!({
+                    "type": "array",
+                    "items": { "$ref": format!("#/$defs/{}", pointer_escape(name)) },
+                }),
+            );
+        }
+
+        json!({
+            "$schema": DIALECT,
+            "title": self.name,
+            "type": "object",
+            "properties": properties,
+            "additionalProperties": false,
+            "$defs": defs,
+        })
+    }
+}
+
+impl Database {
+    /// Attach a JSON Schema to an existing table, or remove it with `None`.
+    /// See `Table::with_schema`.
+    pub async fn set_table_schema(
+        &mut self,
+        table_name: &str,
+        schema: Option<Value>,
+    ) -> Result<(), DatabaseError> {
+        let table = self
+            .get_table_mut(table_name)
+            .ok_or_else(|| DatabaseError::TableNotFound(table_name.to_string()))?;
+        table.set_schema(schema)?;
+        table.dirty = true;
+        self.persist().await
+    }
+}
+
+fn column_schema(column: &Column) -> Value {
+    let json_type = match column.column_type {
+        // any JSON value is allowed
+        ColumnType::Any => return json!({}),
+        ColumnType::Text => "string",
+        ColumnType::Integer => "integer",
+        ColumnType::Float => "number",
+        ColumnType::Boolean => "boolean",
+        ColumnType::Array => "array",
+        ColumnType::Object => "object",
+    };
+    if column.nullable {
+        json!({ "type": [json_type, "null"] })
+    } else {
+        json!({ "type": json_type })
+    }
+}
+
+// table names become JSON pointer segments in `$ref`
+fn pointer_escape(segment: &str) -> String {
+    segment.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::{setup_temp_db, Columns};
+
+    #[derive(Serialize, Deserialize, Default)]
+    struct User {
+        id: String,
+        age: u32,
+        score: f64,
+        nickname: Option<String>,
+        tags: Vec<String>,
+    }
+
+    fn people_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string", "pattern": "^[A-Z]" },
+                "age": { "type": "integer", "minimum": 18 },
+                "role": { "enum": ["admin", "member"] },
+                "address": {
+                    "type": "object",
+                    "properties": { "zip": { "type": "string", "minLength": 5 } },
+                    "required": ["zip"],
+                },
+            },
+        })
+    }
+
+    async fn setup_people() -> (Database, Table) {
+        let mut db = setup_temp_db().await;
+        let mut people = Table::new(
+            "people".to_string(),
+            Columns::new(vec![
+                Column::new("id", true),
+                Column::new("name", true),
+                Column::new("age", false),
+                Column::new("role", false),
+                Column::new("address", false),
+            ]),
+        )
+        .with_schema(people_schema())
+        .unwrap();
+        db.add_table(&mut people).await.unwrap();
+        (db, people)
+    }
+
+    #[tokio::test]
+    async fn test_schema_reports_every_violation() {
+        let (db, _) = setup_people().await;
+        let table = db.get_table("people").unwrap();
+
+        let result = table.check_schema(&json!({
+            "id": "1",
+            "name": "john",
+            "age": 12,
+            "role": "owner",
+            "address": { "zip": "123" },
+        }));
+
+        let Err(DatabaseError::SchemaViolation(violations)) = result else {
+            panic!("expected schema violations, got {:?}", result);
+        };
+        let mut pointers: Vec<&str> = violations.iter().map(|v| v.pointer.as_str()).collect();
+        pointers.sort();
+        assert_eq!(pointers, vec!["/address/zip", "/age", "/name", "/role"]);
+
+        assert!(table
+            .check_schema(&json!({"id": "1", "name": "John", "age": 30}))
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_schema_checked_on_insert_and_update() {
+        let (mut db, mut people) = setup_people().await;
+
+        people
+            .add_row(&mut db, json!({"id": "1", "name": "john"}))
+            .await;
+        assert_eq!(db.count_rows("people").unwrap(), 0);
+
+        people
+            .add_row(&mut db, json!({"id": "1", "name": "John", "age": 30}))
+            .await;
+        assert_eq!(db.count_rows("people").unwrap(), 1);
+
+        let added = db
+            .add_row()
+            .from("people")
+            .data_from_struct(json!({"id": "2", "name": "Jane", "age": 3}))
+            .execute_add()
+            .await;
+        assert!(matches!(added, Err(DatabaseError::SchemaViolation(_))));
+
+        let updated = db
+            .update_row()
+            .from("people")
+            .data(json!({"age": 10}))
+            .where_eq::<Value>("id", "1")
+            .await;
+        assert!(matches!(updated, Err(DatabaseError::SchemaViolation(_))));
+        let on_disk = Database::load_from_file(&db.file_name).await.unwrap();
+        assert_eq!(on_disk.tables["people"].rows["1"].data["age"], json!(30));
+        // the schema is stored with the table
+        assert_eq!(on_disk.tables["people"].schema(), Some(&people_schema()));
+    }
+
+    #[tokio::test]
+    async fn test_set_table_schema() {
+        let mut db = setup_temp_db().await;
+
+        let invalid = db
+            .set_table_schema("TestTable", Some(json!({"type": "nonsense"})))
+            .await;
+        assert!(matches!(invalid, Err(DatabaseError::InvalidData(_))));
+
+        db.set_table_schema("TestTable", Some(json!({"required": ["name"]})))
+            .await
+            .unwrap();
+        let table = db.get_table("TestTable").unwrap();
+        assert!(table.check_schema(&json!({"id": "1"})).is_err());
+
+        db.set_table_schema("TestTable", None).await.unwrap();
+        let table = db.get_table("TestTable").unwrap();
+        assert!(table.check_schema(&json!({"id": "1"})).is_ok());
+    }
+
+    #[test]
+    fn test_table_json_schema() {
+        let mut columns = Columns::from_struct::<User>(true);
+        columns.0.push(Column::new("extra", false));
+        let table = Table::new("users".to_string(), columns);
+
+        assert_eq!(
+            table.json_schema(),
+            json!({
+                "$schema": DIALECT,
+                "title": "users",
+                "type": "object",
+                "properties": {
+                    "id": { "type": "string" },
+                    "age": { "type": "integer" },
+                    "score": { "type": "number" },
+                    "nickname": { "type": ["string", "null"] },
+                    "tags": { "type": "array" },
+                    "extra": {},
+                },
+                "required": ["id", "age", "score", "nickname", "tags"],
+                "additionalProperties": false,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_database_json_schema() {
+        let mut db = setup_temp_db().await;
+        let mut users = Table::new("a/b".to_string(), Columns::from_struct::<User>(true));
+        db.add_table(&mut users).await.unwrap();
+
+        let schema = db.json_schema();
+
+        assert_eq!(schema["$schema"], json!(DIALECT));
+        assert_eq!(
+            schema["properties"]["a/b"],
+            json!({ "type": "array", "items": { "$ref": "#/$defs/a~1b" } })
+        );
+        assert_eq!(schema["$defs"]["a/b"], users.row_schema());
+        assert_eq!(schema["$defs"]["TestTable"]["title"], json!("TestTable"));
+        assert!(schema["$defs"]["TestTable"].get("$schema").is_none());
+    }
+}
@@ -0,0 +1,350 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{ColumnType, Database, DatabaseError, Table};
+
+/// How a table fills in the key of rows inserted without one. Rows that
+/// bring their own key keep it whatever the strategy. Keys spanning several
+/// columns are never generated.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+pub enum KeyStrategy {
+    /// Every row has to bring its own key.
+    #[default]
+    Supplied,
+    /// `1`, `2`, ... counting up from the highest key handed out so far,
+    /// stored as numbers in `Integer` columns and as strings otherwise. Keys
+    /// of deleted rows are not reused.
+    AutoIncrement,
+    /// A random UUID v4.
+    Uuid,
+    /// A UUID v7, which sorts in insertion order.
+    TimeOrdered,
+}
+
+pub(crate) fn default_primary_key() -> Vec<String> {
+    vec!["id".to_string()]
+}
+
+/// The key under which `Table::rows` stores a row whose primary key columns
+/// hold `values`. A single string is its own key and a number is written
+/// out, so `"7"` and `7` are the same key. Composite keys are the values as
+/// a JSON array, e.g. `["acme",7]`. Other values cannot be keys.
+pub(crate) fn encode_key(values: &[&Value]) -> Option<String> {
+    match values {
+        [] => None,
+        [Value::String(key)] => Some(key.clone()),
+        [Value::Number(key)] => Some(key.to_string()),
+        [_] => None,
+        _ if values.iter().all(|v| v.is_string() || v.is_number()) => {
+            serde_json::to_string(values).ok()
+        }
+        _ => None,
+    }
+}
+
+/// The key of the row holding `data` in a table keyed by `primary_key`.
+pub(crate) fn key_of(primary_key: &[String], data: &Value) -> Option<String> {
+    let values = primary_key
+        .iter()
+        .map(|column| data.get(column))
+        .collect::<Option<Vec<_>>>()?;
+    encode_key(&values)
+}
+
+impl Table {
+    /// Key rows by `columns` instead of `id`. Key values have to be strings
+    /// or numbers and cannot be changed by updates.
+    pub fn with_primary_key(mut self, columns: &[&str]) -> Self {
+        if !columns.is_empty() {
+            self.primary_key = columns.iter().map(|c| c.to_string()).collect();
+        }
+        self
+    }
+
+    pub fn primary_key(&self) -> &[String] {
+        &self.primary_key
+    }
+
+    pub fn is_key_column(&self, column: &str) -> bool {
+        self.primary_key.iter().any(|key| key == column)
+    }
+
+    /// The key of the row holding `data`, `None` when a key column is
+    /// missing or does not hold a string or number.
+    pub fn row_key(&self, data: &Value) -> Option<String> {
+        key_of(&self.primary_key, data)
+    }
+
+    /// Check that `data` still has the key `row_id` it is stored under.
+    pub(crate) fn check_key(&self, row_id: &str, data: &Value) -> Result<(), DatabaseError> {
+        if self.row_key(data).as_deref() == Some(row_id) {
+            Ok(())
+        } else {
+            Err(DatabaseError::InvalidData(format!(
+                "The primary key ({}) of a row cannot be updated.",
+                self.primary_key.join(", ")
+            )))
+        }
+    }
+
+    /// Generate the key of rows inserted without one with `strategy`.
+    pub fn with_key_strategy(mut self, strategy: KeyStrategy) -> Self {
+        self.key_strategy = strategy;
+        self
+    }
+
+    pub fn key_strategy(&self) -> KeyStrategy {
+        self.key_strategy
+    }
+
+    /// The key of `row`, generated and written into the row when it has
+    /// none and the table's strategy allows it. `None` when the row needs a
+    /// key but has none, or holds one of the wrong type.
+    pub(crate) fn assign_key(&mut self, row: &mut Value) -> Option<String> {
+        let missing = match self.primary_key.as_slice() {
+            [column] => matches!(row.get(column), None | Some(Value::Null)).then(|| column.clone()),
+            _ => None,
+        };
+        let Some(column) = missing else {
+            let key = self.row_key(row)?;
+            // keep the counter ahead of numeric keys given by the caller
+            if let Ok(n) = key.parse::<u64>() {
+                self.last_id = self.last_id.max(n);
+            }
+            return Some(key);
+        };
+
+        let value = match self.key_strategy {
+            KeyStrategy::Supplied => return None,
+            KeyStrategy::AutoIncrement => {
+                loop {
+                    self.last_id += 1;
+                    // rows added before the strategy was set may hold the key
+                    if !self.rows.contains_key(&self.last_id.to_string()) {
+                        break;
+                    }
+                }
+                let numeric = self
+                    .columns
+                    .0
+                    .iter()
+                    .any(|c| c.name == column && c.column_type == ColumnType::Integer);
+                if numeric {
+                    Value::from(self.last_id)
+                } else {
+                    Value::String(self.last_id.to_string())
+                }
+            }
+            KeyStrategy::Uuid => Value::String(Uuid::new_v4().to_string()),
+            KeyStrategy::TimeOrdered => Value::String(Uuid::now_v7().to_string()),
+        };
+        let key = encode_key(&[&value]);
+        row.as_object_mut()?.insert(column, value);
+        key
+    }
+}
+
+impl Database {
+    /// Whether `table` has a row whose primary key columns hold `key`, in
+    /// the order of `Table::primary_key`.
+    pub fn key_exists(&self, table: &str, key: &[Value]) -> bool {
+        let key = encode_key(&key.iter().collect::<Vec<_>>());
+        match (self.tables.get(table), key) {
+            (Some(table), Some(key)) => table.rows.contains_key(&key),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{setup_temp_db, Column, Columns, Database, FilterOp};
+
+    async fn setup_keyed_db(strategy: KeyStrategy) -> Database {
+        let mut db = setup_temp_db().await;
+        let mut table = Table::new(
+            "Items".to_string(),
+            Columns::new(vec![Column::new("id", true), Column::new("name", true)]),
+        )
+        .with_key_strategy(strategy);
+        db.add_table(&mut table).await.unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn test_auto_increment() {
+        let mut db = setup_keyed_db(KeyStrategy::AutoIncrement).await;
+
+        let first = db
+            .add_row()
+            .from("Items")
+            .data_from_struct(json!({"name": "a"}))
+            .execute_add()
+            .await
+            .unwrap();
+        let supplied = db
+            .add_row()
+            .from("Items")
+            .data_from_struct(json!({"id": "10", "name": "b"}))
+            .execute_add()
+            .await
+            .unwrap();
+        let next = db
+            .add_row()
+            .from("Items")
+            .data_from_struct(json!({"name": "c"}))
+            .execute_add()
+            .await
+            .unwrap();
+        assert_eq!((first.as_str(), supplied.as_str()), ("1", "10"));
+        assert_eq!(next, "11");
+
+        let row: Option<Value> = db
+            .get_single()
+            .from("Items")
+            .where_eq("id", "11")
+            .await
+            .unwrap();
+        assert_eq!(row, Some(json!({"id": "11", "name": "c"})));
+    }
+
+    #[tokio::test]
+    async fn test_add_row_returns_generated_ids() {
+        let mut db = setup_keyed_db(KeyStrategy::Uuid).await;
+        let mut table = db.get_table("Items").unwrap().clone();
+
+        let ids = table
+            .add_row(&mut db, json!([{"name": "a"}, {"name": "b"}]))
+            .await;
+
+        assert_eq!(ids.len(), 2);
+        assert!(ids.iter().all(|id| Uuid::parse_str(id).is_ok()));
+        let rows = &db.get_table("Items").unwrap().rows;
+        assert_eq!(rows[&ids[1]].data["id"], json!(ids[1]));
+    }
+
+    #[test]
+    fn test_time_ordered_ids_sort_by_insertion() {
+        let mut table = Table::new("Items".to_string(), Columns::new(vec![]))
+            .with_key_strategy(KeyStrategy::TimeOrdered);
+
+        let ids: Vec<String> = (0..3)
+            .map(|_| table.assign_key(&mut json!({})).unwrap())
+            .collect();
+        let mut sorted = ids.clone();
+        sorted.sort();
+        assert_eq!(ids, sorted);
+    }
+
+    #[tokio::test]
+    async fn test_sql_insert_generates_id() {
+        let db = setup_keyed_db(KeyStrategy::AutoIncrement).await;
+        db.execute_sql("INSERT INTO Items (name) VALUES ('a'), ('b')")
+            .await
+            .unwrap();
+
+        let rows = db.get_rows().from("Items").rows().await.unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                json!({"id": "1", "name": "a"}),
+                json!({"id": "2", "name": "b"})
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_composite_primary_key() {
+        let mut db = setup_temp_db().await;
+        let mut members = Table::new(
+            "members".to_string(),
+            Columns::new(vec![
+                Column::new("org_id", true),
+                Column::new("user_id", true),
+                Column::new("role", false),
+            ]),
+        )
+        .with_primary_key(&["org_id", "user_id"]);
+        db.add_table(&mut members).await.unwrap();
+
+        let ids = members
+            .add_row(
+                &mut db,
+                json!([
+                    {"org_id": "acme", "user_id": 1, "role": "admin"},
+                    {"org_id": "acme", "user_id": 2},
+                    {"org_id": "globex", "user_id": 1},
+                ]),
+            )
+            .await;
+        assert_eq!(ids[0], r#"["acme",1]"#);
+        assert!(db.key_exists("members", &[json!("globex"), json!(1)]));
+        assert!(!db.key_exists("members", &[json!("globex"), json!(2)]));
+
+        // the same key again is rejected
+        let ids = members
+            .add_row(&mut db, json!({"org_id": "acme", "user_id": 1}))
+            .await;
+        assert!(ids.is_empty());
+
+        let result = db
+            .update_row()
+            .from("members")
+            .data(json!({"user_id": 3}))
+            .filter("org_id", FilterOp::Eq, "globex")
+            .update_matching()
+            .await;
+        assert!(matches!(result, Err(DatabaseError::InvalidData(_))));
+    }
+
+    #[tokio::test]
+    async fn test_numeric_keys_and_foreign_keys() {
+        let mut db = setup_temp_db().await;
+        let mut users = Table::new(
+            "users".to_string(),
+            Columns::new(vec![
+                Column::new("user_id", true).with_type(ColumnType::Integer),
+                Column::new("name", true),
+            ]),
+        )
+        .with_primary_key(&["user_id"])
+        .with_key_strategy(KeyStrategy::AutoIncrement);
+        db.add_table(&mut users).await.unwrap();
+        let mut posts = Table::new("posts".to_string(), Columns::new(vec![]));
+        db.add_table(&mut posts).await.unwrap();
+
+        let ids = users.add_row(&mut db, json!({"name": "Ann"})).await;
+        assert_eq!(ids, vec!["1"]);
+        assert_eq!(
+            db.get_table("users").unwrap().rows["1"].data,
+            json!({"user_id": 1, "name": "Ann"})
+        );
+        assert!(db.record_exists("users", "1"));
+
+        let fk = Some(&[("users", "author")][..]);
+        assert!(posts
+            .add_row_with_fk(&mut db, json!({"id": "p1", "author": 1}), fk)
+            .await
+            .is_ok());
+        assert!(posts
+            .add_row_with_fk(&mut db, json!({"id": "p2", "author": 2}), fk)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_supplied_requires_id() {
+        let mut db = setup_keyed_db(KeyStrategy::Supplied).await;
+        let result = db
+            .add_row()
+            .from("Items")
+            .data_from_struct(json!({"name": "a"}))
+            .execute_add()
+            .await;
+        assert!(result.is_err());
+    }
+}
@@ -1,7 +1,13 @@
+pub mod alter;
 pub mod columns;
+pub mod json_schema;
+pub mod keys;
 pub mod row;
 pub mod table;
+pub mod timestamps;
 
-pub use columns::{Column, Columns};
+pub use columns::{Column, ColumnType, Columns, ForeignKey, Generated};
+pub use json_schema::SchemaViolation;
+pub use keys::KeyStrategy;
 pub use row::Row;
 pub use table::Table;
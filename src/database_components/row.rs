@@ -6,12 +6,22 @@ use uuid::Uuid;
 pub struct Row {
     pub _id: String, // uuid v4
     pub data: Value,
+    // kept on tables created `with_timestamps`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
 }
 
 impl Row {
     pub fn new(data: Value) -> Self {
         let _id = Uuid::new_v4().to_string();
-        Row { _id, data }
+        Row {
+            _id,
+            data,
+            created_at: None,
+            updated_at: None,
+        }
     }
 }
 
@@ -4,13 +4,59 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tracing;
 
-use crate::{Columns, Database, Row};
+use super::json_schema::CompiledSchema;
+use super::keys::{default_primary_key, encode_key};
+use crate::database_operations::hooks::TableHooks;
+use crate::{ChangeEvent, Columns, Database, DatabaseError, KeyStrategy, Operation, Row};
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+/// What `Table::insert_row` does with a row whose key is already taken.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum OnConflict {
+    Fail,
+    Replace,
+    Ignore,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Table {
     pub(crate) name: String,
     pub rows: HashMap<String, Row>, // Row ID -> Row
     pub columns: Columns,
+    // JSON Schema every row must match, see `Table::with_schema`
+    #[serde(default)]
+    pub(crate) schema: Option<Value>,
+    #[serde(skip)]
+    pub(crate) compiled_schema: CompiledSchema,
+    // see `Table::with_timestamps`
+    #[serde(default)]
+    pub(crate) timestamps: bool,
+    // columns whose values key `rows`, see `Table::with_primary_key`
+    #[serde(default = "default_primary_key")]
+    pub(crate) primary_key: Vec<String>,
+    #[serde(default)]
+    pub(crate) key_strategy: KeyStrategy,
+    // highest id handed out by `KeyStrategy::AutoIncrement`
+    #[serde(default)]
+    pub(crate) last_id: u64,
+    // set whenever the table changes, cleared once it has been written to disk
+    #[serde(skip)]
+    pub(crate) dirty: bool,
+    // changes waiting to be published to subscribers once they are saved
+    #[serde(skip)]
+    pub(crate) changes: Vec<ChangeEvent>,
+}
+
+// `dirty` and `changes` are bookkeeping, two tables with the same contents are equal
+impl PartialEq for Table {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.rows == other.rows
+            && self.columns == other.columns
+            && self.schema == other.schema
+            && self.timestamps == other.timestamps
+            && self.primary_key == other.primary_key
+            && self.key_strategy == other.key_strategy
+    }
 }
 
 impl Table {
@@ -19,16 +65,29 @@ impl Table {
             name,
             rows: HashMap::new(),
             columns,
+            schema: None,
+            compiled_schema: CompiledSchema::default(),
+            timestamps: false,
+            primary_key: default_primary_key(),
+            key_strategy: KeyStrategy::default(),
+            last_id: 0,
+            dirty: false,
+            changes: Vec::new(),
         }
     }
 
-    pub async fn add_row(&mut self, db: &mut Database, data: Value) {
+    /// Add one row, or every row of an array, and return their ids. Ids
+    /// missing from the data are generated by the table's `KeyStrategy`.
+    /// Errors are logged and nothing is returned for them.
+    pub async fn add_row(&mut self, db: &mut Database, data: Value) -> Vec<String> {
+        let hooks = db.hooks(&self.name);
         if let Some(table) = db.get_table_mut(&self.name) {
-            match table.process_data(data) {
-                Ok(_) => {
-                    if let Err(e) = db.save_to_file().await {
+            match table.process_data(data, hooks.as_deref()) {
+                Ok(ids) => {
+                    if let Err(e) = db.persist().await {
                         tracing::error!("Failed to save to file: {}", e);
                     }
+                    return ids;
                 }
                 Err(err) => {
                     tracing::error!("Error adding row(s): {}", err);
@@ -37,52 +96,135 @@ impl Table {
         } else {
             tracing::error!("Table {} not found", self.name);
         }
+        Vec::new()
     }
 
-    fn process_data(&mut self, data: Value) -> Result<(), String> {
-        if let Some(array) = data.as_array() {
-            self.add_multiple_rows(array)?;
-        } else {
-            self.add_single_row(data)?;
+    fn process_data(
+        &mut self,
+        data: Value,
+        hooks: Option<&TableHooks>,
+    ) -> Result<Vec<String>, DatabaseError> {
+        match data {
+            Value::Array(rows) => self.add_multiple_rows(rows, hooks),
+            data => Ok(vec![self.add_hooked_row(data, hooks)?]),
         }
-        Ok(())
     }
 
-    /*
-     * update this....
-     * automatucally convert to Value
-     * let _ = serde_json::to_value(&data).unwrap();
-     */
-    fn add_multiple_rows(&mut self, rows: &[Value]) -> Result<(), String> {
-        for row in rows {
-            self.add_single_row(row.clone())?;
+    // takes the rows by value so no row is cloned on the way in
+    fn add_multiple_rows(
+        &mut self,
+        rows: Vec<Value>,
+        hooks: Option<&TableHooks>,
+    ) -> Result<Vec<String>, DatabaseError> {
+        rows.into_iter()
+            .map(|row| self.add_hooked_row(row, hooks))
+            .collect()
+    }
+
+    fn add_hooked_row(
+        &mut self,
+        mut row: Value,
+        hooks: Option<&TableHooks>,
+    ) -> Result<String, DatabaseError> {
+        self.prepare_insert(&mut row, hooks)?;
+        self.add_single_row(row)
+    }
+
+    /// Fill in the defaults of a row about to be inserted and run the insert
+    /// hooks on it.
+    pub(crate) fn prepare_insert(
+        &self,
+        row: &mut Value,
+        hooks: Option<&TableHooks>,
+    ) -> Result<(), DatabaseError> {
+        self.columns.fill_defaults(row);
+        match hooks {
+            Some(hooks) => hooks.before_insert(row),
+            None => Ok(()),
         }
-        Ok(())
     }
 
-    fn add_single_row(&mut self, row: Value) -> Result<(), String> {
-        if let Some(row_id) = row.get("id").and_then(Value::as_str) {
-            if self.rows.contains_key(row_id) {
-                return Err(format!("Row with id '{}' already exists", row_id));
+    pub(crate) fn add_single_row(&mut self, mut row: Value) -> Result<String, DatabaseError> {
+        let row_id = self
+            .assign_key(&mut row)
+            .ok_or_else(|| self.missing_key(&row))?;
+        self.store_row(&row_id, row, OnConflict::Fail)?;
+        Ok(row_id)
+    }
+
+    /// Insert a row the way queries and imports do: fill in its defaults,
+    /// run the insert hooks, assign its key, check it against the columns
+    /// and the schema, then store it and record the change. `None` when
+    /// `OnConflict::Ignore` left the row out.
+    pub(crate) fn insert_row(
+        &mut self,
+        mut row: Value,
+        hooks: Option<&TableHooks>,
+        on_conflict: OnConflict,
+    ) -> Result<Option<String>, DatabaseError> {
+        self.prepare_insert(&mut row, hooks)?;
+        // the id may be required, generate it before validating
+        let row_id = self.assign_key(&mut row);
+        self.columns.validate(row.clone())?;
+        let row_id = row_id.ok_or_else(|| self.missing_key(&row))?;
+        Ok(self.store_row(&row_id, row, on_conflict)?.then_some(row_id))
+    }
+
+    // false when the row was left out for `OnConflict::Ignore`
+    fn store_row(
+        &mut self,
+        row_id: &str,
+        data: Value,
+        on_conflict: OnConflict,
+    ) -> Result<bool, DatabaseError> {
+        let op = match (self.rows.contains_key(row_id), on_conflict) {
+            (false, _) => Operation::Create,
+            (true, OnConflict::Replace) => Operation::Update,
+            (true, OnConflict::Ignore) => return Ok(false),
+            (true, OnConflict::Fail) => {
+                return Err(DatabaseError::InvalidData(format!(
+                    "Row with id '{}' already exists",
+                    row_id
+                )))
             }
-            self.rows.insert(row_id.to_string(), Row::new(row.clone()));
-            Ok(())
-        } else {
-            Err(format!("Row is missing an 'id' field: {:?}", row))
-        }
+        };
+        let row = self.new_row(row_id, data);
+        self.check_schema(&row.data)?;
+        self.record_change(op, row_id, Some(&row.data));
+        self.rows.insert(row_id.to_string(), row);
+        self.dirty = true;
+        Ok(true)
     }
 
-    pub fn add_row_with_fk(
+    fn missing_key(&self, row: &Value) -> DatabaseError {
+        DatabaseError::InvalidData(format!(
+            "Row is missing its primary key ({}): {:?}",
+            self.primary_key.join(", "),
+            row
+        ))
+    }
+
+    /// Add a row whose foreign key columns, given as `(table, column)`
+    /// pairs, must hold the key of an existing row, and save it like
+    /// `add_row`. A row with the key of an existing one replaces it.
+    pub async fn add_row_with_fk(
         &mut self,
-        db: &Database,
-        row_data: serde_json::Value,
+        db: &mut Database,
+        mut row_data: serde_json::Value,
         fk_constraints: Option<&[(&str, &str)]>, // Vec of (Table, Column)
-    ) -> Result<(), String> {
+    ) -> Result<String, String> {
+        let not_found = || format!("Table {} not found", self.name);
+        let hooks = db.hooks(&self.name);
+        db.get_table(&self.name)
+            .ok_or_else(not_found)?
+            .prepare_insert(&mut row_data, hooks.as_deref())
+            .map_err(|e| e.to_string())?;
+
         // Validate FK constraints if provided
         if let Some(constraints) = fk_constraints {
             for (table_name, fk_column) in constraints {
-                if let Some(fk_value) = row_data.get(fk_column).and_then(|v| v.as_str()) {
-                    if !db.record_exists(table_name, fk_value) {
+                if let Some(fk_value) = row_data.get(fk_column).and_then(|v| encode_key(&[v])) {
+                    if !db.record_exists(table_name, &fk_value) {
                         return Err(format!(
                             "Foreign key constraint failed: `{}` does not exist in `{}`",
                             fk_value, table_name
@@ -98,13 +240,15 @@ impl Table {
         }
 
         // Add the row after validation
-        let row_id = row_data
-            .get("id")
-            .and_then(|id| id.as_str())
-            .ok_or_else(|| "Missing primary key `id` in row data".to_string())?;
-
-        self.rows.insert(row_id.to_string(), Row::new(row_data));
-        Ok(())
+        let table = db.get_table_mut(&self.name).ok_or_else(not_found)?;
+        let row_id = table
+            .assign_key(&mut row_data)
+            .ok_or_else(|| table.missing_key(&row_data).to_string())?;
+        table
+            .store_row(&row_id, row_data, OnConflict::Replace)
+            .map_err(|e| e.to_string())?;
+        db.persist().await.map_err(|e| e.to_string())?;
+        Ok(row_id)
     }
 }
 
@@ -194,6 +338,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_insert_row_conflicts() {
+        let mut table = Table::new(
+            "users".to_string(),
+            Columns::new(vec![Column::new("id", true), Column::new("name", true)]),
+        );
+        let row = |name| json!({"id": "1", "name": name});
+
+        assert_eq!(
+            table
+                .insert_row(row("Ann"), None, OnConflict::Fail)
+                .unwrap(),
+            Some("1".to_string())
+        );
+        assert!(table
+            .insert_row(row("Bob"), None, OnConflict::Fail)
+            .is_err());
+        assert_eq!(
+            table
+                .insert_row(row("Bob"), None, OnConflict::Ignore)
+                .unwrap(),
+            None
+        );
+        assert_eq!(table.rows["1"].data["name"], "Ann");
+
+        table
+            .insert_row(row("Bob"), None, OnConflict::Replace)
+            .unwrap();
+        assert_eq!(table.rows["1"].data["name"], "Bob");
+
+        // checked against the columns, unlike `add_row`
+        assert!(table
+            .insert_row(json!({"id": "2"}), None, OnConflict::Fail)
+            .is_err());
+    }
+
     #[traced_test]
     #[tokio::test]
     async fn test_table_add_row_table_now_found() {
@@ -0,0 +1,217 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::Value;
+
+use crate::{Column, ColumnType, Row, Table};
+
+pub const CREATED_AT: &str = "created_at";
+pub const UPDATED_AT: &str = "updated_at";
+
+impl Table {
+    /// Keep `created_at` and `updated_at` on every row added from now on.
+    /// Both are set when a row is inserted and `updated_at` is refreshed on
+    /// every update. They are stored on the `Row` and copied into its data,
+    /// so they can be filtered and sorted on like any other column.
+    ///
+    /// Timestamps are UTC RFC 3339 strings with millisecond precision, which
+    /// sort in time order.
+    pub fn with_timestamps(mut self) -> Self {
+        for name in [CREATED_AT, UPDATED_AT] {
+            if !self.columns.0.iter().any(|column| column.name == name) {
+                self.columns
+                    .0
+                    .push(Column::new(name, false).with_type(ColumnType::Text));
+            }
+        }
+        self.timestamps = true;
+        self
+    }
+
+    pub fn has_timestamps(&self) -> bool {
+        self.timestamps
+    }
+
+    /// Build the row stored for `data` under `row_id`. A row replacing an
+    /// existing one keeps its `created_at`.
+    pub(crate) fn new_row(&self, row_id: &str, data: Value) -> Row {
+        let mut row = Row::new(data);
+        if self.timestamps {
+            let now = now();
+            row.created_at = self
+                .rows
+                .get(row_id)
+                .and_then(|existing| existing.created_at.clone())
+                .or_else(|| Some(now.clone()));
+            row.updated_at = Some(now);
+            copy_timestamps(&mut row);
+        }
+        row
+    }
+
+    /// Refresh `updated_at` on a row that is being updated. The stored
+    /// timestamps win over any value the update put in the row's data.
+    pub(crate) fn touch(&self, row: &mut Row) {
+        if self.timestamps {
+            row.updated_at = Some(now());
+            copy_timestamps(row);
+        }
+    }
+}
+
+fn copy_timestamps(row: &mut Row) {
+    if let Value::Object(data) = &mut row.data {
+        for (name, timestamp) in [(CREATED_AT, &row.created_at), (UPDATED_AT, &row.updated_at)] {
+            if let Some(timestamp) = timestamp {
+                data.insert(name.to_string(), Value::String(timestamp.clone()));
+            }
+        }
+    }
+}
+
+fn now() -> String {
+    format_timestamp(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default(),
+    )
+}
+
+// RFC 3339 in UTC for a time since the Unix epoch, the date follows Howard
+// Hinnant's `civil_from_days`
+fn format_timestamp(since_epoch: Duration) -> String {
+    let secs = since_epoch.as_secs();
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    let time = secs % 86_400;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        time / 3_600,
+        time % 3_600 / 60,
+        time % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{setup_temp_db, Columns};
+
+    async fn setup_timestamped_db() -> crate::Database {
+        let mut db = setup_temp_db().await;
+        let mut table = Table::new(
+            "Notes".to_string(),
+            Columns::new(vec![Column::new("id", true), Column::new("text", true)]),
+        )
+        .with_timestamps();
+        db.add_table(&mut table).await.unwrap();
+        db
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(Duration::ZERO), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            format_timestamp(Duration::from_millis(1_709_210_096_789)),
+            "2024-02-29T12:34:56.789Z"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_insert_sets_timestamps() {
+        let mut db = setup_timestamped_db().await;
+        let mut table = db.get_table("Notes").unwrap().clone();
+        table
+            .add_row(&mut db, json!({"id": "1", "text": "hello"}))
+            .await;
+
+        let row = &db.get_table("Notes").unwrap().rows["1"];
+        assert!(row.created_at.is_some());
+        assert_eq!(row.created_at, row.updated_at);
+        assert_eq!(row.data[CREATED_AT].as_str(), row.created_at.as_deref());
+    }
+
+    #[tokio::test]
+    async fn test_update_refreshes_updated_at() {
+        let mut db = setup_timestamped_db().await;
+        db.add_row()
+            .from("Notes")
+            .data_from_struct(json!({"id": "1", "text": "hello"}))
+            .execute_add()
+            .await
+            .unwrap();
+        let before: Value = db
+            .get_single()
+            .from("Notes")
+            .where_eq("id", "1")
+            .await
+            .unwrap()
+            .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        db.update_row()
+            .from("Notes")
+            .data(json!({"text": "bye", "created_at": "tampered"}))
+            .where_eq::<Value>("id", "1")
+            .await
+            .unwrap();
+        let after: Value = db
+            .get_single()
+            .from("Notes")
+            .where_eq("id", "1")
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(after[CREATED_AT], before[CREATED_AT]);
+        assert!(after[UPDATED_AT].as_str() > before[UPDATED_AT].as_str());
+    }
+
+    #[tokio::test]
+    async fn test_order_by_timestamp() {
+        let mut db = setup_timestamped_db().await;
+        for id in ["b", "a"] {
+            db.add_row()
+                .from("Notes")
+                .data_from_struct(json!({"id": id, "text": id}))
+                .execute_add()
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        let rows = db
+            .get_rows()
+            .from("Notes")
+            .order_by(CREATED_AT)
+            .rows()
+            .await
+            .unwrap();
+        let ids: Vec<&str> = rows.iter().map(|row| row["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, vec!["b", "a"]);
+    }
+
+    #[test]
+    fn test_tables_without_timestamps() {
+        let table = Table::new("Plain".to_string(), Columns::new(vec![]));
+        let row = table.new_row("1", json!({"id": "1"}));
+        assert!(!table.has_timestamps());
+        assert_eq!(row.created_at, None);
+        assert_eq!(row.data, json!({"id": "1"}));
+    }
+}
@@ -0,0 +1,517 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::Duration;
+
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tracing;
+
+use super::registry_key;
+use super::storage::SavePlan;
+use crate::{Database, DatabaseError};
+
+/// When changes made to a database are written to disk.
+///
+/// The policy belongs to the database file: queries built with `add_row`,
+/// `update_row`, `delete_single`, ... follow the policy set on the handle
+/// they came from, and read the changes it has not written yet.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum SavePolicy {
+    /// Every mutation is written before it returns.
+    #[default]
+    Immediate,
+    /// Mutations are coalesced by a background task that writes the latest
+    /// state at most once per interval.
+    Debounce(Duration),
+    /// Changes are written once every `n` mutations.
+    EveryNMutations(usize),
+    /// Changes are only written by `Database::flush` or `Database::close`.
+    Manual,
+}
+
+/// Runtime auto-save state of a `Database` handle. It is never persisted and
+/// a cloned `Database` starts with a fresh one.
+#[derive(Default)]
+pub(crate) struct AutoSave {
+    // set on the handle that chose a deferred policy, which flushes the
+    // file's pending changes when it goes away
+    owner: bool,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl Clone for AutoSave {
+    fn clone(&self) -> Self {
+        AutoSave::default()
+    }
+}
+
+impl fmt::Debug for AutoSave {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AutoSave")
+            .field("owner", &self.owner)
+            .field("background_writer", &self.writer.is_some())
+            .finish()
+    }
+}
+
+#[derive(Default)]
+struct SaveState {
+    policy: SavePolicy,
+    // mutations since the last write, for `SavePolicy::EveryNMutations`
+    mutations: usize,
+    // latest state of the database that has not been written yet
+    pending: Option<Database>,
+}
+
+/// Save state of one database file, shared by every handle on it and the
+/// queries built from them.
+#[derive(Default)]
+pub(crate) struct SharedSave {
+    state: Mutex<SaveState>,
+    // held while the files are read or written, so nobody sees a save half
+    // done; taken before `state` when both are needed
+    io: Mutex<()>,
+    // wakes the background writer of `SavePolicy::Debounce`
+    wake: Notify,
+}
+
+static REGISTRY: OnceLock<Mutex<HashMap<PathBuf, Arc<SharedSave>>>> = OnceLock::new();
+
+/// The save state of the database stored at `path`.
+pub(crate) fn shared_save(path: &Path) -> Arc<SharedSave> {
+    let mut registry = REGISTRY
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    registry.entry(registry_key(path)).or_default().clone()
+}
+
+impl SharedSave {
+    fn state(&self) -> MutexGuard<'_, SaveState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn lock_io(&self) -> MutexGuard<'_, ()> {
+        self.io.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn policy(&self) -> SavePolicy {
+        self.state().policy
+    }
+
+    fn set_policy(&self, policy: SavePolicy) {
+        let mut state = self.state();
+        state.policy = policy;
+        state.mutations = 0;
+    }
+
+    /// A copy of the changes that have not been written yet, if any.
+    pub(crate) fn pending(&self) -> Option<Database> {
+        self.state().pending.clone()
+    }
+
+    fn has_pending(&self) -> bool {
+        self.state().pending.is_some()
+    }
+
+    /// Record a mutation, `update` merges it into the pending state. Returns
+    /// whether the policy wants it written right away.
+    fn record(&self, update: impl FnOnce(&mut Option<Database>)) -> bool {
+        let due = {
+            let mut state = self.state();
+            update(&mut state.pending);
+            state.mutations += 1;
+            matches!(state.policy, SavePolicy::EveryNMutations(n) if state.mutations >= n)
+        };
+        self.wake.notify_one();
+        due
+    }
+
+    /// Write `plan` while nobody else reads or writes the files.
+    pub(crate) fn write(&self, plan: SavePlan) -> Result<(), std::io::Error> {
+        let _io = self.lock_io();
+        plan.apply_blocking()
+    }
+
+    /// Write the pending changes, if any. Returns whether there were some.
+    fn write_pending(&self) -> Result<bool, std::io::Error> {
+        let _io = self.lock_io();
+        let Some(db) = ({
+            let mut state = self.state();
+            state.mutations = 0;
+            state.pending.take()
+        }) else {
+            return Ok(false);
+        };
+
+        let result = db.save_plan().and_then(|plan| plan.apply_blocking());
+        if result.is_err() {
+            let mut state = self.state();
+            match &mut state.pending {
+                // newer changes were handed off meanwhile, rewrite everything
+                Some(newer) => newer.tables.values_mut().for_each(|t| t.dirty = true),
+                pending => *pending = Some(db),
+            }
+        }
+        result.map(|_| true)
+    }
+}
+
+async fn write_pending(shared: Arc<SharedSave>) -> Result<bool, DatabaseError> {
+    tokio::task::spawn_blocking(move || shared.write_pending())
+        .await
+        .map_err(|e| DatabaseError::SaveError(std::io::Error::other(e)))?
+        .map_err(DatabaseError::SaveError)
+}
+
+fn spawn_writer(interval: Duration, shared: Arc<SharedSave>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            shared.wake.notified().await;
+            // let further mutations pile up before writing
+            tokio::time::sleep(interval).await;
+            if let Err(e) = write_pending(shared.clone()).await {
+                tracing::error!("Background save failed: {}", e);
+            }
+        }
+    })
+}
+
+impl Database {
+    /// Change when mutations made to this database are written to disk.
+    /// Pending changes are flushed before the new policy takes effect.
+    pub async fn set_save_policy(&mut self, policy: SavePolicy) -> Result<(), DatabaseError> {
+        self.flush().await?;
+        self.stop_writer().await;
+
+        let shared = shared_save(&self.file_name);
+        shared.set_policy(policy);
+        self.save_policy = policy;
+        self.autosave.owner = policy != SavePolicy::Immediate;
+        if let SavePolicy::Debounce(interval) = policy {
+            self.autosave.writer = Some(spawn_writer(interval, shared));
+        }
+        Ok(())
+    }
+
+    pub fn save_policy(&self) -> SavePolicy {
+        self.save_policy
+    }
+
+    /// Whether there are changes that have not reached the disk yet.
+    pub fn is_dirty(&self) -> bool {
+        match self.save_policy {
+            SavePolicy::Immediate => self.dirty,
+            _ => shared_save(&self.file_name).has_pending(),
+        }
+    }
+
+    /// Write any pending changes to disk now.
+    pub async fn flush(&mut self) -> Result<(), DatabaseError> {
+        let written = match self.save_policy {
+            SavePolicy::Immediate if self.dirty => {
+                self.save_to_file()
+                    .await
+                    .map_err(DatabaseError::SaveError)?;
+                true
+            }
+            SavePolicy::Immediate => false,
+            _ => write_pending(shared_save(&self.file_name)).await?,
+        };
+        if written {
+            tracing::info!("Database flushed to file: {:?}", self.file_name);
+        }
+        Ok(())
+    }
+
+    /// Flush pending changes and stop the background writer, if any.
+    pub async fn close(mut self) -> Result<(), DatabaseError> {
+        self.stop_writer().await;
+        self.flush().await?;
+        if self.autosave.owner {
+            shared_save(&self.file_name).set_policy(SavePolicy::Immediate);
+            self.autosave.owner = false;
+        }
+        Ok(())
+    }
+
+    /// Record a mutation made through this handle and write it out
+    /// according to the save policy.
+    pub(crate) async fn persist(&mut self) -> Result<(), DatabaseError> {
+        self.dirty = true;
+        if self.save_policy == SavePolicy::Immediate {
+            let result = self.save_to_file().await.map_err(DatabaseError::SaveError);
+            if result.is_ok() {
+                self.publish_changes();
+            }
+            return result;
+        }
+
+        // subscribers hear about a change once the save policy accepted it,
+        // even when it is only written later
+        self.publish_changes();
+        let due = shared_save(&self.file_name).record(|pending| self.hand_off(pending));
+        if due {
+            self.flush().await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Save a copy of the database loaded by a query, following the save
+    /// policy set on its file.
+    pub(crate) async fn commit(&mut self) -> Result<(), DatabaseError> {
+        let shared = shared_save(&self.file_name);
+        if shared.policy() == SavePolicy::Immediate {
+            self.save_to_file()
+                .await
+                .map_err(DatabaseError::SaveError)?;
+            self.publish_changes();
+            return Ok(());
+        }
+
+        self.publish_changes();
+        // the copy is the latest state as a whole, it replaces what is pending
+        let empty = Database::empty(&self.name, self.file_name.clone(), self.layout);
+        let db = std::mem::replace(self, empty);
+        if shared.record(|pending| *pending = Some(db)) {
+            write_pending(shared).await?;
+        }
+        Ok(())
+    }
+
+    /// The latest state of the database stored at `path`, including changes
+    /// its save policy has not written yet.
+    pub(crate) async fn load_latest(path: &Path) -> Result<Database, DatabaseError> {
+        match shared_save(path).pending() {
+            Some(db) => Ok(db),
+            None => Database::load_from_file(path)
+                .await
+                .map_err(DatabaseError::LoadError),
+        }
+    }
+
+    // only the tables changed since the last hand-off are copied, they are
+    // clean on this handle from then on
+    fn hand_off(&mut self, pending: &mut Option<Database>) {
+        match pending {
+            None => *pending = Some(self.clone()),
+            Some(db) => {
+                db.name.clone_from(&self.name);
+                db.schema_version = self.schema_version;
+                db.tables.retain(|name, _| self.tables.contains_key(name));
+                for (name, table) in &self.tables {
+                    if table.dirty {
+                        db.tables.insert(name.clone(), table.clone());
+                    }
+                }
+            }
+        }
+        self.mark_clean();
+    }
+
+    // anything the writer still had pending is covered by the next flush
+    async fn stop_writer(&mut self) {
+        if let Some(writer) = self.autosave.writer.take() {
+            writer.abort();
+            if let Err(e) = writer.await {
+                if !e.is_cancelled() {
+                    tracing::error!("Background writer stopped unexpectedly: {}", e);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Database {
+    fn drop(&mut self) {
+        if let Some(writer) = self.autosave.writer.take() {
+            writer.abort();
+        }
+        if !self.autosave.owner {
+            return;
+        }
+
+        // queries write straight to disk again once the owner is gone
+        let shared = shared_save(&self.file_name);
+        shared.set_policy(SavePolicy::Immediate);
+        match shared.write_pending() {
+            Ok(true) => tracing::info!("Database flushed on drop: {:?}", self.file_name),
+            Ok(false) => {}
+            Err(e) => tracing::error!("Failed to flush database on drop: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{setup_temp_db, Column, Columns, Table};
+
+    fn users_table() -> Table {
+        Table::new(
+            "users".to_string(),
+            Columns::new(vec![Column::new("id", true), Column::new("name", true)]),
+        )
+    }
+
+    async fn tables_on_disk(db: &Database) -> usize {
+        Database::load_from_file(&db.file_name)
+            .await
+            .expect("Failed to load database")
+            .tables
+            .len()
+    }
+
+    #[tokio::test]
+    async fn test_manual_policy_waits_for_flush() {
+        let mut db = setup_temp_db().await;
+        db.set_save_policy(SavePolicy::Manual).await.unwrap();
+
+        let mut users = users_table();
+        db.add_table(&mut users).await.unwrap();
+
+        assert!(db.is_dirty());
+        assert_eq!(tables_on_disk(&db).await, 1);
+
+        db.flush().await.unwrap();
+
+        assert!(!db.is_dirty());
+        assert_eq!(tables_on_disk(&db).await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_every_n_mutations_policy() {
+        let mut db = setup_temp_db().await;
+        db.set_save_policy(SavePolicy::EveryNMutations(2))
+            .await
+            .unwrap();
+
+        let mut users = users_table();
+        db.add_table(&mut users).await.unwrap();
+        assert_eq!(tables_on_disk(&db).await, 1);
+
+        users
+            .add_row(&mut db, json!({"id": "1", "name": "John Doe"}))
+            .await;
+        assert!(!db.is_dirty());
+
+        let on_disk = Database::load_from_file(&db.file_name).await.unwrap();
+        assert_eq!(on_disk.tables.get("users").unwrap().rows.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_debounce_policy_coalesces_writes() {
+        let mut db = setup_temp_db().await;
+        db.set_save_policy(SavePolicy::Debounce(Duration::from_millis(20)))
+            .await
+            .unwrap();
+
+        let mut users = users_table();
+        db.add_table(&mut users).await.unwrap();
+        for id in 1..=5 {
+            users
+                .add_row(&mut db, json!({"id": id.to_string(), "name": "John Doe"}))
+                .await;
+        }
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        assert!(!db.is_dirty());
+        let on_disk = Database::load_from_file(&db.file_name).await.unwrap();
+        assert_eq!(on_disk.tables.get("users").unwrap().rows.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_debounce_only_rewrites_changed_tables() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dir_db");
+        let mut db =
+            Database::with_layout(path.to_str().unwrap(), crate::StorageLayout::Directory).await;
+        db.set_save_policy(SavePolicy::Debounce(Duration::from_millis(20)))
+            .await
+            .unwrap();
+
+        let mut users = users_table();
+        db.add_table(&mut users).await.unwrap();
+        let mut posts = Table::new("posts".to_string(), Columns::new(vec![]));
+        db.add_table(&mut posts).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        // a table written by an earlier save must not be written again
+        let posts_path = path.join("tables").join("posts.json");
+        tokio::fs::write(&posts_path, "marker").await.unwrap();
+        users
+            .add_row(&mut db, json!({"id": "1", "name": "John Doe"}))
+            .await;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        assert!(!db.is_dirty());
+        let posts_contents = tokio::fs::read_to_string(&posts_path).await.unwrap();
+        assert_eq!(posts_contents, "marker");
+    }
+
+    #[tokio::test]
+    async fn test_queries_follow_the_save_policy() {
+        let mut db = setup_temp_db().await;
+        db.set_save_policy(SavePolicy::Manual).await.unwrap();
+
+        db.add_row()
+            .from("TestTable")
+            .data_from_struct(json!({"id": "1", "name": "Alice"}))
+            .execute_add()
+            .await
+            .unwrap();
+        db.update_row()
+            .from("TestTable")
+            .set(json!({"name": "Bob"}))
+            .update_matching()
+            .await
+            .unwrap();
+
+        // queries see the pending change, the file does not have it yet
+        let rows = db.get_rows().from("TestTable").rows().await.unwrap();
+        assert_eq!(rows, vec![json!({"id": "1", "name": "Bob"})]);
+        assert!(db.is_dirty());
+        let on_disk = Database::load_from_file(&db.file_name).await.unwrap();
+        assert!(on_disk.tables["TestTable"].rows.is_empty());
+
+        db.flush().await.unwrap();
+        let on_disk = Database::load_from_file(&db.file_name).await.unwrap();
+        assert_eq!(on_disk.tables["TestTable"].rows.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_drop_flushes_pending_changes() {
+        let mut db = setup_temp_db().await;
+        let file_name = db.file_name.clone();
+        db.set_save_policy(SavePolicy::Manual).await.unwrap();
+
+        let mut users = users_table();
+        db.add_table(&mut users).await.unwrap();
+        drop(db);
+
+        let on_disk = Database::load_from_file(&file_name).await.unwrap();
+        assert!(on_disk.tables.contains_key("users"));
+    }
+
+    #[tokio::test]
+    async fn test_close_flushes_and_stops_writer() {
+        let mut db = setup_temp_db().await;
+        let file_name = db.file_name.clone();
+        db.set_save_policy(SavePolicy::Debounce(Duration::from_secs(60)))
+            .await
+            .unwrap();
+
+        let mut users = users_table();
+        db.add_table(&mut users).await.unwrap();
+        db.close().await.unwrap();
+
+        let on_disk = Database::load_from_file(&file_name).await.unwrap();
+        assert!(on_disk.tables.contains_key("users"));
+    }
+}
@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tracing;
+
+use super::storage::temp_path_for;
+use crate::{Database, DatabaseError, StorageLayout, Table};
+
+/// Progress of a running backup, reported after each table is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackupProgress {
+    pub tables_done: usize,
+    pub tables_total: usize,
+    pub rows_done: usize,
+    pub rows_total: usize,
+    pub bytes_done: usize,
+}
+
+/// Summary of a finished and verified backup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupReport {
+    pub path: PathBuf,
+    pub tables: usize,
+    pub rows: usize,
+    pub bytes: usize,
+}
+
+impl Database {
+    /// Copy the database to a single JSON file at `path`.
+    ///
+    /// The copy is taken from one consistent state of the database: the
+    /// changes its save policy has not written yet, or else the files on
+    /// disk, read while no save is running. It includes rows written by
+    /// queries. The backup is loaded back and compared with that state
+    /// before this returns.
+    pub async fn backup_to<P: AsRef<Path>>(&self, path: P) -> Result<BackupReport, DatabaseError> {
+        self.backup_to_with_progress(path, |_| {}).await
+    }
+
+    /// Like `backup_to`, calling `progress` after each table is written.
+    pub async fn backup_to_with_progress<P, F>(
+        &self,
+        path: P,
+        mut progress: F,
+    ) -> Result<BackupReport, DatabaseError>
+    where
+        P: AsRef<Path>,
+        F: FnMut(BackupProgress),
+    {
+        let path = path.as_ref();
+        if path == self.file_name {
+            return Err(DatabaseError::BackupError(
+                "backup path must differ from the database path".to_string(),
+            ));
+        }
+
+        let mut source = Database::load_latest(&self.file_name).await?;
+        let source_tables = std::mem::take(&mut source.tables);
+
+        let mut state = BackupProgress {
+            tables_done: 0,
+            tables_total: source_tables.len(),
+            rows_done: 0,
+            rows_total: source_tables.values().map(|table| table.rows.len()).sum(),
+            bytes_done: 0,
+        };
+
+        // the copy is a standalone single-file database that points at itself
+        source.file_name = path.to_path_buf();
+        source.layout = StorageLayout::SingleFile;
+
+        // written next to the target and moved over it once complete
+        let temp_path = temp_path_for(path);
+        let written = write_backup(
+            &temp_path,
+            &source,
+            &source_tables,
+            &mut state,
+            &mut progress,
+        )
+        .await;
+        let written = match written {
+            Ok(()) => tokio::fs::rename(&temp_path, path).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = written {
+            tokio::fs::remove_file(&temp_path).await.ok();
+            return Err(DatabaseError::SaveError(e));
+        }
+
+        let restored = Database::load_from_file(path).await.map_err(|e| {
+            DatabaseError::BackupError(format!("backup at {:?} does not load: {}", path, e))
+        })?;
+        if restored.tables != source_tables {
+            return Err(DatabaseError::BackupError(format!(
+                "backup at {:?} does not match the source database",
+                path
+            )));
+        }
+
+        tracing::info!("Database `{}` backed up to {:?}", self.name, path);
+        Ok(BackupReport {
+            path: path.to_path_buf(),
+            tables: state.tables_total,
+            rows: state.rows_total,
+            bytes: state.bytes_done,
+        })
+    }
+}
+
+// `source` as JSON with `tables` in its place, streamed one table at a time
+async fn write_backup<F>(
+    path: &Path,
+    source: &Database,
+    tables: &HashMap<String, Table>,
+    state: &mut BackupProgress,
+    progress: &mut F,
+) -> Result<(), std::io::Error>
+where
+    F: FnMut(BackupProgress),
+{
+    let mut file = BufWriter::new(tokio::fs::File::create(path).await?);
+
+    // the metadata object, left open for the tables
+    let mut header = serde_json::to_value(source)?;
+    if let Some(fields) = header.as_object_mut() {
+        fields.remove("tables");
+    }
+    let mut head = serde_json::to_string(&header)?;
+    head.pop();
+    head.push_str(if head.ends_with('{') { "" } else { "," });
+    head.push_str("\"tables\":{");
+    file.write_all(head.as_bytes()).await?;
+    state.bytes_done += head.len();
+
+    let mut table_names: Vec<&String> = tables.keys().collect();
+    table_names.sort();
+    for (i, table_name) in table_names.into_iter().enumerate() {
+        let table = &tables[table_name];
+        let mut chunk = if i == 0 {
+            String::new()
+        } else {
+            ",".to_string()
+        };
+        chunk.push_str(&serde_json::to_string(table_name)?);
+        chunk.push(':');
+        chunk.push_str(&serde_json::to_string(table)?);
+        file.write_all(chunk.as_bytes()).await?;
+        file.flush().await?;
+
+        state.tables_done += 1;
+        state.rows_done += table.rows.len();
+        state.bytes_done += chunk.len();
+        progress(*state);
+    }
+
+    file.write_all(b"}}").await?;
+    state.bytes_done += 2;
+    file.flush().await?;
+    file.get_ref().sync_all().await
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{setup_temp_db, Column, Columns, Table};
+
+    #[tokio::test]
+    async fn test_backup_to() {
+        let mut db = setup_temp_db().await;
+        let mut users = Table::new(
+            "users".to_string(),
+            Columns::new(vec![Column::new("id", true), Column::new("name", true)]),
+        );
+        db.add_table(&mut users).await.unwrap();
+        users
+            .add_row(
+                &mut db,
+                json!([{"id": "1", "name": "John Doe"}, {"id": "2", "name": "Jane Doe"}]),
+            )
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let backup_path = dir.path().join("backup.json");
+
+        let mut updates = Vec::new();
+        let report = db
+            .backup_to_with_progress(&backup_path, |progress| updates.push(progress))
+            .await
+            .unwrap();
+
+        assert_eq!(report.tables, 2);
+        assert_eq!(report.rows, 2);
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates.last().unwrap().rows_done, 2);
+        assert!(updates[0].bytes_done < updates[1].bytes_done);
+        let size = std::fs::metadata(&backup_path).unwrap().len() as usize;
+        assert_eq!(report.bytes, size);
+
+        let backup = Database::load_from_file(&backup_path).await.unwrap();
+        assert_eq!(backup.tables, db.tables);
+        assert_eq!(backup.file_name, backup_path);
+    }
+
+    #[tokio::test]
+    async fn test_backup_includes_query_writes() {
+        let mut db = setup_temp_db().await;
+
+        db.add_row()
+            .from("TestTable")
+            .data_from_struct(json!({"id": "1", "name": "Alice"}))
+            .execute_add()
+            .await
+            .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let backup_path = dir.path().join("backup.json");
+        let report = db.backup_to(&backup_path).await.unwrap();
+
+        assert_eq!(report.rows, 1);
+    }
+
+    #[tokio::test]
+    async fn test_backup_from_directory_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("dir_db");
+        let mut db =
+            Database::with_layout(db_path.to_str().unwrap(), StorageLayout::Directory).await;
+        let mut users = Table::new(
+            "users".to_string(),
+            Columns::new(vec![Column::new("id", true)]),
+        );
+        db.add_table(&mut users).await.unwrap();
+
+        let backup_path = dir.path().join("backup.json");
+        db.backup_to(&backup_path).await.unwrap();
+
+        let backup = Database::load_from_file(&backup_path).await.unwrap();
+        assert_eq!(backup.layout, StorageLayout::SingleFile);
+        assert_eq!(backup.tables, db.tables);
+    }
+
+    #[tokio::test]
+    async fn test_backup_while_writing() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("dir_db");
+        let mut db =
+            Database::with_layout(db_path.to_str().unwrap(), StorageLayout::Directory).await;
+        for name in ["users", "posts"] {
+            let mut table = Table::new(
+                name.to_string(),
+                Columns::new(vec![Column::new("id", true)]),
+            );
+            db.add_table(&mut table).await.unwrap();
+        }
+
+        let writer = {
+            let mut db = Database::open(&db_path).await.unwrap();
+            tokio::spawn(async move {
+                for id in 0..50 {
+                    for table in ["users", "posts"] {
+                        db.add_row()
+                            .from(table)
+                            .data_from_struct(json!({"id": id.to_string()}))
+                            .execute_add()
+                            .await
+                            .unwrap();
+                    }
+                }
+            })
+        };
+        for i in 0..10 {
+            let backup_path = dir.path().join(format!("backup{i}.json"));
+            db.backup_to(&backup_path).await.unwrap();
+        }
+        writer.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_backup_to_same_path() {
+        let db = setup_temp_db().await;
+
+        let result = db.backup_to(db.file_name.clone()).await;
+        assert!(matches!(result, Err(DatabaseError::BackupError(_))));
+    }
+}
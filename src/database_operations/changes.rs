@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::broadcast;
+
+use super::{hooks, registry_key};
+use crate::{Database, Operation, Table};
+
+// events a slow subscriber can fall behind by before it starts missing them
+const FEED_CAPACITY: usize = 256;
+
+/// A single change made to a table.
+///
+/// `before` and `after` hold the row as it was and as it is now: a create
+/// only has `after`, a delete only has `before`. Dropping a table is
+/// reported as a `Delete` without a `row_id`.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct ChangeEvent {
+    pub op: Operation,
+    pub table: String,
+    pub row_id: Option<String>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+/// The changes made to one table, see `Database::subscribe`.
+#[derive(Debug)]
+pub struct ChangeFeed {
+    table: String,
+    receiver: broadcast::Receiver<ChangeEvent>,
+}
+
+impl ChangeFeed {
+    /// Wait for the next change to the table. Fails with `Lagged` when the
+    /// feed fell too far behind and missed events, and with `Closed` once
+    /// nothing can publish to it anymore.
+    pub async fn recv(&mut self) -> Result<ChangeEvent, broadcast::error::RecvError> {
+        loop {
+            let event = self.receiver.recv().await?;
+            if event.table == self.table {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// The next change to the table, if one is already waiting.
+    pub fn try_recv(&mut self) -> Result<ChangeEvent, broadcast::error::TryRecvError> {
+        loop {
+            let event = self.receiver.try_recv()?;
+            if event.table == self.table {
+                return Ok(event);
+            }
+        }
+    }
+
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+}
+
+impl Drop for ChangeFeed {
+    fn drop(&mut self) {
+        FEEDS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// queries load their own copy of the database, so feeds are shared by every
+// handle on the same file rather than living on a `Database`
+type Registry = Mutex<HashMap<PathBuf, broadcast::Sender<ChangeEvent>>>;
+
+static REGISTRY: OnceLock<Registry> = OnceLock::new();
+
+// open feeds across all databases, lets mutations skip cloning rows when
+// nobody is listening
+static FEEDS: AtomicUsize = AtomicUsize::new(0);
+
+fn registry() -> &'static Registry {
+    REGISTRY.get_or_init(Default::default)
+}
+
+/// Whether any change feed is open.
+pub(crate) fn watching() -> bool {
+    FEEDS.load(Ordering::Relaxed) > 0
+}
+
+/// Send `events` to the feeds of the database stored at `path`.
+pub(crate) fn publish(path: &Path, events: Vec<ChangeEvent>) {
+    if events.is_empty() || !watching() {
+        return;
+    }
+
+    let key = registry_key(path);
+    let mut feeds = registry().lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(sender) = feeds.get(&key) {
+        for event in events {
+            if sender.send(event).is_err() {
+                // every feed on this database has been dropped
+                feeds.remove(&key);
+                break;
+            }
+        }
+    }
+}
+
+/// Run the after hooks for `events` of the database stored at `path`, then
+/// publish them.
+pub(crate) fn deliver(path: &Path, events: Vec<ChangeEvent>) {
+    hooks::run_after(path, &events);
+    publish(path, events);
+}
+
+impl Table {
+    /// Remember a change to the row `row_id`, to publish once it has been
+    /// saved. Called before the change is applied, the row as it is now
+    /// becomes the event's `before`.
+    pub(crate) fn record_change(&mut self, op: Operation, row_id: &str, after: Option<&Value>) {
+        if watching() || hooks::registered() {
+            let before = self.rows.get(row_id).map(|row| row.data.clone());
+            self.changes.push(ChangeEvent {
+                op,
+                table: self.name.clone(),
+                row_id: Some(row_id.to_string()),
+                before,
+                after: after.cloned(),
+            });
+        }
+    }
+
+    /// Remember that the table is being dropped, see `record_change`.
+    pub(crate) fn record_drop(&mut self) {
+        if watching() || hooks::registered() {
+            self.changes.push(ChangeEvent {
+                op: Operation::Delete,
+                table: self.name.clone(),
+                row_id: None,
+                before: None,
+                after: None,
+            });
+        }
+    }
+}
+
+impl Database {
+    /// Follow every change made to `table` from now on, through this handle,
+    /// any other handle on the same file, or the queries built from them.
+    pub fn subscribe(&self, table: &str) -> ChangeFeed {
+        let mut feeds = registry().lock().unwrap_or_else(|e| e.into_inner());
+        let receiver = feeds
+            .entry(registry_key(&self.file_name))
+            .or_insert_with(|| broadcast::channel(FEED_CAPACITY).0)
+            .subscribe();
+        FEEDS.fetch_add(1, Ordering::Relaxed);
+
+        ChangeFeed {
+            table: table.to_string(),
+            receiver,
+        }
+    }
+
+    /// Run the after hooks for the changes recorded on every table, then
+    /// publish them.
+    pub(crate) fn publish_changes(&mut self) {
+        let events: Vec<ChangeEvent> = self
+            .tables
+            .values_mut()
+            .flat_map(|table| table.changes.drain(..))
+            .collect();
+        deliver(&self.file_name, events);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    use super::*;
+    use crate::setup_temp_db;
+
+    #[tokio::test]
+    async fn test_subscribe_add_row() {
+        let mut db = setup_temp_db().await;
+        let mut feed = db.subscribe("TestTable");
+
+        let mut table = db.get_table("TestTable").unwrap().clone();
+        table
+            .add_row(&mut db, json!({"id": "1", "name": "Alice"}))
+            .await;
+
+        let event = feed.recv().await.unwrap();
+        assert_eq!(
+            event,
+            ChangeEvent {
+                op: Operation::Create,
+                table: "TestTable".to_string(),
+                row_id: Some("1".to_string()),
+                before: None,
+                after: Some(json!({"id": "1", "name": "Alice"})),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_query_mutations() {
+        let mut db = setup_temp_db().await;
+        let mut feed = db.subscribe("TestTable");
+
+        db.add_row()
+            .from("TestTable")
+            .data_from_struct(json!({"id": "1", "name": "Alice"}))
+            .execute_add()
+            .await
+            .unwrap();
+        db.update_row()
+            .from("TestTable")
+            .data(json!({"name": "Bob"}))
+            .where_eq::<Value>("id", "1")
+            .await
+            .unwrap();
+        db.delete_single()
+            .from("TestTable")
+            .where_eq::<Value>("id", "1")
+            .await
+            .unwrap();
+
+        let ops: Vec<Operation> = [(); 3]
+            .iter()
+            .map(|_| feed.try_recv().unwrap().op)
+            .collect();
+        assert_eq!(
+            ops,
+            vec![Operation::Create, Operation::Update, Operation::Delete]
+        );
+        assert!(matches!(feed.try_recv(), Err(TryRecvError::Empty)));
+    }
+
+    #[tokio::test]
+    async fn test_update_carries_before_and_after() {
+        let mut db = setup_temp_db().await;
+        db.add_row()
+            .from("TestTable")
+            .data_from_struct(json!({"id": "1", "name": "Alice"}))
+            .execute_add()
+            .await
+            .unwrap();
+        let mut feed = db.subscribe("TestTable");
+
+        db.update_row()
+            .from("TestTable")
+            .data(json!({"name": "Bob"}))
+            .update_matching()
+            .await
+            .unwrap();
+
+        let event = feed.try_recv().unwrap();
+        assert_eq!(event.before, Some(json!({"id": "1", "name": "Alice"})));
+        assert_eq!(event.after, Some(json!({"id": "1", "name": "Bob"})));
+    }
+
+    #[tokio::test]
+    async fn test_feed_only_sees_its_table() {
+        let mut db = setup_temp_db().await;
+        let mut feed = db.subscribe("Other");
+
+        db.add_row()
+            .from("TestTable")
+            .data_from_struct(json!({"id": "1", "name": "Alice"}))
+            .execute_add()
+            .await
+            .unwrap();
+        assert!(matches!(feed.try_recv(), Err(TryRecvError::Empty)));
+    }
+
+    #[tokio::test]
+    async fn test_drop_table_event() {
+        let mut db = setup_temp_db().await;
+        let mut feed = db.subscribe("TestTable");
+
+        db.drop_table("TestTable").await.unwrap();
+
+        let event = feed.try_recv().unwrap();
+        assert_eq!(event.op, Operation::Delete);
+        assert_eq!(event.row_id, None);
+    }
+
+    #[tokio::test]
+    async fn test_fk_insert_event() {
+        let mut db = setup_temp_db().await;
+        let mut feed = db.subscribe("TestTable");
+
+        let mut table = db.get_table("TestTable").unwrap().clone();
+        table
+            .add_row_with_fk(&mut db, json!({"id": "1", "name": "Alice"}), None)
+            .await
+            .unwrap();
+        assert!(db.record_exists("TestTable", "1"));
+
+        let event = feed.try_recv().unwrap();
+        assert_eq!(event.op, Operation::Create);
+        assert_eq!(event.row_id.as_deref(), Some("1"));
+    }
+}
@@ -1,13 +1,25 @@
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tracing;
 
-use crate::{Database, DatabaseError, Operation, Query, Table, View};
+use super::autosave::{shared_save, AutoSave};
+use super::changes;
+use crate::{Database, DatabaseError, Operation, Query, SavePolicy, StorageLayout, Table, View};
 
 impl Database {
     pub async fn new(name: &str) -> Self {
+        Database::with_layout(name, StorageLayout::SingleFile).await
+    }
+
+    /// Create or open a database stored with the given `StorageLayout`.
+    /// `SingleFile` databases live in `<name>.json`, `Directory` databases
+    /// live in the `<name>` directory.
+    pub async fn with_layout(name: &str, layout: StorageLayout) -> Self {
         let name = name.to_string();
-        let file_name = format!("{name}.json");
+        let file_name = match layout {
+            StorageLayout::SingleFile => format!("{name}.json"),
+            StorageLayout::Directory => name.clone(),
+        };
 
         if tokio::fs::metadata(&file_name).await.is_ok() {
             tracing::info!("Database already exists: {name}, loading database");
@@ -21,27 +33,72 @@ impl Database {
             }
         } else {
             tracing::info!("Creating new database: {file_name}");
-            // Create an empty JSON file for the new database
-            if let Err(e) = tokio::fs::write(&file_name, "{}").await {
-                tracing::error!("Failed to create database file: {e}");
+            match layout {
+                StorageLayout::SingleFile => {
+                    // Create an empty JSON file for the new database
+                    if let Err(e) = tokio::fs::write(&file_name, "{}").await {
+                        tracing::error!("Failed to create database file: {e}");
+                    }
+                }
+                StorageLayout::Directory => {
+                    let mut db = Database::empty(&name, file_name.into(), layout);
+                    if let Err(e) = db.save_to_file().await {
+                        tracing::error!("Failed to create database directory: {e}");
+                    }
+                    return db;
+                }
             }
         }
 
+        Database::empty(&name, file_name.into(), layout)
+    }
+
+    /// Open an existing database from its `.json` file or directory. Unlike
+    /// `new`, nothing is created when `path` does not exist.
+    pub async fn open<P: AsRef<Path>>(path: P) -> Result<Self, DatabaseError> {
+        let mut db = Database::load_from_file(path.as_ref())
+            .await
+            .map_err(DatabaseError::LoadError)?;
+        // the stored path is stale if the database was moved
+        db.file_name = path.as_ref().to_path_buf();
+        Ok(db)
+    }
+
+    pub(crate) fn empty(name: &str, file_name: PathBuf, layout: StorageLayout) -> Self {
         Database {
-            name,
-            file_name: file_name.into(),
-            tables: HashMap::new(), // tables: Vec::new(),
+            name: name.to_string(),
+            file_name,
+            tables: HashMap::new(),
+            layout,
+            schema_version: 0,
+            dirty: false,
+            save_policy: SavePolicy::Immediate,
+            autosave: AutoSave::default(),
         }
     }
 
     pub async fn drop_database(&self) -> Result<(), DatabaseError> {
-        if tokio::fs::remove_file(&self.file_name).await.is_err() {
+        let removed = match self.layout {
+            StorageLayout::SingleFile => tokio::fs::remove_file(&self.file_name).await,
+            StorageLayout::Directory => tokio::fs::remove_dir_all(&self.file_name).await,
+        };
+        if removed.is_err() {
             tracing::error!(
                 "{}",
                 DatabaseError::DeleteError("Failed to delete database file".to_string())
             );
         }
 
+        let snapshot_dir = self.snapshot_dir();
+        if tokio::fs::metadata(&snapshot_dir).await.is_ok()
+            && tokio::fs::remove_dir_all(&snapshot_dir).await.is_err()
+        {
+            tracing::error!(
+                "{}",
+                DatabaseError::DeleteError("Failed to delete database snapshots".to_string())
+            );
+        }
+
         tracing::info!("Database `{}` dropped successfully", self.name);
         Ok(())
     }
@@ -55,23 +112,22 @@ impl Database {
             return Ok(());
         }
 
-        self.tables.insert(table.name.clone(), table.clone());
-        self.save_to_file()
-            .await
-            .map_err(DatabaseError::SaveError)?;
-        Ok(())
+        let mut new_table = table.clone();
+        new_table.dirty = true;
+        self.tables.insert(table.name.clone(), new_table);
+        self.persist().await
     }
 
     pub async fn drop_table(&mut self, table_name: &str) -> Result<(), DatabaseError> {
-        let mut db = Database::load_from_file(&self.file_name)
-            .await
-            .map_err(DatabaseError::LoadError)?;
+        // queries may have written since this handle loaded
+        let mut db = Database::load_latest(&self.file_name).await?;
 
-        if let Some(removed_table) = db.tables.remove(table_name) {
+        if let Some(mut removed_table) = db.tables.remove(table_name) {
             tracing::info!("Table `{}` dropped successfully", removed_table.name);
-            db.save_to_file().await.map_err(DatabaseError::SaveError)?;
-
-            self.tables = db.tables;
+            removed_table.record_drop();
+            self.tables = db.tables.clone();
+            db.commit().await?;
+            changes::deliver(&self.file_name, removed_table.changes);
             Ok(())
         } else {
             tracing::error!("{}", DatabaseError::TableNotFound(table_name.to_string()));
@@ -93,6 +149,10 @@ impl Database {
         self.tables.keys().cloned().collect()
     }
 
+    pub fn get_table(&self, table_name: &str) -> Option<&Table> {
+        self.tables.get(table_name)
+    }
+
     pub async fn rename_table(
         &mut self,
         old_name: &str,
@@ -115,13 +175,10 @@ impl Database {
 
         let mut table = table?;
         table.name = new_name.to_string();
+        table.dirty = true;
         self.tables.insert(new_name.to_string(), table);
 
-        self.save_to_file()
-            .await
-            .map_err(DatabaseError::SaveError)?;
-
-        Ok(())
+        self.persist().await
     }
 
     pub fn count_rows(&self, table_name: &str) -> Result<usize, DatabaseError> {
@@ -135,6 +192,8 @@ impl Database {
         }
     }
 
+    /// Whether `table_name` has a row keyed by `pk_value`. Numeric keys are
+    /// written out, use `key_exists` for composite keys.
     pub fn record_exists(&self, table_name: &str, pk_value: &str) -> bool {
         if let Some(table) = self.tables.get(table_name) {
             table.rows.contains_key(pk_value)
@@ -143,22 +202,42 @@ impl Database {
         }
     }
 
-    pub(crate) async fn save_to_file(&self) -> Result<(), tokio::io::Error> {
-        let json_data = serde_json::to_string_pretty(&self)?;
-        tokio::fs::write(&self.file_name, json_data).await?;
+    pub(crate) async fn save_to_file(&mut self) -> Result<(), tokio::io::Error> {
+        let plan = self.save_plan()?;
+        let shared = shared_save(&self.file_name);
+        tokio::task::spawn_blocking(move || shared.write(plan))
+            .await
+            .map_err(std::io::Error::other)??;
+        self.mark_clean();
         tracing::info!("Database saved to file: {:?}", self.file_name);
         Ok(())
     }
 
+    /// Load what is on disk, waiting for any save of the same database to
+    /// finish first.
     pub(crate) async fn load_from_file<P: AsRef<Path>>(
         file_name: P,
     ) -> Result<Self, tokio::io::Error> {
-        let json_data = tokio::fs::read_to_string(file_name.as_ref()).await?;
+        let path = file_name.as_ref().to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            let shared = shared_save(&path);
+            let _io = shared.lock_io();
+            Database::load_blocking(&path)
+        })
+        .await
+        .map_err(std::io::Error::other)?
+    }
+
+    fn load_blocking(path: &Path) -> Result<Self, std::io::Error> {
+        if std::fs::metadata(path)?.is_dir() {
+            let db = Database::load_from_directory(path)?;
+            tracing::info!("Database loaded from directory: {:?}", path.display());
+            return Ok(db);
+        }
+
+        let json_data = std::fs::read_to_string(path)?;
         let db: Database = serde_json::from_str(&json_data)?;
-        tracing::info!(
-            "Database loaded from file: {:?}",
-            file_name.as_ref().display()
-        );
+        tracing::info!("Database loaded from file: {:?}", path.display());
         Ok(db)
     }
 
@@ -177,53 +256,23 @@ impl Database {
 
 impl Database {
     pub fn add_row(&mut self) -> Query {
-        Query {
-            db_file_name: self.file_name.clone(),
-            table_name: None,
-            operation: Operation::Create,
-            update_data: None,
-            row_data: None,
-        }
+        Query::new(self.file_name.clone(), Operation::Create)
     }
 
     pub fn get_rows(&self) -> Query {
-        Query {
-            db_file_name: self.file_name.clone(),
-            table_name: None,
-            operation: Operation::Read,
-            update_data: None,
-            row_data: None,
-        }
+        Query::new(self.file_name.clone(), Operation::Read)
     }
 
     pub fn get_single(&self) -> Query {
-        Query {
-            db_file_name: self.file_name.clone(),
-            table_name: None,
-            operation: Operation::Read,
-            update_data: None,
-            row_data: None,
-        }
+        Query::new(self.file_name.clone(), Operation::Read)
     }
 
     pub fn delete_single(&self) -> Query {
-        Query {
-            db_file_name: self.file_name.clone(),
-            table_name: None,
-            operation: Operation::Delete,
-            update_data: None,
-            row_data: None,
-        }
+        Query::new(self.file_name.clone(), Operation::Delete)
     }
 
     pub fn update_row(&self) -> Query {
-        Query {
-            db_file_name: self.file_name.clone(),
-            table_name: None,
-            operation: Operation::Update,
-            update_data: None,
-            row_data: None,
-        }
+        Query::new(self.file_name.clone(), Operation::Update)
     }
 }
 
@@ -242,6 +291,21 @@ mod tests {
         name: String,
     }
 
+    #[tokio::test]
+    async fn test_database_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = Database::open(dir.path().join("missing.json")).await;
+        assert!(matches!(missing, Err(DatabaseError::LoadError(_))));
+
+        let db = setup_temp_db().await;
+        let moved = dir.path().join("moved.json");
+        tokio::fs::copy(&db.file_name, &moved).await.unwrap();
+
+        let opened = Database::open(&moved).await.unwrap();
+        assert_eq!(opened.tables, db.tables);
+        assert_eq!(opened.file_name, moved);
+    }
+
     #[tokio::test]
     async fn test_database_new() {
         let db = setup_temp_db().await;
@@ -313,6 +377,26 @@ mod tests {
         assert_eq!(db.tables.len(), 0);
     }
 
+    #[tokio::test]
+    async fn test_drop_table_keeps_query_writes() {
+        let mut db = setup_temp_db().await;
+        let mut other = Table::new("Other".to_string(), Columns::new(vec![]));
+        db.add_table(&mut other).await.unwrap();
+
+        db.add_row()
+            .from("TestTable")
+            .data_from_struct(serde_json::json!({"id": "1", "name": "Alice"}))
+            .execute_add()
+            .await
+            .unwrap();
+        db.drop_table("Other").await.unwrap();
+
+        let on_disk = Database::load_from_file(&db.file_name).await.unwrap();
+        assert_eq!(on_disk.count_rows("TestTable").unwrap(), 1);
+        assert!(on_disk.get_table("Other").is_none());
+        assert_eq!(db.count_rows("TestTable").unwrap(), 1);
+    }
+
     #[traced_test]
     #[tokio::test]
     async fn test_drop_table_not_found() {
@@ -470,12 +554,14 @@ mod tests {
 
         // Valid FK
         assert!(posts_table
-            .add_row_with_fk(&db, valid_post, Some(&[("users", "user_id")]))
+            .add_row_with_fk(&mut db, valid_post, Some(&[("users", "user_id")]))
+            .await
             .is_ok());
 
         // Invalid FK
         assert!(posts_table
-            .add_row_with_fk(&db, invalid_post, Some(&[("users", "user_id")]))
+            .add_row_with_fk(&mut db, invalid_post, Some(&[("users", "user_id")]))
+            .await
             .is_err());
     }
 
@@ -486,11 +572,7 @@ mod tests {
         let temp_file = NamedTempFile::new().expect("Failed to create a temporary file");
         let db_path = temp_file.path().to_path_buf();
 
-        let db = Database {
-            name: "test_db".to_string(),
-            file_name: db_path.clone(),
-            tables: HashMap::new(),
-        };
+        let mut db = Database::empty("test_db", db_path.clone(), StorageLayout::SingleFile);
 
         db.save_to_file().await.expect("Failed to save database");
         let loaded_db = Database::load_from_file(&db_path)
@@ -506,11 +588,7 @@ mod tests {
         let temp_file = NamedTempFile::new().expect("Failed to create a temporary file");
         let db_path = temp_file.path().to_path_buf();
 
-        let db = Database {
-            name: "test_db".to_string(),
-            file_name: db_path.clone(),
-            tables: HashMap::new(),
-        };
+        let mut db = Database::empty("test_db", db_path.clone(), StorageLayout::SingleFile);
 
         db.save_to_file().await.expect("Failed to save database");
 
@@ -537,11 +615,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_add_row() {
-        let mut db = Database {
-            name: "test_db".to_string(),
-            file_name: "test_db.json".into(),
-            tables: HashMap::new(),
-        };
+        let mut db = Database::empty("test_db", "test_db.json".into(), StorageLayout::SingleFile);
 
         let query = db.add_row();
         assert_eq!(query.operation, Operation::Create);
@@ -549,11 +623,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_get_rows() {
-        let db = Database {
-            name: "test_db".to_string(),
-            file_name: "test_db.json".into(),
-            tables: HashMap::new(),
-        };
+        let db = Database::empty("test_db", "test_db.json".into(), StorageLayout::SingleFile);
 
         let query = db.get_rows();
         assert_eq!(query.operation, Operation::Read);
@@ -561,11 +631,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_get_single() {
-        let db = Database {
-            name: "test_db".to_string(),
-            file_name: "test_db.json".into(),
-            tables: HashMap::new(),
-        };
+        let db = Database::empty("test_db", "test_db.json".into(), StorageLayout::SingleFile);
 
         let query = db.get_single();
         assert_eq!(query.operation, Operation::Read);
@@ -573,11 +639,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_delete_single() {
-        let db = Database {
-            name: "test_db".to_string(),
-            file_name: "test_db.json".into(),
-            tables: HashMap::new(),
-        };
+        let db = Database::empty("test_db", "test_db.json".into(), StorageLayout::SingleFile);
 
         let query = db.delete_single();
         assert_eq!(query.operation, Operation::Delete);
@@ -585,11 +647,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_update_row() {
-        let db = Database {
-            name: "test_db".to_string(),
-            file_name: "test_db.json".into(),
-            tables: HashMap::new(),
-        };
+        let db = Database::empty("test_db", "test_db.json".into(), StorageLayout::SingleFile);
 
         let query = db.update_row();
         assert_eq!(query.operation, Operation::Update);
@@ -0,0 +1,559 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use serde_json::Value;
+
+use super::registry_key;
+use crate::{ChangeEvent, Column, Database, DatabaseError, Generated, Operation};
+
+type BeforeHook = Arc<dyn Fn(&mut Value) -> Result<(), DatabaseError> + Send + Sync>;
+type CheckHook = Arc<dyn Fn(&Value) -> Result<(), DatabaseError> + Send + Sync>;
+type AfterHook = Arc<dyn Fn(&Value) + Send + Sync>;
+type DefaultFn = Arc<dyn Fn() -> Value + Send + Sync>;
+type ComputeFn = Arc<dyn Fn(&Value) -> Value + Send + Sync>;
+
+/// The hooks registered on one table, see `Database::before_insert`.
+#[derive(Default, Clone)]
+pub(crate) struct TableHooks {
+    before_insert: Vec<BeforeHook>,
+    before_update: Vec<BeforeHook>,
+    before_delete: Vec<CheckHook>,
+    after_insert: Vec<AfterHook>,
+    after_update: Vec<AfterHook>,
+    after_delete: Vec<AfterHook>,
+    defaults: Vec<(String, DefaultFn)>,
+    computed: Vec<(String, ComputeFn)>,
+}
+
+impl TableHooks {
+    /// Fill the generated defaults `row` is missing, run the insert hooks
+    /// and compute the computed columns, in that order.
+    pub(crate) fn before_insert(&self, row: &mut Value) -> Result<(), DatabaseError> {
+        if let Value::Object(data) = row {
+            for (column, default) in &self.defaults {
+                if !data.contains_key(column) {
+                    data.insert(column.clone(), default());
+                }
+            }
+        }
+        self.before_insert.iter().try_for_each(|hook| hook(row))?;
+        self.compute(row);
+        Ok(())
+    }
+
+    /// Run the update hooks on `row`, which already holds the updated data,
+    /// then compute the computed columns. Callers check that the hooks left
+    /// the row's key alone.
+    pub(crate) fn before_update(&self, row: &mut Value) -> Result<(), DatabaseError> {
+        self.before_update.iter().try_for_each(|hook| hook(row))?;
+        self.compute(row);
+        Ok(())
+    }
+
+    // each computed column sees the ones registered before it
+    fn compute(&self, row: &mut Value) {
+        for (column, compute) in &self.computed {
+            let value = compute(row);
+            if let Value::Object(data) = row {
+                data.insert(column.clone(), value);
+            }
+        }
+    }
+
+    pub(crate) fn before_delete(&self, row: &Value) -> Result<(), DatabaseError> {
+        self.before_delete.iter().try_for_each(|hook| hook(row))
+    }
+
+    /// Run the after hooks matching a change that has been saved.
+    fn after(&self, event: &ChangeEvent) {
+        let (hooks, row) = match event.op {
+            Operation::Create => (&self.after_insert, &event.after),
+            Operation::Update => (&self.after_update, &event.after),
+            Operation::Delete => (&self.after_delete, &event.before),
+            Operation::Read => return,
+        };
+        if let Some(row) = row {
+            hooks.iter().for_each(|hook| hook(row));
+        }
+    }
+}
+
+// like change feeds, hooks belong to the database file so that they also
+// run for queries, which load their own copy of the database
+type Registry = Mutex<HashMap<(PathBuf, String), Arc<TableHooks>>>;
+
+static REGISTRY: OnceLock<Registry> = OnceLock::new();
+
+// tables with hooks across all databases, lets mutations skip recording
+// changes when there are none
+static HOOKED: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether any table has hooks.
+pub(crate) fn registered() -> bool {
+    HOOKED.load(Ordering::Relaxed) > 0
+}
+
+/// The hooks of `table` in the database stored at `path`.
+pub(crate) fn table_hooks(path: &Path, table: &str) -> Option<Arc<TableHooks>> {
+    let hooks = REGISTRY.get()?.lock().unwrap_or_else(|e| e.into_inner());
+    hooks.get(&(registry_key(path), table.to_string())).cloned()
+}
+
+/// Move the generated default or computed value of `old` over to `new`, once
+/// the column has been renamed.
+pub(crate) fn rename_column(path: &Path, table: &str, old: &str, new: &str) {
+    edit_hooks(path, table, |hooks| {
+        let defaults = hooks.defaults.iter_mut().map(|(name, _)| name);
+        let computed = hooks.computed.iter_mut().map(|(name, _)| name);
+        for name in defaults.chain(computed).filter(|name| *name == old) {
+            *name = new.to_string();
+        }
+    });
+}
+
+/// Forget the generated default or computed value of a dropped column.
+pub(crate) fn drop_column(path: &Path, table: &str, column: &str) {
+    edit_hooks(path, table, |hooks| {
+        hooks.defaults.retain(|(name, _)| name != column);
+        hooks.computed.retain(|(name, _)| name != column);
+    });
+}
+
+// change the hooks of `table`, if it has any
+fn edit_hooks(path: &Path, table: &str, edit: impl FnOnce(&mut TableHooks)) {
+    if let Some(registry) = REGISTRY.get() {
+        let mut hooks = registry.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(table_hooks) = hooks.get_mut(&(registry_key(path), table.to_string())) {
+            edit(Arc::make_mut(table_hooks));
+        }
+    }
+}
+
+/// Run the after hooks for `events`, once they have been saved.
+pub(crate) fn run_after(path: &Path, events: &[ChangeEvent]) {
+    if !registered() {
+        return;
+    }
+    for event in events {
+        if let Some(hooks) = table_hooks(path, &event.table) {
+            hooks.after(event);
+        }
+    }
+}
+
+impl Database {
+    /// Run `hook` on every row about to be inserted into `table`, before it
+    /// is validated. The hook may change the row, or return an error to
+    /// reject it.
+    pub fn before_insert<F>(&self, table: &str, hook: F)
+    where
+        F: Fn(&mut Value) -> Result<(), DatabaseError> + Send + Sync + 'static,
+    {
+        self.register_hook(table, |hooks| hooks.before_insert.push(Arc::new(hook)));
+    }
+
+    /// Run `hook` on every updated row of `table` before it is validated and
+    /// stored. The hook sees the row with the update applied and may change
+    /// it further, except for its primary key, or return an error to reject
+    /// it.
+    pub fn before_update<F>(&self, table: &str, hook: F)
+    where
+        F: Fn(&mut Value) -> Result<(), DatabaseError> + Send + Sync + 'static,
+    {
+        self.register_hook(table, |hooks| hooks.before_update.push(Arc::new(hook)));
+    }
+
+    /// Run `hook` on every row about to be deleted from `table`, an error
+    /// keeps the row.
+    pub fn before_delete<F>(&self, table: &str, hook: F)
+    where
+        F: Fn(&Value) -> Result<(), DatabaseError> + Send + Sync + 'static,
+    {
+        self.register_hook(table, |hooks| hooks.before_delete.push(Arc::new(hook)));
+    }
+
+    /// Run `hook` on every row inserted into `table`, once it has been saved.
+    pub fn after_insert<F>(&self, table: &str, hook: F)
+    where
+        F: Fn(&Value) + Send + Sync + 'static,
+    {
+        self.register_hook(table, |hooks| hooks.after_insert.push(Arc::new(hook)));
+    }
+
+    /// Run `hook` on every updated row of `table`, once it has been saved.
+    pub fn after_update<F>(&self, table: &str, hook: F)
+    where
+        F: Fn(&Value) + Send + Sync + 'static,
+    {
+        self.register_hook(table, |hooks| hooks.after_update.push(Arc::new(hook)));
+    }
+
+    /// Run `hook` on every row deleted from `table`, once the delete has
+    /// been saved.
+    pub fn after_delete<F>(&self, table: &str, hook: F)
+    where
+        F: Fn(&Value) + Send + Sync + 'static,
+    {
+        self.register_hook(table, |hooks| hooks.after_delete.push(Arc::new(hook)));
+    }
+
+    /// Fill `column` with the value `default` returns in rows inserted into
+    /// `table` without it, e.g. a fresh UUID or the current time. The column
+    /// is declared on the table, and added when it is missing. Literal
+    /// defaults are declared on the column instead, see `Column::with_default`.
+    pub async fn default_with<F>(
+        &mut self,
+        table: &str,
+        column: &str,
+        default: F,
+    ) -> Result<(), DatabaseError>
+    where
+        F: Fn() -> Value + Send + Sync + 'static,
+    {
+        self.declare_generated(table, column, Generated::Default)?;
+        self.persist().await?;
+        let column = column.to_string();
+        self.register_hook(table, |hooks| {
+            hooks.defaults.retain(|(name, _)| *name != column);
+            hooks.defaults.push((column, Arc::new(default)))
+        });
+        Ok(())
+    }
+
+    /// Keep `column` of `table` set to what `compute` derives from the rest
+    /// of the row, on every insert and update. The column is declared on the
+    /// table, and added when it is missing. The value is stored with the
+    /// row, so it can be filtered and sorted on. Rows already in the table
+    /// keep their value until they are next updated.
+    pub async fn computed_column<F>(
+        &mut self,
+        table: &str,
+        column: &str,
+        compute: F,
+    ) -> Result<(), DatabaseError>
+    where
+        F: Fn(&Value) -> Value + Send + Sync + 'static,
+    {
+        self.declare_generated(table, column, Generated::Computed)?;
+        self.persist().await?;
+        let column = column.to_string();
+        self.register_hook(table, |hooks| {
+            hooks.computed.retain(|(name, _)| *name != column);
+            hooks.computed.push((column, Arc::new(compute)))
+        });
+        Ok(())
+    }
+
+    /// Remove every hook registered on `table`, along with the functions
+    /// behind its generated defaults and computed columns.
+    pub fn clear_hooks(&self, table: &str) {
+        if let Some(registry) = REGISTRY.get() {
+            let mut hooks = registry.lock().unwrap_or_else(|e| e.into_inner());
+            hooks.remove(&(registry_key(&self.file_name), table.to_string()));
+            HOOKED.store(hooks.len(), Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn hooks(&self, table: &str) -> Option<Arc<TableHooks>> {
+        table_hooks(&self.file_name, table)
+    }
+
+    fn register_hook(&self, table: &str, add: impl FnOnce(&mut TableHooks)) {
+        let mut hooks = REGISTRY
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let table_hooks = hooks
+            .entry((registry_key(&self.file_name), table.to_string()))
+            .or_default();
+        add(Arc::make_mut(table_hooks));
+        HOOKED.store(hooks.len(), Ordering::Relaxed);
+    }
+
+    // mark `column` of `table` as `generated`, adding it when it is missing
+    fn declare_generated(
+        &mut self,
+        table_name: &str,
+        column: &str,
+        generated: Generated,
+    ) -> Result<(), DatabaseError> {
+        let table = self
+            .get_table_mut(table_name)
+            .ok_or_else(|| DatabaseError::TableNotFound(table_name.to_string()))?;
+        // a computed key would change under the row it identifies
+        if generated == Generated::Computed && table.is_key_column(column) {
+            return Err(DatabaseError::InvalidOperation(format!(
+                "the key column `{}` cannot be computed",
+                column
+            )));
+        }
+
+        match table.columns.0.iter_mut().find(|c| c.name == column) {
+            Some(declared) => declared.generated = Some(generated),
+            None => {
+                let mut added = Column::new(column, false);
+                added.generated = Some(generated);
+                table.columns.0.push(added);
+            }
+        }
+        table.dirty = true;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::{setup_temp_db, Column, Columns, Table};
+
+    async fn add(db: &mut Database, row: Value) -> Result<String, DatabaseError> {
+        db.add_row()
+            .from("TestTable")
+            .data_from_struct(row)
+            .execute_add()
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_before_insert_changes_row() {
+        let mut db = setup_temp_db().await;
+        db.before_insert("TestTable", |row| {
+            row["name"] = json!(row["name"].as_str().unwrap_or_default().to_uppercase());
+            Ok(())
+        });
+
+        let mut table = db.get_table("TestTable").unwrap().clone();
+        table
+            .add_row(&mut db, json!({"id": "1", "name": "alice"}))
+            .await;
+        add(&mut db, json!({"id": "2", "name": "bob"}))
+            .await
+            .unwrap();
+
+        let rows = db.get_rows().from("TestTable").order_by("id").rows().await;
+        let names: Vec<Value> = rows
+            .unwrap()
+            .into_iter()
+            .map(|r| r["name"].clone())
+            .collect();
+        assert_eq!(names, vec![json!("ALICE"), json!("BOB")]);
+    }
+
+    #[tokio::test]
+    async fn test_before_insert_veto() {
+        let mut db = setup_temp_db().await;
+        db.before_insert("TestTable", |row| match row["name"].as_str() {
+            Some("") => Err(DatabaseError::InvalidData("name is empty".to_string())),
+            _ => Ok(()),
+        });
+
+        let result = add(&mut db, json!({"id": "1", "name": ""})).await;
+        assert!(matches!(result, Err(DatabaseError::InvalidData(_))));
+        let rows = db.get_rows().from("TestTable").rows().await.unwrap();
+        assert!(rows.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_before_update_sets_field_and_keeps_id() {
+        let mut db = setup_temp_db().await;
+        add(&mut db, json!({"id": "1", "name": "alice"}))
+            .await
+            .unwrap();
+        db.before_update("TestTable", |row| {
+            row["name"] = json!(format!("{}!", row["name"].as_str().unwrap_or_default()));
+            Ok(())
+        });
+
+        db.update_row()
+            .from("TestTable")
+            .data(json!({"name": "bob"}))
+            .where_eq::<Value>("id", "1")
+            .await
+            .unwrap();
+        let row: Option<Value> = db
+            .get_single()
+            .from("TestTable")
+            .where_eq("id", "1")
+            .await
+            .unwrap();
+        assert_eq!(row.unwrap()["name"], json!("bob!"));
+
+        db.before_update("TestTable", |row| {
+            row["id"] = json!("2");
+            Ok(())
+        });
+        let result = db
+            .update_row()
+            .from("TestTable")
+            .data(json!({"name": "carol"}))
+            .update_matching()
+            .await;
+        assert!(matches!(result, Err(DatabaseError::InvalidData(_))));
+    }
+
+    #[tokio::test]
+    async fn test_before_delete_veto_and_after_delete() {
+        let mut db = setup_temp_db().await;
+        add(&mut db, json!({"id": "1", "name": "keep"}))
+            .await
+            .unwrap();
+        add(&mut db, json!({"id": "2", "name": "drop"}))
+            .await
+            .unwrap();
+
+        let deleted = Arc::new(AtomicUsize::new(0));
+        let counter = deleted.clone();
+        db.before_delete("TestTable", |row| match row["name"].as_str() {
+            Some("keep") => Err(DatabaseError::InvalidOperation("row is kept".to_string())),
+            _ => Ok(()),
+        });
+        db.after_delete("TestTable", move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let result = db
+            .delete_single()
+            .from("TestTable")
+            .where_eq::<Value>("id", "1")
+            .await;
+        assert!(matches!(result, Err(DatabaseError::InvalidOperation(_))));
+        db.delete_single()
+            .from("TestTable")
+            .where_eq::<Value>("id", "2")
+            .await
+            .unwrap();
+
+        let rows = db.get_rows().from("TestTable").rows().await.unwrap();
+        assert_eq!(rows, vec![json!({"id": "1", "name": "keep"})]);
+        assert_eq!(deleted.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_default_with() {
+        let mut db = setup_temp_db().await;
+        let next = Arc::new(AtomicUsize::new(0));
+        db.default_with("TestTable", "name", move || {
+            json!(format!("user{}", next.fetch_add(1, Ordering::SeqCst)))
+        })
+        .await
+        .unwrap();
+
+        add(&mut db, json!({"id": "1"})).await.unwrap();
+        add(&mut db, json!({"id": "2", "name": "bob"}))
+            .await
+            .unwrap();
+        add(&mut db, json!({"id": "3"})).await.unwrap();
+
+        let rows = db.get_rows().from("TestTable").order_by("id").rows().await;
+        let names: Vec<Value> = rows
+            .unwrap()
+            .into_iter()
+            .map(|r| r["name"].clone())
+            .collect();
+        assert_eq!(names, vec![json!("user0"), json!("bob"), json!("user1")]);
+    }
+
+    #[tokio::test]
+    async fn test_computed_column() {
+        let mut db = setup_temp_db().await;
+        let mut people = Table::new(
+            "people".to_string(),
+            Columns::new(vec![
+                Column::new("id", true),
+                Column::new("first", true),
+                Column::new("last", true),
+            ]),
+        );
+        db.add_table(&mut people).await.unwrap();
+        db.computed_column("people", "full_name", |row| {
+            json!(format!(
+                "{} {}",
+                row["first"].as_str().unwrap_or_default(),
+                row["last"].as_str().unwrap_or_default()
+            ))
+        })
+        .await
+        .unwrap();
+
+        db.execute_sql("INSERT INTO people (id, first, last) VALUES ('1', 'Ada', 'Lovelace')")
+            .await
+            .unwrap();
+        db.update_row()
+            .from("people")
+            .data(json!({"last": "King", "full_name": "ignored"}))
+            .where_eq::<Value>("id", "1")
+            .await
+            .unwrap();
+
+        let row: Option<Value> = db
+            .get_single()
+            .from("people")
+            .where_eq("full_name", "Ada King")
+            .await
+            .unwrap();
+        assert_eq!(row.unwrap()["last"], json!("King"));
+        assert!(db
+            .computed_column("people", "id", |_| json!(1))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_generated_columns_follow_the_schema() {
+        let mut db = setup_temp_db().await;
+        db.computed_column("TestTable", "shout", |row| {
+            json!(row["name"].as_str().unwrap_or_default().to_uppercase())
+        })
+        .await
+        .unwrap();
+
+        let on_disk = Database::load_from_file(&db.file_name).await.unwrap();
+        let columns = &on_disk.get_table("TestTable").unwrap().columns;
+        let shout = columns.0.iter().find(|c| c.name == "shout").unwrap();
+        assert_eq!(shout.generated, Some(Generated::Computed));
+
+        db.rename_column("TestTable", "shout", "loud")
+            .await
+            .unwrap();
+        add(&mut db, json!({"id": "1", "name": "ada"}))
+            .await
+            .unwrap();
+        let rows = db.get_rows().from("TestTable").rows().await.unwrap();
+        assert_eq!(rows, vec![json!({"id": "1", "name": "ada", "loud": "ADA"})]);
+
+        let mut db = Database::open(&db.file_name).await.unwrap();
+        db.drop_column("TestTable", "loud").await.unwrap();
+        add(&mut db, json!({"id": "2", "name": "bob"}))
+            .await
+            .unwrap();
+        let row: Option<Value> = db
+            .get_single()
+            .from("TestTable")
+            .where_eq("id", "2")
+            .await
+            .unwrap();
+        assert_eq!(row.unwrap(), json!({"id": "2", "name": "bob"}));
+    }
+
+    #[tokio::test]
+    async fn test_clear_hooks() {
+        let mut db = setup_temp_db().await;
+        db.before_insert("TestTable", |_| {
+            Err(DatabaseError::InvalidOperation("read only".to_string()))
+        });
+        assert!(add(&mut db, json!({"id": "1", "name": "alice"}))
+            .await
+            .is_err());
+
+        db.clear_hooks("TestTable");
+        assert!(add(&mut db, json!({"id": "1", "name": "alice"}))
+            .await
+            .is_ok());
+    }
+}
@@ -0,0 +1,473 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde_json::Value;
+use tracing;
+
+use super::hooks;
+use crate::database_components::alter;
+use crate::database_components::keys::key_of;
+use crate::{Column, Database, DatabaseError, Table};
+
+/// Rewrites the data of a single row in place.
+pub type RowTransform = Box<dyn Fn(&mut Value) -> Result<(), DatabaseError> + Send + Sync>;
+
+/// A single schema change applied by a `Migration`.
+pub enum MigrationStep {
+    /// Add `column` to `table`, filling `default` into every existing row.
+    AddColumn {
+        table: String,
+        column: Column,
+        default: Value,
+    },
+    /// Remove `column` from `table` and from every row.
+    DropColumn { table: String, column: String },
+    /// Rename the column `from` to `to` in `table` and in every row.
+    RenameColumn {
+        table: String,
+        from: String,
+        to: String,
+    },
+    /// Run `transform` on the data of every row in `table`.
+    TransformRows {
+        table: String,
+        transform: RowTransform,
+    },
+}
+
+impl MigrationStep {
+    pub fn add_column(table: &str, column: Column, default: Value) -> Self {
+        MigrationStep::AddColumn {
+            table: table.to_string(),
+            column,
+            default,
+        }
+    }
+
+    pub fn drop_column(table: &str, column: &str) -> Self {
+        MigrationStep::DropColumn {
+            table: table.to_string(),
+            column: column.to_string(),
+        }
+    }
+
+    pub fn rename_column(table: &str, from: &str, to: &str) -> Self {
+        MigrationStep::RenameColumn {
+            table: table.to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+        }
+    }
+
+    pub fn transform_rows<F>(table: &str, transform: F) -> Self
+    where
+        F: Fn(&mut Value) -> Result<(), DatabaseError> + Send + Sync + 'static,
+    {
+        MigrationStep::TransformRows {
+            table: table.to_string(),
+            transform: Box::new(transform),
+        }
+    }
+
+    fn table(&self) -> &str {
+        match self {
+            MigrationStep::AddColumn { table, .. }
+            | MigrationStep::DropColumn { table, .. }
+            | MigrationStep::RenameColumn { table, .. }
+            | MigrationStep::TransformRows { table, .. } => table,
+        }
+    }
+}
+
+impl fmt::Debug for MigrationStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationStep::AddColumn {
+                table,
+                column,
+                default,
+            } => f
+                .debug_struct("AddColumn")
+                .field("table", table)
+                .field("column", column)
+                .field("default", default)
+                .finish(),
+            MigrationStep::DropColumn { table, column } => f
+                .debug_struct("DropColumn")
+                .field("table", table)
+                .field("column", column)
+                .finish(),
+            MigrationStep::RenameColumn { table, from, to } => f
+                .debug_struct("RenameColumn")
+                .field("table", table)
+                .field("from", from)
+                .field("to", to)
+                .finish(),
+            MigrationStep::TransformRows { table, .. } => f
+                .debug_struct("TransformRows")
+                .field("table", table)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+/// A versioned schema change. `up` moves a database from the previous
+/// version to `version()`, `down` undoes it.
+pub trait Migration {
+    /// Schema version of the database once this migration has run.
+    fn version(&self) -> u32;
+
+    fn up(&self) -> Vec<MigrationStep>;
+
+    fn down(&self) -> Vec<MigrationStep>;
+}
+
+impl Database {
+    /// Schema version the persisted data is at, `0` for a database that has
+    /// never been migrated.
+    pub fn schema_version(&self) -> u32 {
+        self.schema_version
+    }
+
+    /// Run every migration newer than the current schema version, in version
+    /// order. Returns the resulting schema version.
+    pub async fn migrate(&mut self, migrations: &[&dyn Migration]) -> Result<u32, DatabaseError> {
+        let latest = migrations
+            .iter()
+            .map(|migration| migration.version())
+            .max()
+            .unwrap_or(self.schema_version)
+            .max(self.schema_version);
+        self.migrate_to(latest, migrations).await
+    }
+
+    /// Migrate up or down to `target`. Either every step succeeds or the
+    /// database is left untouched.
+    pub async fn migrate_to(
+        &mut self,
+        target: u32,
+        migrations: &[&dyn Migration],
+    ) -> Result<u32, DatabaseError> {
+        let mut ordered: Vec<&dyn Migration> = migrations.to_vec();
+        ordered.sort_by_key(|migration| migration.version());
+        if let Some(pair) = ordered
+            .windows(2)
+            .find(|pair| pair[0].version() == pair[1].version())
+        {
+            return Err(DatabaseError::MigrationError(format!(
+                "duplicate migration version {}",
+                pair[0].version()
+            )));
+        }
+        if target != 0
+            && !ordered
+                .iter()
+                .any(|migration| migration.version() == target)
+        {
+            return Err(DatabaseError::MigrationError(format!(
+                "no migration with version {}",
+                target
+            )));
+        }
+
+        let current = self.schema_version;
+        if target == current {
+            return Ok(current);
+        }
+
+        let mut tables = self.tables.clone();
+        let mut moved = Vec::new();
+        if target > current {
+            for migration in ordered
+                .iter()
+                .filter(|m| m.version() > current && m.version() <= target)
+            {
+                apply_steps(&mut tables, migration.up(), migration.version(), &mut moved)?;
+                tracing::info!("Applied migration {}", migration.version());
+            }
+        } else {
+            for migration in ordered
+                .iter()
+                .rev()
+                .filter(|m| m.version() > target && m.version() <= current)
+            {
+                apply_steps(
+                    &mut tables,
+                    migration.down(),
+                    migration.version(),
+                    &mut moved,
+                )?;
+                tracing::info!("Reverted migration {}", migration.version());
+            }
+        }
+
+        for (table, column, renamed) in moved {
+            match renamed {
+                Some(new) => hooks::rename_column(&self.file_name, &table, &column, &new),
+                None => hooks::drop_column(&self.file_name, &table, &column),
+            }
+        }
+        self.tables = tables;
+        self.schema_version = target;
+        self.persist().await?;
+        Ok(target)
+    }
+}
+
+// columns that were dropped (`None`) or renamed, as (table, column, new
+// name), whose generated values follow once the migration is kept
+type MovedColumns = Vec<(String, String, Option<String>)>;
+
+fn apply_steps(
+    tables: &mut HashMap<String, Table>,
+    steps: Vec<MigrationStep>,
+    version: u32,
+    moved: &mut MovedColumns,
+) -> Result<(), DatabaseError> {
+    for step in steps {
+        let table_name = step.table().to_string();
+        if !tables.contains_key(&table_name) {
+            return Err(DatabaseError::MigrationError(format!(
+                "migration {}: table `{}` not found",
+                version, table_name
+            )));
+        }
+        apply_step(tables, step, moved)
+            .map_err(|e| DatabaseError::MigrationError(format!("migration {}: {}", version, e)))?;
+        if let Some(table) = tables.get_mut(&table_name) {
+            table.dirty = true;
+        }
+    }
+    Ok(())
+}
+
+// column changes go through the same rules as `Database::drop_column` and
+// `Database::rename_column`
+fn apply_step(
+    tables: &mut HashMap<String, Table>,
+    step: MigrationStep,
+    moved: &mut MovedColumns,
+) -> Result<(), String> {
+    match step {
+        MigrationStep::AddColumn {
+            table,
+            column,
+            default,
+        } => tables
+            .get_mut(&table)
+            .ok_or_else(|| format!("table `{}` not found", table))?
+            .add_column(column, default)
+            .map_err(|e| e.to_string())?,
+        MigrationStep::DropColumn { table, column } => {
+            alter::drop_column(tables, &table, &column).map_err(|e| e.to_string())?;
+            moved.push((table, column, None));
+        }
+        MigrationStep::RenameColumn { table, from, to } => {
+            alter::rename_column(tables, &table, &from, &to).map_err(|e| e.to_string())?;
+            moved.push((table, from, Some(to)));
+        }
+        MigrationStep::TransformRows { table, transform } => {
+            let table = tables
+                .get_mut(&table)
+                .ok_or_else(|| format!("table `{}` not found", table))?;
+            for (row_id, row) in table.rows.iter_mut() {
+                transform(&mut row.data).map_err(|e| e.to_string())?;
+                if key_of(&table.primary_key, &row.data).as_deref() != Some(row_id.as_str()) {
+                    return Err(format!("transform changed the key of row `{}`", row_id));
+                }
+                table
+                    .columns
+                    .validate(row.data.clone())
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{setup_temp_db, Columns};
+
+    struct AddEmail;
+
+    impl Migration for AddEmail {
+        fn version(&self) -> u32 {
+            1
+        }
+
+        fn up(&self) -> Vec<MigrationStep> {
+            vec![MigrationStep::add_column(
+                "users",
+                Column::new("email", false),
+                json!("unknown@example.com"),
+            )]
+        }
+
+        fn down(&self) -> Vec<MigrationStep> {
+            vec![MigrationStep::drop_column("users", "email")]
+        }
+    }
+
+    struct RenameAndUppercase;
+
+    impl Migration for RenameAndUppercase {
+        fn version(&self) -> u32 {
+            2
+        }
+
+        fn up(&self) -> Vec<MigrationStep> {
+            vec![
+                MigrationStep::rename_column("users", "name", "full_name"),
+                MigrationStep::transform_rows("users", |row| {
+                    let upper = row["full_name"].as_str().unwrap_or_default().to_uppercase();
+                    row["full_name"] = json!(upper);
+                    Ok(())
+                }),
+            ]
+        }
+
+        fn down(&self) -> Vec<MigrationStep> {
+            vec![MigrationStep::rename_column("users", "full_name", "name")]
+        }
+    }
+
+    struct Broken;
+
+    impl Migration for Broken {
+        fn version(&self) -> u32 {
+            3
+        }
+
+        fn up(&self) -> Vec<MigrationStep> {
+            vec![
+                MigrationStep::drop_column("users", "email"),
+                MigrationStep::drop_column("users", "missing"),
+            ]
+        }
+
+        fn down(&self) -> Vec<MigrationStep> {
+            vec![]
+        }
+    }
+
+    async fn setup_users_db() -> Database {
+        let mut db = setup_temp_db().await;
+        let mut users = Table::new(
+            "users".to_string(),
+            Columns::new(vec![Column::new("id", true), Column::new("name", true)]),
+        );
+        db.add_table(&mut users).await.unwrap();
+        users
+            .add_row(&mut db, json!({"id": "1", "name": "John Doe"}))
+            .await;
+        db
+    }
+
+    fn user(db: &Database) -> Value {
+        db.tables["users"].rows["1"].data.clone()
+    }
+
+    #[tokio::test]
+    async fn test_migrate_up() {
+        let mut db = setup_users_db().await;
+        assert_eq!(db.schema_version(), 0);
+
+        let version = db.migrate(&[&RenameAndUppercase, &AddEmail]).await.unwrap();
+
+        assert_eq!(version, 2);
+        assert_eq!(
+            user(&db),
+            json!({"id": "1", "full_name": "JOHN DOE", "email": "unknown@example.com"})
+        );
+
+        // the version is persisted with the data
+        let on_disk = Database::load_from_file(&db.file_name).await.unwrap();
+        assert_eq!(on_disk.schema_version(), 2);
+
+        // already up to date
+        let version = db.migrate(&[&AddEmail, &RenameAndUppercase]).await.unwrap();
+        assert_eq!(version, 2);
+    }
+
+    #[tokio::test]
+    async fn test_migrate_down() {
+        let mut db = setup_users_db().await;
+        db.migrate(&[&AddEmail, &RenameAndUppercase]).await.unwrap();
+
+        let version = db
+            .migrate_to(0, &[&AddEmail, &RenameAndUppercase])
+            .await
+            .unwrap();
+
+        assert_eq!(version, 0);
+        assert_eq!(user(&db), json!({"id": "1", "name": "JOHN DOE"}));
+        assert_eq!(
+            db.tables["users"].columns,
+            Columns::new(vec![Column::new("id", true), Column::new("name", true)])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_migration_leaves_database_untouched() {
+        let mut db = setup_users_db().await;
+        db.migrate(&[&AddEmail]).await.unwrap();
+        let before = db.tables.clone();
+
+        let result = db.migrate(&[&AddEmail, &RenameAndUppercase, &Broken]).await;
+
+        assert!(matches!(result, Err(DatabaseError::MigrationError(_))));
+        assert_eq!(db.schema_version(), 1);
+        assert_eq!(db.tables, before);
+    }
+
+    struct RenameId;
+
+    impl Migration for RenameId {
+        fn version(&self) -> u32 {
+            1
+        }
+
+        fn up(&self) -> Vec<MigrationStep> {
+            vec![MigrationStep::rename_column("users", "id", "user_id")]
+        }
+
+        fn down(&self) -> Vec<MigrationStep> {
+            vec![MigrationStep::drop_column("users", "user_id")]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_migrations_follow_foreign_keys() {
+        let mut db = setup_users_db().await;
+        let mut posts = Table::new(
+            "posts".to_string(),
+            Columns::new(vec![
+                Column::new("id", true),
+                Column::new("author", true).references("users", "id"),
+            ]),
+        );
+        db.add_table(&mut posts).await.unwrap();
+
+        db.migrate(&[&RenameId]).await.unwrap();
+        let fk = db.tables["posts"].columns.0[1].references.clone();
+        assert_eq!(fk.unwrap().column, "user_id");
+
+        let result = db.migrate_to(0, &[&RenameId]).await;
+        assert!(
+            matches!(result, Err(DatabaseError::MigrationError(m)) if m.contains("foreign key"))
+        );
+        assert_eq!(db.schema_version(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_migration_versions() {
+        let mut db = setup_users_db().await;
+
+        let result = db.migrate(&[&AddEmail, &AddEmail]).await;
+        assert!(matches!(result, Err(DatabaseError::MigrationError(_))));
+    }
+}
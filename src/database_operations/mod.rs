@@ -1,14 +1,57 @@
+pub mod autosave;
+pub mod backup;
+pub mod changes;
 pub mod core;
+pub mod hooks;
+pub mod migrations;
+pub mod snapshots;
+pub mod storage;
+
+pub use autosave::SavePolicy;
+pub use backup::{BackupProgress, BackupReport};
+pub use changes::{ChangeEvent, ChangeFeed};
+pub use migrations::{Migration, MigrationStep};
+pub use storage::StorageLayout;
+
+use autosave::AutoSave;
 
 use crate::Table;
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Database {
     pub(crate) name: String,
     pub(crate) file_name: PathBuf,
     pub(crate) tables: HashMap<String, Table>,
+    #[serde(default)]
+    pub(crate) layout: StorageLayout,
+    #[serde(default)]
+    pub(crate) schema_version: u32,
+    // set by every mutation, cleared once the change has been written
+    #[serde(skip)]
+    pub(crate) dirty: bool,
+    #[serde(skip)]
+    pub(crate) save_policy: SavePolicy,
+    #[serde(skip)]
+    pub(crate) autosave: AutoSave,
+}
+
+// only the persisted state takes part in comparisons
+impl PartialEq for Database {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.file_name == other.file_name
+            && self.tables == other.tables
+            && self.layout == other.layout
+            && self.schema_version == other.schema_version
+    }
+}
+
+// key of the process-wide registries (change feeds, hooks) shared by every
+// handle on the same database file
+pub(crate) fn registry_key(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
 }
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tracing;
+
+use super::storage::write_atomic;
+use crate::{Database, DatabaseError, StorageLayout, Table};
+
+const SNAPSHOT_DIR: &str = "snapshots";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Snapshot {
+    label: String,
+    tables: HashMap<String, Table>,
+}
+
+impl Database {
+    /// Record a copy of every table under `label`, replacing any earlier
+    /// snapshot with the same label. Snapshots are stored next to the
+    /// database and survive restarts.
+    pub async fn snapshot(&self, label: &str) -> Result<(), DatabaseError> {
+        validate_label(label)?;
+
+        let snapshot = Snapshot {
+            label: label.to_string(),
+            tables: self.tables.clone(),
+        };
+        let json_data = serde_json::to_string(&snapshot)?;
+
+        let dir = self.snapshot_dir();
+        tokio::fs::create_dir_all(&dir)
+            .await
+            .map_err(DatabaseError::SaveError)?;
+        write_atomic(dir.join(format!("{label}.json")), json_data)
+            .await
+            .map_err(DatabaseError::SaveError)?;
+
+        tracing::info!("Snapshot `{}` taken of database `{}`", label, self.name);
+        Ok(())
+    }
+
+    /// Labels of every stored snapshot, sorted.
+    pub async fn list_snapshots(&self) -> Result<Vec<String>, DatabaseError> {
+        let mut entries = match tokio::fs::read_dir(self.snapshot_dir()).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(DatabaseError::LoadError(e)),
+        };
+
+        let mut labels = Vec::new();
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .map_err(DatabaseError::LoadError)?
+        {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if let Some(label) = file_name.strip_suffix(".json") {
+                if !label.starts_with('.') {
+                    labels.push(label.to_string());
+                }
+            }
+        }
+        labels.sort();
+        Ok(labels)
+    }
+
+    /// Replace every table with the contents of the `label` snapshot. The
+    /// restored state is written according to the save policy.
+    pub async fn restore_snapshot(&mut self, label: &str) -> Result<(), DatabaseError> {
+        validate_label(label)?;
+
+        let path = self.snapshot_dir().join(format!("{label}.json"));
+        let json_data = match tokio::fs::read_to_string(&path).await {
+            Ok(json_data) => json_data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(DatabaseError::SnapshotNotFound(label.to_string()))
+            }
+            Err(e) => return Err(DatabaseError::LoadError(e)),
+        };
+        let snapshot: Snapshot = serde_json::from_str(&json_data)?;
+
+        self.tables = snapshot.tables;
+        for table in self.tables.values_mut() {
+            table.dirty = true;
+        }
+        self.persist().await?;
+
+        tracing::info!("Snapshot `{}` restored to database `{}`", label, self.name);
+        Ok(())
+    }
+
+    pub(crate) fn snapshot_dir(&self) -> PathBuf {
+        match self.layout {
+            StorageLayout::SingleFile => {
+                let mut dir = self.file_name.clone().into_os_string();
+                dir.push(".snapshots");
+                dir.into()
+            }
+            StorageLayout::Directory => self.file_name.join(SNAPSHOT_DIR),
+        }
+    }
+}
+
+// labels become file names, so keep them to a safe character set
+fn validate_label(label: &str) -> Result<(), DatabaseError> {
+    let valid = !label.is_empty()
+        && !label.starts_with('.')
+        && label
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+
+    if valid {
+        Ok(())
+    } else {
+        Err(DatabaseError::InvalidData(format!(
+            "Invalid snapshot label `{}`: use letters, digits, `_`, `-` and `.`",
+            label
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{setup_temp_db, Column, Columns};
+
+    #[tokio::test]
+    async fn test_snapshot_and_restore() {
+        let mut db = setup_temp_db().await;
+        let mut users = Table::new(
+            "users".to_string(),
+            Columns::new(vec![Column::new("id", true), Column::new("name", true)]),
+        );
+        db.add_table(&mut users).await.unwrap();
+        users
+            .add_row(&mut db, json!({"id": "1", "name": "John Doe"}))
+            .await;
+
+        db.snapshot("seeded").await.unwrap();
+        let seeded_tables = db.tables.clone();
+
+        users
+            .add_row(&mut db, json!({"id": "2", "name": "Jane Doe"}))
+            .await;
+        db.drop_table("TestTable").await.unwrap();
+
+        db.restore_snapshot("seeded").await.unwrap();
+        assert_eq!(db.tables, seeded_tables);
+
+        // the restored state is what queries see on disk
+        let on_disk = Database::load_from_file(&db.file_name).await.unwrap();
+        assert_eq!(on_disk.tables, seeded_tables);
+
+        db.drop_database().await.unwrap();
+        assert!(!db.snapshot_dir().exists());
+    }
+
+    #[tokio::test]
+    async fn test_list_snapshots() {
+        let db = setup_temp_db().await;
+        assert!(db.list_snapshots().await.unwrap().is_empty());
+
+        db.snapshot("second").await.unwrap();
+        db.snapshot("first").await.unwrap();
+        db.snapshot("first").await.unwrap();
+
+        assert_eq!(db.list_snapshots().await.unwrap(), vec!["first", "second"]);
+
+        db.drop_database().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_restore_missing_snapshot() {
+        let mut db = setup_temp_db().await;
+
+        let result = db.restore_snapshot("missing").await;
+        assert!(matches!(result, Err(DatabaseError::SnapshotNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_invalid_label() {
+        let db = setup_temp_db().await;
+
+        for label in ["", "../escape", ".hidden", "with space"] {
+            let result = db.snapshot(label).await;
+            assert!(matches!(result, Err(DatabaseError::InvalidData(_))));
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing;
+use uuid::Uuid;
+
+use crate::{Database, Table};
+
+const MANIFEST_FILE: &str = "manifest.json";
+const TABLES_DIR: &str = "tables";
+
+/// How a `Database` is laid out on disk.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+pub enum StorageLayout {
+    /// Every table lives in a single `<name>.json` file.
+    #[default]
+    SingleFile,
+    /// `<name>/manifest.json` holds the database metadata and every table
+    /// lives in its own `<name>/tables/<table>.json` file. Only tables that
+    /// changed since the last save are rewritten.
+    Directory,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    name: String,
+    #[serde(default)]
+    schema_version: u32,
+    tables: Vec<String>,
+}
+
+/// When a planned file actually needs to be written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WriteMode {
+    Always,
+    /// Small metadata files are only rewritten when their contents change.
+    IfChanged,
+}
+
+/// Everything a save has to touch, computed up front so it can be carried
+/// out from a blocking task or from `Drop`.
+#[derive(Debug)]
+pub(crate) struct SavePlan {
+    create_dir: Option<PathBuf>,
+    files: Vec<(PathBuf, String, WriteMode)>,
+    // directory to prune and the file names that must be kept in it
+    prune: Option<(PathBuf, HashSet<String>)>,
+}
+
+impl Database {
+    pub(crate) fn save_plan(&self) -> Result<SavePlan, tokio::io::Error> {
+        match self.layout {
+            StorageLayout::SingleFile => Ok(SavePlan {
+                create_dir: None,
+                files: vec![(
+                    self.file_name.clone(),
+                    serde_json::to_string_pretty(&self)?,
+                    WriteMode::Always,
+                )],
+                prune: None,
+            }),
+            StorageLayout::Directory => self.directory_save_plan(),
+        }
+    }
+
+    fn directory_save_plan(&self) -> Result<SavePlan, tokio::io::Error> {
+        let tables_dir = self.file_name.join(TABLES_DIR);
+
+        let mut table_names: Vec<String> = self.tables.keys().cloned().collect();
+        table_names.sort();
+
+        let manifest = Manifest {
+            name: self.name.clone(),
+            schema_version: self.schema_version,
+            tables: table_names,
+        };
+        let mut files = vec![(
+            self.file_name.join(MANIFEST_FILE),
+            serde_json::to_string_pretty(&manifest)?,
+            WriteMode::IfChanged,
+        )];
+
+        for table in self.tables.values() {
+            let table_path = tables_dir.join(table_file_name(&table.name));
+            // clean tables are skipped unless their file has gone missing
+            if table.dirty || !table_path.exists() {
+                // going through `Value` sorts the row keys so the files diff cleanly
+                let table_json = serde_json::to_string_pretty(&serde_json::to_value(table)?)?;
+                files.push((table_path, table_json, WriteMode::Always));
+            }
+        }
+
+        // files left behind by dropped or renamed tables are removed
+        let expected: HashSet<String> = self
+            .tables
+            .values()
+            .map(|table| table_file_name(&table.name))
+            .collect();
+
+        Ok(SavePlan {
+            create_dir: Some(tables_dir.clone()),
+            files,
+            prune: Some((tables_dir, expected)),
+        })
+    }
+
+    pub(crate) fn mark_clean(&mut self) {
+        self.dirty = false;
+        for table in self.tables.values_mut() {
+            table.dirty = false;
+        }
+    }
+
+    pub(crate) fn load_from_directory(dir: &Path) -> Result<Self, std::io::Error> {
+        let manifest_json = std::fs::read_to_string(dir.join(MANIFEST_FILE))?;
+        let manifest: Manifest = serde_json::from_str(&manifest_json)?;
+
+        let tables_dir = dir.join(TABLES_DIR);
+        let mut tables = HashMap::new();
+        for table_name in manifest.tables {
+            let table_json =
+                std::fs::read_to_string(tables_dir.join(table_file_name(&table_name)))?;
+            let table: Table = serde_json::from_str(&table_json)?;
+            tables.insert(table_name, table);
+        }
+
+        let mut db = Database::empty(&manifest.name, dir.to_path_buf(), StorageLayout::Directory);
+        db.tables = tables;
+        db.schema_version = manifest.schema_version;
+        Ok(db)
+    }
+}
+
+impl SavePlan {
+    pub(crate) fn apply_blocking(self) -> Result<(), std::io::Error> {
+        if let Some(dir) = &self.create_dir {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        for (path, contents, mode) in self.files {
+            match mode {
+                WriteMode::Always => write_atomic_blocking(&path, contents)?,
+                WriteMode::IfChanged => {
+                    let current = std::fs::read_to_string(&path).ok();
+                    if current.as_deref() != Some(contents.as_str()) {
+                        write_atomic_blocking(&path, contents)?;
+                    }
+                }
+            }
+        }
+
+        if let Some((dir, expected)) = self.prune {
+            for entry in std::fs::read_dir(&dir)? {
+                let entry = entry?;
+                if is_stale(&entry.file_name(), &expected) {
+                    std::fs::remove_file(entry.path())?;
+                    tracing::debug!("Removed stale table file: {:?}", entry.path());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// in-flight temp files start with a dot and are left alone
+fn is_stale(file_name: &std::ffi::OsStr, expected: &HashSet<String>) -> bool {
+    let file_name = file_name.to_string_lossy();
+    !file_name.starts_with('.') && !expected.contains(file_name.as_ref())
+}
+
+pub(crate) fn temp_path_for(path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.tmp", file_name, Uuid::new_v4()))
+}
+
+/// Write `contents` to a sibling temp file and rename it over `path`, so
+/// readers never observe a partially written file.
+pub(crate) async fn write_atomic<P, C>(path: P, contents: C) -> Result<(), tokio::io::Error>
+where
+    P: AsRef<Path>,
+    C: AsRef<[u8]>,
+{
+    let path = path.as_ref();
+    let temp_path = temp_path_for(path);
+
+    if let Err(e) = tokio::fs::write(&temp_path, contents).await {
+        tokio::fs::remove_file(&temp_path).await.ok();
+        return Err(e);
+    }
+    if let Err(e) = tokio::fs::rename(&temp_path, path).await {
+        tokio::fs::remove_file(&temp_path).await.ok();
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Blocking counterpart of `write_atomic`, used for saves.
+pub(crate) fn write_atomic_blocking<P, C>(path: P, contents: C) -> Result<(), std::io::Error>
+where
+    P: AsRef<Path>,
+    C: AsRef<[u8]>,
+{
+    let path = path.as_ref();
+    let temp_path = temp_path_for(path);
+
+    if let Err(e) = std::fs::write(&temp_path, contents) {
+        std::fs::remove_file(&temp_path).ok();
+        return Err(e);
+    }
+    if let Err(e) = std::fs::rename(&temp_path, path) {
+        std::fs::remove_file(&temp_path).ok();
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Table names are free-form, so anything that is not safe in a file name
+/// is percent-encoded.
+fn table_file_name(table_name: &str) -> String {
+    let mut encoded = String::with_capacity(table_name.len() + 5);
+    for byte in table_name.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-' {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded.push_str(".json");
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{Column, Columns};
+
+    fn temp_dir_path() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().expect("Failed to create a temporary directory");
+        let path = dir.path().join("dir_db");
+        (dir, path)
+    }
+
+    fn users_table() -> Table {
+        Table::new(
+            "users".to_string(),
+            Columns::new(vec![Column::new("id", true), Column::new("name", true)]),
+        )
+    }
+
+    #[test]
+    fn test_table_file_name() {
+        assert_eq!(table_file_name("users"), "users.json");
+        assert_eq!(table_file_name("user posts/2"), "user%20posts%2F2.json");
+    }
+
+    #[tokio::test]
+    async fn test_directory_layout_round_trip() {
+        let (_dir, path) = temp_dir_path();
+        let mut db = Database::with_layout(path.to_str().unwrap(), StorageLayout::Directory).await;
+
+        let mut users = users_table();
+        db.add_table(&mut users).await.unwrap();
+        users
+            .add_row(&mut db, json!({"id": "1", "name": "John Doe"}))
+            .await;
+
+        assert!(path.join(MANIFEST_FILE).exists());
+        assert!(path.join(TABLES_DIR).join("users.json").exists());
+
+        let loaded = Database::load_from_file(&path).await.unwrap();
+        assert_eq!(loaded.layout, StorageLayout::Directory);
+        assert_eq!(loaded.tables, db.tables);
+    }
+
+    #[tokio::test]
+    async fn test_directory_layout_only_rewrites_dirty_tables() {
+        let (_dir, path) = temp_dir_path();
+        let mut db = Database::with_layout(path.to_str().unwrap(), StorageLayout::Directory).await;
+
+        let mut users = users_table();
+        db.add_table(&mut users).await.unwrap();
+        let mut posts = Table::new(
+            "posts".to_string(),
+            Columns::new(vec![Column::new("id", true)]),
+        );
+        db.add_table(&mut posts).await.unwrap();
+
+        // replace the posts file with a marker; a clean table must not be rewritten
+        let posts_path = path.join(TABLES_DIR).join("posts.json");
+        tokio::fs::write(&posts_path, "marker").await.unwrap();
+
+        users
+            .add_row(&mut db, json!({"id": "1", "name": "John Doe"}))
+            .await;
+
+        let posts_contents = tokio::fs::read_to_string(&posts_path).await.unwrap();
+        assert_eq!(posts_contents, "marker");
+
+        let users_contents = tokio::fs::read_to_string(path.join(TABLES_DIR).join("users.json"))
+            .await
+            .unwrap();
+        assert!(users_contents.contains("John Doe"));
+    }
+
+    #[tokio::test]
+    async fn test_directory_layout_removes_dropped_tables() {
+        let (_dir, path) = temp_dir_path();
+        let mut db = Database::with_layout(path.to_str().unwrap(), StorageLayout::Directory).await;
+
+        let mut users = users_table();
+        db.add_table(&mut users).await.unwrap();
+        db.rename_table("users", "members").await.unwrap();
+
+        assert!(!path.join(TABLES_DIR).join("users.json").exists());
+        assert!(path.join(TABLES_DIR).join("members.json").exists());
+
+        db.drop_table("members").await.unwrap();
+        assert!(!path.join(TABLES_DIR).join("members.json").exists());
+
+        let loaded = Database::load_from_file(&path).await.unwrap();
+        assert!(loaded.tables.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_write_atomic_leaves_no_temp_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.json");
+
+        write_atomic(&path, "{}").await.unwrap();
+
+        let entries: Vec<_> = std::fs::read_dir(dir.path()).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{}");
+    }
+}
@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::database_components::SchemaViolation;
+
 #[derive(Error, Debug)]
 pub enum DatabaseError {
     #[error("Failed to load the batabase: `{0}`")]
@@ -17,21 +19,49 @@ pub enum DatabaseError {
     #[error("Table `{0}` not found")]
     TableNotFound(String),
 
+    #[error("Snapshot `{0}` not found")]
+    SnapshotNotFound(String),
+
+    #[error("Backup failed: {0}")]
+    BackupError(String),
+
+    #[error("Migration failed: {0}")]
+    MigrationError(String),
+
+    #[error("SQL error at line {line}, column {column}: {message}")]
+    SqlError {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+
     #[error("Invalid data: `{0}`")]
     InvalidData(String),
 
     #[error("Row not found with {0} = {1}")]
     RowNotFound(String, String),
 
+    #[error(
+        "Row does not match the table schema: {}",
+        .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+    )]
+    SchemaViolation(Vec<SchemaViolation>),
+
     #[error("Column `{0}` is missing from the row data")]
     MissingColumn(String),
 
     #[error("Column `{0}` is required")]
     ColumnRequiredError(String),
 
-    #[error("")] // could expand to specify serialization/deserialization error
+    #[error("JSON error: {0}")]
     JSONError(#[from] serde_json::Error),
 
-    #[error("")] // could expand to specify serialization/deserialization Error
+    #[error("I/O error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("CSV error: {0}")]
+    CsvError(#[from] csv::Error),
+
+    #[error("Invalid operation: {0}")]
     InvalidOperation(String),
 }
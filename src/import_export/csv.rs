@@ -0,0 +1,348 @@
+use std::io::{Read, Write};
+
+use serde_json::{Map, Number, Value};
+use tracing;
+
+use super::{ImportError, ImportReport};
+use crate::database_components::table::OnConflict;
+use crate::{Column, ColumnType, Database, DatabaseError, Table};
+
+/// How CSV input is read by `Table::import_csv`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvOptions {
+    pub delimiter: u8,
+    /// Trim whitespace around every field.
+    pub trim: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: b',',
+            trim: true,
+        }
+    }
+}
+
+impl Table {
+    /// Import the records of a CSV document into this table. The header row
+    /// names the columns, every field is converted to its column's
+    /// `ColumnType` and each record is checked with `Columns::validate`.
+    /// Rejected records are listed in the returned report by line.
+    pub async fn import_csv<R: Read>(
+        &self,
+        db: &mut Database,
+        reader: R,
+        options: CsvOptions,
+    ) -> Result<ImportReport, DatabaseError> {
+        let hooks = db.hooks(&self.name);
+        let table = db
+            .get_table_mut(&self.name)
+            .ok_or_else(|| DatabaseError::TableNotFound(self.name.clone()))?;
+
+        let mut csv_reader = ::csv::ReaderBuilder::new()
+            .delimiter(options.delimiter)
+            .trim(if options.trim {
+                ::csv::Trim::All
+            } else {
+                ::csv::Trim::None
+            })
+            .from_reader(reader);
+
+        let mut header_columns: Vec<Column> = Vec::new();
+        for header in csv_reader.headers()?.iter() {
+            let column = table
+                .columns
+                .0
+                .iter()
+                .find(|column| column.name == header)
+                .ok_or_else(|| {
+                    DatabaseError::InvalidData(format!("Column '{}' is not valid.", header))
+                })?;
+            if header_columns.iter().any(|c| c.name == header) {
+                return Err(DatabaseError::InvalidData(format!(
+                    "Column '{}' appears more than once.",
+                    header
+                )));
+            }
+            header_columns.push(column.clone());
+        }
+
+        let mut report = ImportReport::default();
+        for result in csv_reader.records() {
+            let record = match result {
+                Ok(record) => record,
+                Err(e) => {
+                    let line = e.position().map(|p| p.line()).unwrap_or_default();
+                    report.errors.push(ImportError {
+                        line,
+                        message: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+            let line = record.position().map(|p| p.line()).unwrap_or_default();
+
+            let inserted = record_to_row(&header_columns, &record)
+                .and_then(|row| table.insert_row(row, hooks.as_deref(), OnConflict::Fail));
+            match inserted {
+                Ok(_) => report.imported += 1,
+                Err(e) => report.errors.push(ImportError {
+                    line,
+                    message: e.to_string(),
+                }),
+            }
+        }
+
+        if report.imported > 0 {
+            db.persist().await?;
+        }
+        tracing::info!(
+            "Imported {} CSV record(s) into `{}`, {} rejected",
+            report.imported,
+            self.name,
+            report.errors.len()
+        );
+        Ok(report)
+    }
+
+    /// Write every row as CSV, with the columns in the order they are
+    /// declared in `Table.columns`. Returns the number of rows written.
+    pub fn export_csv<W: Write>(&self, writer: W) -> Result<usize, DatabaseError> {
+        let mut csv_writer = ::csv::Writer::from_writer(writer);
+        csv_writer.write_record(self.columns.0.iter().map(|column| column.name.as_str()))?;
+
+        let mut row_ids: Vec<&String> = self.rows.keys().collect();
+        row_ids.sort();
+
+        for row_id in &row_ids {
+            let row = &self.rows[*row_id];
+            let record: Vec<String> = self
+                .columns
+                .0
+                .iter()
+                .map(|column| field_from_value(row.data.get(&column.name)))
+                .collect();
+            csv_writer.write_record(&record)?;
+        }
+
+        csv_writer
+            .flush()
+            .map_err(|e| DatabaseError::CsvError(e.into()))?;
+        Ok(row_ids.len())
+    }
+}
+
+fn record_to_row(columns: &[Column], record: &::csv::StringRecord) -> Result<Value, DatabaseError> {
+    let mut row = Map::new();
+    for (column, field) in columns.iter().zip(record.iter()) {
+        if let Some(value) = coerce_field(column, field)? {
+            row.insert(column.name.clone(), value);
+        }
+    }
+    Ok(Value::Object(row))
+}
+
+// empty fields are left out of the row, except for text columns where an
+// empty string is a real value
+fn coerce_field(column: &Column, field: &str) -> Result<Option<Value>, DatabaseError> {
+    let invalid = |expected: &str| {
+        DatabaseError::InvalidData(format!(
+            "Column '{}' expects {}, got '{}'.",
+            column.name, expected, field
+        ))
+    };
+
+    if field.is_empty() && !matches!(column.column_type, ColumnType::Text | ColumnType::Any) {
+        return Ok(None);
+    }
+
+    let value = match column.column_type {
+        ColumnType::Any | ColumnType::Text => Value::String(field.to_string()),
+        ColumnType::Integer => field
+            .parse::<i64>()
+            .map(Value::from)
+            .map_err(|_| invalid("an integer"))?,
+        ColumnType::Float => field
+            .parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .map(Value::Number)
+            .ok_or_else(|| invalid("a number"))?,
+        ColumnType::Boolean => match field.to_ascii_lowercase().as_str() {
+            "true" | "yes" | "1" => Value::Bool(true),
+            "false" | "no" | "0" => Value::Bool(false),
+            _ => return Err(invalid("a boolean")),
+        },
+        ColumnType::Array => match serde_json::from_str(field) {
+            Ok(value @ Value::Array(_)) => value,
+            _ => return Err(invalid("a JSON array")),
+        },
+        ColumnType::Object => match serde_json::from_str(field) {
+            Ok(value @ Value::Object(_)) => value,
+            _ => return Err(invalid("a JSON object")),
+        },
+    };
+    Ok(Some(value))
+}
+
+fn field_from_value(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    use super::*;
+    use crate::{setup_temp_db, Columns};
+
+    #[derive(Serialize, Deserialize, Default)]
+    struct Person {
+        id: String,
+        name: String,
+        age: u32,
+        score: f64,
+        active: bool,
+        tags: Vec<String>,
+    }
+
+    async fn setup_people() -> (Database, Table) {
+        let mut db = setup_temp_db().await;
+        let mut people = Table::new("people".to_string(), Columns::from_struct::<Person>(true));
+        db.add_table(&mut people).await.unwrap();
+        (db, people)
+    }
+
+    #[tokio::test]
+    async fn test_import_csv_coerces_types() {
+        let (mut db, people) = setup_people().await;
+        let input = "\
+id,name,age,score,active,tags
+1,John Doe,42,9.5,true,\"[\"\"admin\"\"]\"
+2,Jane Doe,37,7,no,[]
+";
+
+        let report = people
+            .import_csv(&mut db, input.as_bytes(), CsvOptions::default())
+            .await
+            .unwrap();
+
+        assert!(report.is_clean(), "{:?}", report.errors);
+        assert_eq!(report.imported, 2);
+        let table = db.get_table("people").unwrap();
+        assert_eq!(
+            table.rows["1"].data,
+            json!({"id": "1", "name": "John Doe", "age": 42, "score": 9.5, "active": true, "tags": ["admin"]})
+        );
+        assert_eq!(table.rows["2"].data["active"], json!(false));
+
+        // imported rows are persisted
+        let on_disk = Database::load_from_file(&db.file_name).await.unwrap();
+        assert_eq!(on_disk.tables["people"].rows.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_import_csv_reports_line_errors() {
+        let (mut db, people) = setup_people().await;
+        let input = "\
+id,name,age,score,active,tags
+1,John Doe,forty,9.5,true,[]
+2,Jane Doe,37,7,false,[]
+2,Jane Again,38,7,false,[]
+3,No Age,,7,false,[]
+";
+
+        let report = people
+            .import_csv(&mut db, input.as_bytes(), CsvOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(report.imported, 1);
+        let lines: Vec<u64> = report.errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, vec![2, 4, 5]);
+        assert!(report.errors[0].message.contains("expects an integer"));
+        assert!(report.errors[1].message.contains("already exists"));
+        assert!(report.errors[2].message.contains("required"));
+    }
+
+    #[tokio::test]
+    async fn test_import_csv_unknown_header() {
+        let (mut db, people) = setup_people().await;
+        let input = "id,name,phone\n1,John Doe,555\n";
+
+        let result = people
+            .import_csv(&mut db, input.as_bytes(), CsvOptions::default())
+            .await;
+
+        assert!(matches!(result, Err(DatabaseError::InvalidData(_))));
+        assert_eq!(db.count_rows("people").unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_import_csv_custom_delimiter() {
+        let (mut db, people) = setup_people().await;
+        let input = "id;name;age;score;active;tags\n1;John Doe;42;1.5;1;[]\n";
+        let options = CsvOptions {
+            delimiter: b';',
+            ..CsvOptions::default()
+        };
+
+        let report = people
+            .import_csv(&mut db, input.as_bytes(), options)
+            .await
+            .unwrap();
+
+        assert_eq!(report.imported, 1);
+    }
+
+    #[test]
+    fn test_export_csv_uses_column_order() {
+        let mut table = Table::new(
+            "people".to_string(),
+            Columns::new(vec![
+                Column::new("id", true),
+                Column::new("name", true),
+                Column::new("tags", false),
+            ]),
+        );
+        table
+            .add_single_row(json!({"name": "Jane, Doe", "id": "2", "tags": ["a"]}))
+            .unwrap();
+        table
+            .add_single_row(json!({"tags": null, "id": "1", "name": "John"}))
+            .unwrap();
+
+        let mut output = Vec::new();
+        let written = table.export_csv(&mut output).unwrap();
+
+        assert_eq!(written, 2);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "id,name,tags\n1,John,\n2,\"Jane, Doe\",\"[\"\"a\"\"]\"\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_csv_round_trip() {
+        let (mut db, people) = setup_people().await;
+        let input = "id,name,age,score,active,tags\n1,John Doe,42,9.5,true,[]\n";
+        people
+            .import_csv(&mut db, input.as_bytes(), CsvOptions::default())
+            .await
+            .unwrap();
+
+        let mut output = Vec::new();
+        db.get_table("people")
+            .unwrap()
+            .export_csv(&mut output)
+            .unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), input);
+    }
+}
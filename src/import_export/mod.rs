@@ -0,0 +1,26 @@
+pub mod csv;
+pub mod ndjson;
+
+pub use self::csv::CsvOptions;
+
+/// Outcome of a bulk import. Records that fail are skipped and reported,
+/// the rest are imported.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub errors: Vec<ImportError>,
+}
+
+/// Why a single record of an import was rejected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportError {
+    /// 1-based line of the record in the input.
+    pub line: u64,
+    pub message: String,
+}
+
+impl ImportReport {
+    pub fn is_clean(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
@@ -0,0 +1,215 @@
+use serde_json::Value;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufWriter};
+use tracing;
+
+use super::{ImportError, ImportReport};
+use crate::database_components::table::OnConflict;
+use crate::{Database, DatabaseError};
+
+// rows are parsed this many at a time before being inserted, so memory use
+// does not grow with the size of the input
+const BATCH_SIZE: usize = 1000;
+
+impl Database {
+    /// Import newline-delimited JSON into `table_name`, one row object per
+    /// line. Blank lines are skipped. Each row is checked with
+    /// `Columns::validate`; rejected rows are listed in the returned report by
+    /// line and the rest are inserted. The database is saved once at the end.
+    pub async fn import_ndjson<R>(
+        &mut self,
+        table_name: &str,
+        reader: R,
+    ) -> Result<ImportReport, DatabaseError>
+    where
+        R: AsyncBufRead + Unpin,
+    {
+        if !self.tables.contains_key(table_name) {
+            return Err(DatabaseError::TableNotFound(table_name.to_string()));
+        }
+
+        let mut report = ImportReport::default();
+        let mut batch: Vec<(u64, Value)> = Vec::with_capacity(BATCH_SIZE);
+        let mut lines = reader.lines();
+        let mut line_number = 0;
+
+        while let Some(line) = lines.next_line().await? {
+            line_number += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<Value>(&line) {
+                Ok(row @ Value::Object(_)) => batch.push((line_number, row)),
+                Ok(_) => report.errors.push(ImportError {
+                    line: line_number,
+                    message: "Expected a JSON object".to_string(),
+                }),
+                Err(e) => report.errors.push(ImportError {
+                    line: line_number,
+                    message: e.to_string(),
+                }),
+            }
+
+            if batch.len() == BATCH_SIZE {
+                self.insert_batch(table_name, &mut batch, &mut report);
+            }
+        }
+        self.insert_batch(table_name, &mut batch, &mut report);
+
+        if report.imported > 0 {
+            self.persist().await?;
+        }
+        tracing::info!(
+            "Imported {} NDJSON row(s) into `{}`, {} rejected",
+            report.imported,
+            table_name,
+            report.errors.len()
+        );
+        Ok(report)
+    }
+
+    /// Write every row of `table_name` as one JSON object per line, sorted by
+    /// row id. Rows are serialized one at a time. Returns the number of rows
+    /// written.
+    pub async fn export_ndjson<W>(
+        &self,
+        table_name: &str,
+        writer: W,
+    ) -> Result<usize, DatabaseError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let table = self
+            .get_table(table_name)
+            .ok_or_else(|| DatabaseError::TableNotFound(table_name.to_string()))?;
+
+        let mut row_ids: Vec<&String> = table.rows.keys().collect();
+        row_ids.sort();
+
+        let mut writer = BufWriter::new(writer);
+        let mut line = Vec::new();
+        for row_id in &row_ids {
+            line.clear();
+            serde_json::to_writer(&mut line, &table.rows[*row_id].data)?;
+            line.push(b'\n');
+            writer.write_all(&line).await?;
+        }
+        writer.flush().await?;
+
+        Ok(row_ids.len())
+    }
+
+    fn insert_batch(
+        &mut self,
+        table_name: &str,
+        batch: &mut Vec<(u64, Value)>,
+        report: &mut ImportReport,
+    ) {
+        let hooks = self.hooks(table_name);
+        let Some(table) = self.tables.get_mut(table_name) else {
+            return;
+        };
+        for (line, row) in batch.drain(..) {
+            match table.insert_row(row, hooks.as_deref(), OnConflict::Fail) {
+                Ok(_) => report.imported += 1,
+                Err(e) => report.errors.push(ImportError {
+                    line,
+                    message: e.to_string(),
+                }),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{setup_temp_db, Column, Columns, Table};
+
+    async fn setup_users() -> Database {
+        let mut db = setup_temp_db().await;
+        let mut users = Table::new(
+            "users".to_string(),
+            Columns::new(vec![Column::new("id", true), Column::new("name", true)]),
+        );
+        db.add_table(&mut users).await.unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn test_import_ndjson() {
+        let mut db = setup_users().await;
+        let input = "\
+{\"id\": \"1\", \"name\": \"John Doe\"}
+
+{\"id\": \"2\", \"name\": \"Jane Doe\"}
+";
+
+        let report = db.import_ndjson("users", input.as_bytes()).await.unwrap();
+
+        assert!(report.is_clean(), "{:?}", report.errors);
+        assert_eq!(report.imported, 2);
+        assert_eq!(
+            db.get_table("users").unwrap().rows["2"].data,
+            json!({"id": "2", "name": "Jane Doe"})
+        );
+
+        let on_disk = Database::load_from_file(&db.file_name).await.unwrap();
+        assert_eq!(on_disk.tables["users"].rows.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_import_ndjson_reports_line_errors() {
+        let mut db = setup_users().await;
+        let input = "\
+{\"id\": \"1\", \"name\": \"John Doe\"}
+{\"id\": \"2\",
+[1, 2]
+{\"id\": \"1\", \"name\": \"John Again\"}
+{\"id\": \"3\"}
+";
+
+        let report = db.import_ndjson("users", input.as_bytes()).await.unwrap();
+
+        assert_eq!(report.imported, 1);
+        let lines: Vec<u64> = report.errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, vec![2, 3, 4, 5]);
+        assert!(report.errors[2].message.contains("already exists"));
+        assert!(report.errors[3].message.contains("required"));
+    }
+
+    #[tokio::test]
+    async fn test_import_ndjson_across_batches() {
+        let mut db = setup_users().await;
+        let input: String = (0..BATCH_SIZE + 5)
+            .map(|i| format!("{{\"id\": \"{i}\", \"name\": \"user {i}\"}}\n"))
+            .collect();
+
+        let report = db.import_ndjson("users", input.as_bytes()).await.unwrap();
+
+        assert_eq!(report.imported, BATCH_SIZE + 5);
+        assert_eq!(db.count_rows("users").unwrap(), BATCH_SIZE + 5);
+    }
+
+    #[tokio::test]
+    async fn test_import_ndjson_missing_table() {
+        let mut db = setup_users().await;
+
+        let result = db.import_ndjson("missing", "".as_bytes()).await;
+        assert!(matches!(result, Err(DatabaseError::TableNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_round_trip() {
+        let mut db = setup_users().await;
+        let input = "{\"id\":\"1\",\"name\":\"John Doe\"}\n{\"id\":\"2\",\"name\":\"Jane Doe\"}\n";
+        db.import_ndjson("users", input.as_bytes()).await.unwrap();
+
+        let mut output = Vec::new();
+        let written = db.export_ndjson("users", &mut output).await.unwrap();
+
+        assert_eq!(written, 2);
+        assert_eq!(String::from_utf8(output).unwrap(), input);
+    }
+}
@@ -5,13 +5,27 @@ pub mod errors;
 pub use errors::DatabaseError;
 
 pub mod database_components;
-pub use database_components::{Column, Columns, Row, Table};
+pub use database_components::{
+    Column, ColumnType, Columns, ForeignKey, Generated, KeyStrategy, Row, SchemaViolation, Table,
+};
 
 pub mod query_operations;
-pub use query_operations::{Operation, Query};
+pub use query_operations::{Filter, FilterOp, Operation, Order, Query};
 
 pub mod database_operations;
-pub use database_operations::Database;
+pub use database_operations::{
+    BackupProgress, BackupReport, ChangeEvent, ChangeFeed, Database, Migration, MigrationStep,
+    SavePolicy, StorageLayout,
+};
+
+pub mod import_export;
+pub use import_export::{CsvOptions, ImportError, ImportReport};
+
+pub mod sql;
+pub use sql::{SqlImportReport, SqlOutput};
+
+#[cfg(feature = "server")]
+pub mod server;
 
 pub mod view;
-pub use view::View;
+pub use view::{Grid, View, ViewFormat, ViewOptions};
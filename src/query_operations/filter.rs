@@ -0,0 +1,160 @@
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// How `Query::filter` compares a column with a value.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+pub enum FilterOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// A string column containing the value as a substring, or an array
+    /// column containing the value as an element.
+    Contains,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Filter {
+    pub column: String,
+    pub op: FilterOp,
+    pub value: Value,
+}
+
+impl Filter {
+    /// Whether `row` passes the filter. A missing column reads as `null`.
+    pub fn matches(&self, row: &Value) -> bool {
+        let actual = row.get(&self.column).unwrap_or(&Value::Null);
+        match self.op {
+            FilterOp::Eq => compare_values(actual, &self.value) == Ordering::Equal,
+            FilterOp::Ne => compare_values(actual, &self.value) != Ordering::Equal,
+            // ordering only makes sense between values of the same kind
+            FilterOp::Lt => {
+                same_kind(actual, &self.value) && compare_values(actual, &self.value).is_lt()
+            }
+            FilterOp::Le => {
+                same_kind(actual, &self.value) && compare_values(actual, &self.value).is_le()
+            }
+            FilterOp::Gt => {
+                same_kind(actual, &self.value) && compare_values(actual, &self.value).is_gt()
+            }
+            FilterOp::Ge => {
+                same_kind(actual, &self.value) && compare_values(actual, &self.value).is_ge()
+            }
+            FilterOp::Contains => match (actual, &self.value) {
+                (Value::String(s), Value::String(needle)) => s.contains(needle.as_str()),
+                (Value::Array(items), value) => items
+                    .iter()
+                    .any(|item| compare_values(item, value) == Ordering::Equal),
+                _ => false,
+            },
+        }
+    }
+}
+
+/// Sort order set by `Query::order_by` and `Query::order_by_desc`.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Order {
+    pub column: String,
+    pub descending: bool,
+}
+
+fn same_kind(a: &Value, b: &Value) -> bool {
+    kind_rank(a) == kind_rank(b)
+}
+
+// null < booleans < numbers < strings < arrays < objects
+fn kind_rank(value: &Value) -> u8 {
+    match value {
+        Value::Null => 0,
+        Value::Bool(_) => 1,
+        Value::Number(_) => 2,
+        Value::String(_) => 3,
+        Value::Array(_) => 4,
+        Value::Object(_) => 5,
+    }
+}
+
+/// A total order over JSON values, used for filters and sorting. Numbers
+/// compare by value, so `1` equals `1.0`.
+pub(crate) fn compare_values(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (Value::Number(a), Value::Number(b)) => match (a.as_i64(), b.as_i64()) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => a
+                .as_f64()
+                .unwrap_or(f64::NAN)
+                .total_cmp(&b.as_f64().unwrap_or(f64::NAN)),
+        },
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Array(a), Value::Array(b)) => a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| compare_values(a, b))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        (Value::Object(_), Value::Object(_)) => a.to_string().cmp(&b.to_string()),
+        _ => kind_rank(a).cmp(&kind_rank(b)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn filter(column: &str, op: FilterOp, value: Value) -> Filter {
+        Filter {
+            column: column.to_string(),
+            op,
+            value,
+        }
+    }
+
+    #[test]
+    fn test_filter_comparisons() {
+        let row = json!({"age": 42, "score": 9.5, "name": "John Doe", "tags": ["a", "b"]});
+
+        assert!(filter("age", FilterOp::Eq, json!(42.0)).matches(&row));
+        assert!(filter("age", FilterOp::Ge, json!(42)).matches(&row));
+        assert!(filter("score", FilterOp::Lt, json!(10)).matches(&row));
+        assert!(filter("name", FilterOp::Gt, json!("Jane")).matches(&row));
+        assert!(filter("name", FilterOp::Contains, json!("Doe")).matches(&row));
+        assert!(filter("tags", FilterOp::Contains, json!("b")).matches(&row));
+        assert!(filter("missing", FilterOp::Eq, Value::Null).matches(&row));
+
+        assert!(!filter("age", FilterOp::Ne, json!(42)).matches(&row));
+        // no ordering between a number and a string
+        assert!(!filter("age", FilterOp::Lt, json!("50")).matches(&row));
+        assert!(!filter("age", FilterOp::Gt, json!("50")).matches(&row));
+    }
+
+    #[test]
+    fn test_compare_values_orders_kinds() {
+        let mut values = vec![
+            json!("b"),
+            json!(2),
+            Value::Null,
+            json!(true),
+            json!(1.5),
+            json!("a"),
+        ];
+        values.sort_by(compare_values);
+        assert_eq!(
+            values,
+            vec![
+                Value::Null,
+                json!(true),
+                json!(1.5),
+                json!(2),
+                json!("a"),
+                json!("b")
+            ]
+        );
+    }
+}
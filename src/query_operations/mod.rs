@@ -1,5 +1,7 @@
+pub mod filter;
 pub mod query;
 
+pub use filter::{Filter, FilterOp, Order};
 pub use query as query_operations;
 
 use serde::{Deserialize, Serialize};
@@ -21,4 +23,29 @@ pub struct Query {
     pub operation: Operation,
     pub update_data: Option<Value>,
     pub row_data: Option<Value>,
+    #[serde(default)]
+    pub filters: Vec<Filter>,
+    #[serde(default)]
+    pub order: Option<Order>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// Columns kept by `Query::select`, every column when `None`.
+    #[serde(default)]
+    pub columns: Option<Vec<String>>,
+}
+
+impl Query {
+    pub(crate) fn new(db_file_name: PathBuf, operation: Operation) -> Self {
+        Query {
+            db_file_name,
+            table_name: None,
+            operation,
+            update_data: None,
+            row_data: None,
+            filters: Vec::new(),
+            order: None,
+            limit: None,
+            columns: None,
+        }
+    }
 }
@@ -1,10 +1,13 @@
-use std::collections::HashMap;
-
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use serde_json::Value;
 
-use crate::{Database, DatabaseError, Operation, Query, Row, Table};
+use super::filter::compare_values;
+use crate::database_components::table::OnConflict;
+use crate::database_operations::hooks::TableHooks;
+use crate::{
+    Database, DatabaseError, Filter, FilterOp, Operation, Order, Query, Row, StorageLayout, Table,
+};
 
 impl Query {
     pub fn from(mut self, table_name: &str) -> Self {
@@ -27,12 +30,23 @@ impl Query {
     where
         T: DeserializeOwned + Default,
     {
-        let mut db = Database::load_from_file(&self.db_file_name)
-            .await
-            .map_err(DatabaseError::LoadError)?;
+        let mut db = Database::load_latest(&self.db_file_name).await?;
         self.handle_where_eq(&mut db, key, value).await // Shared logic
     }
 
+    /// Like `where_eq`, for the row stored under `row_id`, the key
+    /// `Table::row_key` gives it. Reaches rows whatever their key columns are.
+    pub async fn where_key<T>(self, row_id: &str) -> Result<Option<T>, DatabaseError>
+    where
+        T: DeserializeOwned + Default,
+    {
+        let mut db = Database::load_latest(&self.db_file_name).await?;
+        self.handle_row(&mut db, |table| {
+            table.rows.contains_key(row_id).then(|| row_id.to_string())
+        })
+        .await
+    }
+
     // Shared logic for where_eq
     async fn handle_where_eq<T>(
         &self,
@@ -40,6 +54,23 @@ impl Query {
         key: &str,
         value: &str,
     ) -> Result<Option<T>, DatabaseError>
+    where
+        T: DeserializeOwned + Default,
+    {
+        self.handle_row(db, |table| {
+            table.rows.iter().find_map(|(id, row)| {
+                (row.data.get(key).and_then(Value::as_str) == Some(value)).then(|| id.clone())
+            })
+        })
+        .await
+    }
+
+    // run the query on the row `find` picks out of the table
+    async fn handle_row<T>(
+        &self,
+        db: &mut Database,
+        find: impl FnOnce(&Table) -> Option<String>,
+    ) -> Result<Option<T>, DatabaseError>
     where
         T: DeserializeOwned + Default,
     {
@@ -48,57 +79,60 @@ impl Query {
             .clone()
             .ok_or_else(|| DatabaseError::TableNotFound("Table name not specified.".to_string()))?;
 
+        let hooks = db.hooks(&table_name);
         let table = db.tables.get_mut(&table_name).ok_or_else(|| {
             DatabaseError::TableNotFound(format!("Table '{}' not found.", table_name))
         })?;
+        let target_id = find(table);
 
         match self.operation {
-            Operation::Read => self.execute_select(table, key, value),
+            Operation::Read => self.execute_select(table, target_id),
             Operation::Update => {
-                let result = self.execute_update(table, key, value);
-                db.save_to_file().await.map_err(DatabaseError::SaveError)?;
+                let result = self.execute_update(table, target_id, hooks.as_deref());
+                // nothing is written when no row matched
+                if table.dirty {
+                    db.commit().await?;
+                }
                 result
             }
             Operation::Delete => {
-                let result = self.execute_delete(table, key, value);
-                db.save_to_file().await.map_err(DatabaseError::SaveError)?;
+                let result = self.execute_delete(table, target_id, hooks.as_deref());
+                if table.dirty {
+                    db.commit().await?;
+                }
                 result
             }
             Operation::Create => unreachable!(),
         }
     }
 
-    pub async fn execute_add(self) -> Result<(), DatabaseError> {
-        let mut db = Database::load_from_file(&self.db_file_name)
-            .await
-            .map_err(DatabaseError::LoadError)?;
+    /// Insert the row and return its id, which the table's `KeyStrategy`
+    /// generates when the row has none.
+    pub async fn execute_add(self) -> Result<String, DatabaseError> {
+        let mut db = Database::load_latest(&self.db_file_name).await?;
         self.handle_execute_add_sync(&mut db).await // Shared logic
     }
 
-    async fn handle_execute_add_sync(&self, db: &mut Database) -> Result<(), DatabaseError> {
+    async fn handle_execute_add_sync(&self, db: &mut Database) -> Result<String, DatabaseError> {
         let table_name = self
             .table_name
             .clone()
             .ok_or_else(|| DatabaseError::InvalidData("Table name not specified.".to_string()))?;
 
+        let hooks = db.hooks(&table_name);
         let table = db
             .tables
             .get_mut(&table_name)
             .ok_or_else(|| DatabaseError::TableNotFound(table_name.clone()))?;
 
         if let Some(row_data) = self.row_data.clone() {
-            table.columns.validate(row_data.clone())?;
-
-            if let Some(row_id) = row_data.get("id").and_then(|id| id.as_str()) {
-                table.rows.insert(row_id.to_string(), Row::new(row_data));
-            } else {
-                return Err(DatabaseError::InvalidData(
-                    "No 'id' field provided for the new row.".to_string(),
-                ));
-            }
+            let row_id = table
+                .insert_row(row_data, hooks.as_deref(), OnConflict::Replace)?
+                // only `OnConflict::Ignore` leaves a row out
+                .unwrap_or_default();
 
-            db.save_to_file().await.map_err(DatabaseError::SaveError)?;
-            Ok(())
+            db.commit().await?;
+            Ok(row_id)
         } else {
             Err(DatabaseError::InvalidData(
                 "No data provided for the new row.".to_string(),
@@ -109,44 +143,44 @@ impl Query {
     fn execute_select<T>(
         &self,
         table: &Table,
-        key: &str,
-        value: &str,
+        target_id: Option<String>,
     ) -> Result<Option<T>, DatabaseError>
     where
         T: DeserializeOwned,
     {
-        for row in table.rows.values() {
-            if let Some(field_value) = row.data.get(key) {
-                if field_value.as_str() == Some(value) {
-                    return serde_json::from_value(row.data.clone())
-                        .map(Some)
-                        .map_err(|e| {
-                            DatabaseError::InvalidData(format!("Deserialization error: {}", e))
-                        });
-                }
-            }
+        match target_id {
+            Some(target_id) => self.deserialize_row(&table.rows[&target_id]),
+            None => Ok(None), // No matching record found
         }
-        Ok(None) // No matching record found
     }
 
     fn execute_update<T>(
         &self,
         table: &mut Table,
-        key: &str,
-        value: &str,
+        target_id: Option<String>,
+        hooks: Option<&TableHooks>,
     ) -> Result<Option<T>, DatabaseError>
     where
         T: DeserializeOwned,
     {
-        for row in table.rows.values_mut() {
-            if let Some(field_value) = row.data.get(key) {
-                if field_value.as_str() == Some(value) {
-                    self.apply_update_to_row(row, &self.update_data)?;
-
-                    tracing::info!("Record updated successfully.");
-                    return self.deserialize_row(row);
-                }
+        if let Some(target_id) = target_id {
+            // the row is only replaced once the updated data passes the schema
+            let mut row = table.rows[&target_id].clone();
+            self.apply_update_to_row(&mut row, &self.update_data)?;
+            if let Some(hooks) = hooks {
+                hooks.before_update(&mut row.data)?;
             }
+            table.check_key(&target_id, &row.data)?;
+            table.touch(&mut row);
+            table.check_schema(&row.data)?;
+
+            let result = self.deserialize_row(&row);
+            table.record_change(Operation::Update, &target_id, Some(&row.data));
+            table.rows.insert(target_id, row);
+            table.dirty = true;
+
+            tracing::info!("Record updated successfully.");
+            return result;
         }
         Ok(None) // No matching record found
     }
@@ -195,29 +229,24 @@ impl Query {
     fn execute_delete<T>(
         &self,
         table: &mut Table,
-        key: &str,
-        value: &str,
+        target_id: Option<String>,
+        hooks: Option<&TableHooks>,
     ) -> Result<Option<T>, DatabaseError>
     where
         T: DeserializeOwned,
     {
-        // Identify the `_id` of the row to be deleted.
-        let target_id = table.rows.iter().find_map(|(id, row)| {
-            if let Some(field_value) = row.data.get(key) {
-                if field_value.as_str() == Some(value) {
-                    return Some(id.clone());
-                }
-            }
-            None
-        });
-
         if let Some(target_id) = target_id {
+            if let Some(hooks) = hooks {
+                hooks.before_delete(&table.rows[&target_id].data)?;
+            }
+            table.record_change(Operation::Delete, &target_id, None);
             // Remove the row and deserialize the record.
             let row = table.rows.remove(&target_id).ok_or_else(|| {
                 DatabaseError::InvalidData(
                     "Row unexpectedly not found during deletion.".to_string(),
                 )
             })?;
+            table.dirty = true;
 
             let record = serde_json::from_value(row.data).map_err(DatabaseError::JSONError)?;
             tracing::info!("Record deleted successfully.");
@@ -231,15 +260,11 @@ impl Query {
     where
         T: DeserializeOwned,
     {
-        let db = Database::load_from_file(&self.db_file_name)
+        let db = Database::load_latest(&self.db_file_name)
             .await
             .unwrap_or_else(|e| {
                 tracing::error!("Failed to load database from file: {}", e);
-                Database {
-                    name: String::new(),
-                    file_name: self.db_file_name.clone(),
-                    tables: HashMap::new(),
-                }
+                Database::empty("", self.db_file_name.clone(), StorageLayout::SingleFile)
             });
         self.handle_all(&db) // Shared logic
     }
@@ -254,7 +279,15 @@ impl Query {
                     .rows
                     .values()
                     // .iter()
-                    .filter_map(|row| serde_json::from_value(row.data.clone()).ok())
+                    .filter_map(|row| match serde_json::from_value(row.data.clone()) {
+                        Ok(record) => Some(record),
+                        Err(e) => {
+                            // usually a struct that no longer matches the stored
+                            // schema, see `Database::migrate`
+                            tracing::warn!("Skipping row `{}` in `{}`: {}", row._id, table_name, e);
+                            None
+                        }
+                    })
                     .collect()
             } else {
                 Vec::new()
@@ -268,6 +301,196 @@ impl Query {
         self.update_data = Some(update_data);
         self
     }
+
+    /// Keep only rows where `column` compares to `value` with `op`. Filters
+    /// add up, a row has to pass all of them.
+    pub fn filter(mut self, column: &str, op: FilterOp, value: impl Into<Value>) -> Self {
+        self.filters.push(Filter {
+            column: column.to_string(),
+            op,
+            value: value.into(),
+        });
+        self
+    }
+
+    pub fn order_by(mut self, column: &str) -> Self {
+        self.order = Some(Order {
+            column: column.to_string(),
+            descending: false,
+        });
+        self
+    }
+
+    pub fn order_by_desc(mut self, column: &str) -> Self {
+        self.order = Some(Order {
+            column: column.to_string(),
+            descending: true,
+        });
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Keep only `columns` in each returned row.
+    pub fn select(mut self, columns: &[&str]) -> Self {
+        self.columns = Some(columns.iter().map(|c| c.to_string()).collect());
+        self
+    }
+
+    /// Run the query as a read: the rows passing every filter, sorted,
+    /// limited and narrowed to the selected columns. Without an order, rows
+    /// come sorted by id.
+    pub async fn rows(&self) -> Result<Vec<Value>, DatabaseError> {
+        let db = Database::load_latest(&self.db_file_name).await?;
+        self.handle_rows(&db)
+    }
+
+    /// Run the query as an update of every row passing the filters, and
+    /// return how many rows changed. Nothing is written unless every updated
+    /// row passes the table's schema.
+    pub async fn update_matching(&self) -> Result<usize, DatabaseError> {
+        let mut db = Database::load_latest(&self.db_file_name).await?;
+        let updated = self.handle_update_matching(&mut db)?;
+        if updated > 0 {
+            db.commit().await?;
+        }
+        Ok(updated)
+    }
+
+    fn handle_update_matching(&self, db: &mut Database) -> Result<usize, DatabaseError> {
+        let hooks = self.table_name.as_deref().and_then(|name| db.hooks(name));
+        let table = self.table_mut(db)?;
+        // rows are keyed by their primary key, which has to stay put
+        if let Some(Value::Object(data)) = &self.update_data {
+            if let Some(column) = data.keys().find(|column| table.is_key_column(column)) {
+                return Err(DatabaseError::InvalidData(format!(
+                    "The primary key column `{}` of a row cannot be updated.",
+                    column
+                )));
+            }
+        }
+
+        let mut updated = Vec::new();
+        for (id, row) in &table.rows {
+            if self.filters.iter().all(|filter| filter.matches(&row.data)) {
+                let mut row = row.clone();
+                self.apply_update_to_row(&mut row, &self.update_data)?;
+                if let Some(hooks) = &hooks {
+                    hooks.before_update(&mut row.data)?;
+                }
+                table.check_key(id, &row.data)?;
+                table.touch(&mut row);
+                table.check_schema(&row.data)?;
+                updated.push((id.clone(), row));
+            }
+        }
+
+        let count = updated.len();
+        if count > 0 {
+            for (id, row) in updated {
+                table.record_change(Operation::Update, &id, Some(&row.data));
+                table.rows.insert(id, row);
+            }
+            table.dirty = true;
+        }
+        Ok(count)
+    }
+
+    /// Run the query as a delete of every row passing the filters, and
+    /// return how many rows were removed.
+    pub async fn delete_matching(&self) -> Result<usize, DatabaseError> {
+        let mut db = Database::load_latest(&self.db_file_name).await?;
+        let hooks = self.table_name.as_deref().and_then(|name| db.hooks(name));
+        let table = self.table_mut(&mut db)?;
+
+        let matching: Vec<String> = table
+            .rows
+            .iter()
+            .filter(|(_, row)| self.filters.iter().all(|filter| filter.matches(&row.data)))
+            .map(|(id, _)| id.clone())
+            .collect();
+        // nothing is deleted unless every hook lets its row go
+        if let Some(hooks) = &hooks {
+            for id in &matching {
+                hooks.before_delete(&table.rows[id].data)?;
+            }
+        }
+        let deleted = matching.len();
+        for id in matching {
+            table.record_change(Operation::Delete, &id, None);
+            table.rows.remove(&id);
+        }
+
+        if deleted > 0 {
+            table.dirty = true;
+            db.commit().await?;
+        }
+        Ok(deleted)
+    }
+
+    fn table_mut<'a>(&self, db: &'a mut Database) -> Result<&'a mut Table, DatabaseError> {
+        let table_name = self
+            .table_name
+            .as_deref()
+            .ok_or_else(|| DatabaseError::InvalidData("Table name not specified.".to_string()))?;
+        db.tables
+            .get_mut(table_name)
+            .ok_or_else(|| DatabaseError::TableNotFound(table_name.to_string()))
+    }
+
+    fn handle_rows(&self, db: &Database) -> Result<Vec<Value>, DatabaseError> {
+        let table_name = self
+            .table_name
+            .as_deref()
+            .ok_or_else(|| DatabaseError::InvalidData("Table name not specified.".to_string()))?;
+        let table = db
+            .tables
+            .get(table_name)
+            .ok_or_else(|| DatabaseError::TableNotFound(table_name.to_string()))?;
+
+        let mut rows: Vec<(&String, &Value)> = table
+            .rows
+            .iter()
+            .map(|(id, row)| (id, &row.data))
+            .filter(|(_, data)| self.filters.iter().all(|filter| filter.matches(data)))
+            .collect();
+
+        rows.sort_by(|(a_id, a), (b_id, b)| {
+            let by_column = match &self.order {
+                Some(order) => {
+                    let ordering = compare_values(
+                        a.get(&order.column).unwrap_or(&Value::Null),
+                        b.get(&order.column).unwrap_or(&Value::Null),
+                    );
+                    if order.descending {
+                        ordering.reverse()
+                    } else {
+                        ordering
+                    }
+                }
+                None => std::cmp::Ordering::Equal,
+            };
+            by_column.then_with(|| a_id.cmp(b_id))
+        });
+
+        let limit = self.limit.unwrap_or(usize::MAX);
+        Ok(rows
+            .into_iter()
+            .take(limit)
+            .map(|(_, data)| match &self.columns {
+                Some(columns) => Value::Object(
+                    columns
+                        .iter()
+                        .filter_map(|c| data.get(c).map(|v| (c.clone(), v.clone())))
+                        .collect(),
+                ),
+                None => data.clone(),
+            })
+            .collect())
+    }
 }
 
 #[cfg(test)]
@@ -292,6 +515,10 @@ mod tests {
             operation: Operation::Read,
             update_data: None,
             row_data: None,
+            filters: Vec::new(),
+            order: None,
+            limit: None,
+            columns: None,
         };
 
         let updated_query = query.from("TestTable");
@@ -306,6 +533,10 @@ mod tests {
             operation: Operation::Update,
             update_data: None,
             row_data: None,
+            filters: Vec::new(),
+            order: None,
+            limit: None,
+            columns: None,
         };
 
         let data = json!({ "name": "Updated Name" });
@@ -323,6 +554,10 @@ mod tests {
             operation: Operation::Create,
             update_data: None,
             row_data: None,
+            filters: Vec::new(),
+            order: None,
+            limit: None,
+            columns: None,
         };
 
         let test_data = TestData {
@@ -372,6 +607,60 @@ mod tests {
         // No explicit cleanup needed; tempfile will handle it automatically
     }
 
+    #[tokio::test]
+    async fn test_query_rows() {
+        let mut db = setup_temp_db().await;
+        let mut people = Table::new(
+            "people".to_string(),
+            crate::Columns::new(vec![
+                crate::Column::new("id", true),
+                crate::Column::new("name", true),
+                crate::Column::new("age", false),
+            ]),
+        );
+        db.add_table(&mut people).await.unwrap();
+        people
+            .add_row(
+                &mut db,
+                json!([
+                    {"id": "1", "name": "Alice", "age": 31},
+                    {"id": "2", "name": "Bob", "age": 25},
+                    {"id": "3", "name": "Carol", "age": 47},
+                    {"id": "4", "name": "Dave"},
+                ]),
+            )
+            .await;
+
+        let rows = db
+            .get_rows()
+            .from("people")
+            .filter("age", FilterOp::Gt, 26)
+            .order_by_desc("age")
+            .select(&["name"])
+            .rows()
+            .await
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![json!({"name": "Carol"}), json!({"name": "Alice"})]
+        );
+
+        let rows = db
+            .get_rows()
+            .from("people")
+            .order_by("age")
+            .limit(2)
+            .rows()
+            .await
+            .unwrap();
+        let ids: Vec<&Value> = rows.iter().map(|row| &row["id"]).collect();
+        // a missing age sorts first, as null
+        assert_eq!(ids, vec!["4", "2"]);
+
+        let missing = db.get_rows().from("nobody").rows().await;
+        assert!(matches!(missing, Err(DatabaseError::TableNotFound(_))));
+    }
+
     #[test]
     fn test_query_set() {
         let query = Query {
@@ -380,6 +669,10 @@ mod tests {
             operation: Operation::Update,
             update_data: None,
             row_data: None,
+            filters: Vec::new(),
+            order: None,
+            limit: None,
+            columns: None,
         };
 
         let data = json!({ "name": "Updated Name" });
@@ -517,4 +810,57 @@ mod tests {
         assert!(deleted_record.is_none(), "Expected record to be deleted");
         assert!(rows.is_empty(), "Expected all records to be deleted");
     }
+
+    #[tokio::test]
+    async fn test_query_update_and_delete_matching() {
+        let mut db = setup_temp_db().await;
+        let mut people = Table::new(
+            "people".to_string(),
+            crate::Columns::new(vec![
+                crate::Column::new("id", true),
+                crate::Column::new("age", false),
+            ]),
+        );
+        db.add_table(&mut people).await.unwrap();
+        people
+            .add_row(
+                &mut db,
+                json!([
+                    {"id": "1", "age": 31},
+                    {"id": "2", "age": 25},
+                    {"id": "3", "age": 47},
+                ]),
+            )
+            .await;
+
+        let updated = db
+            .update_row()
+            .from("people")
+            .filter("age", FilterOp::Gt, 30)
+            .set(json!({"age": 0}))
+            .update_matching()
+            .await
+            .unwrap();
+        assert_eq!(updated, 2);
+
+        let renamed = db
+            .update_row()
+            .from("people")
+            .set(json!({"id": "9"}))
+            .update_matching()
+            .await;
+        assert!(matches!(renamed, Err(DatabaseError::InvalidData(_))));
+
+        let deleted = db
+            .delete_single()
+            .from("people")
+            .filter("age", FilterOp::Eq, 0)
+            .delete_matching()
+            .await
+            .unwrap();
+        assert_eq!(deleted, 2);
+
+        let rows = db.get_rows().from("people").rows().await.unwrap();
+        assert_eq!(rows, vec![json!({"id": "2", "age": 25})]);
+    }
 }
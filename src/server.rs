@@ -0,0 +1,509 @@
+//! A local HTTP server for a database, enabled by the `server` feature.
+//!
+//! | Method   | Path                        |                                   |
+//! |----------|-----------------------------|-----------------------------------|
+//! | `GET`    | `/tables`                   | table names and row counts        |
+//! | `GET`    | `/schema`                   | JSON Schema of the database       |
+//! | `GET`    | `/tables/{table}/schema`    | JSON Schema of a table            |
+//! | `GET`    | `/tables/{table}/rows`      | rows, see `list_rows`             |
+//! | `POST`   | `/tables/{table}/rows`      | insert the JSON row in the body   |
+//! | `GET`    | `/tables/{table}/rows/{id}` | one row                           |
+//! | `PATCH`  | `/tables/{table}/rows/{id}` | set the fields of the JSON body   |
+//! | `DELETE` | `/tables/{table}/rows/{id}` | delete a row                      |
+//!
+//! `{id}` is the key a row is stored under: its key column's value, or the
+//! values of a composite key as a JSON array, see `Table::row_key`. Errors
+//! come back as `{"error": "..."}` with a status code matching the
+//! `DatabaseError`.
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use axum::extract::{Path, Query as Params, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
+
+use crate::{ColumnType, Database, DatabaseError, FilterOp, Table};
+
+#[derive(Clone)]
+struct AppState {
+    path: PathBuf,
+    // each write loads and saves the whole file, so writes take turns
+    writes: Arc<Mutex<()>>,
+}
+
+impl AppState {
+    async fn open(&self) -> Result<Database, ApiError> {
+        Ok(Database::open(&self.path).await?)
+    }
+}
+
+/// The routes of the server, for the database stored at `path`. Every
+/// request reads the file again, so changes made outside the server show up
+/// right away.
+pub fn router(path: impl Into<PathBuf>) -> Router {
+    let state = AppState {
+        path: path.into(),
+        writes: Arc::new(Mutex::new(())),
+    };
+    Router::new()
+        .route("/tables", get(list_tables))
+        .route("/schema", get(database_schema))
+        .route("/tables/{table}/schema", get(table_schema))
+        .route("/tables/{table}/rows", get(list_rows).post(create_row))
+        .route(
+            "/tables/{table}/rows/{id}",
+            get(get_row).patch(update_row).delete(delete_row),
+        )
+        .with_state(state)
+}
+
+/// Serve the database stored at `path` on `addr` until the task is
+/// cancelled.
+pub async fn serve(path: impl Into<PathBuf>, addr: SocketAddr) -> Result<(), DatabaseError> {
+    let path = path.into();
+    // fail early on a missing database rather than on the first request
+    Database::open(&path).await?;
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    tracing::info!(
+        "Serving {} on http://{}",
+        path.display(),
+        listener.local_addr()?
+    );
+    axum::serve(listener, router(path)).await?;
+    Ok(())
+}
+
+/// An error response, `{"error": message}`.
+#[derive(Debug)]
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        ApiError {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<DatabaseError> for ApiError {
+    fn from(error: DatabaseError) -> Self {
+        let status = match &error {
+            DatabaseError::TableNotFound(_)
+            | DatabaseError::RowNotFound(_, _)
+            | DatabaseError::SnapshotNotFound(_) => StatusCode::NOT_FOUND,
+            DatabaseError::TableAlreadyExists(_) => StatusCode::CONFLICT,
+            DatabaseError::InvalidData(_)
+            | DatabaseError::SqlError { .. }
+            | DatabaseError::JSONError(_)
+            | DatabaseError::InvalidOperation(_) => StatusCode::BAD_REQUEST,
+            DatabaseError::SchemaViolation(_)
+            | DatabaseError::MissingColumn(_)
+            | DatabaseError::ColumnRequiredError(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            DatabaseError::LoadError(_)
+            | DatabaseError::SaveError(_)
+            | DatabaseError::DeleteError(_)
+            | DatabaseError::BackupError(_)
+            | DatabaseError::MigrationError(_)
+            | DatabaseError::IoError(_)
+            | DatabaseError::CsvError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        ApiError::new(status, error.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(json!({ "error": self.message }))).into_response()
+    }
+}
+
+type ApiResult<T> = Result<T, ApiError>;
+
+async fn list_tables(State(state): State<AppState>) -> ApiResult<Json<Value>> {
+    let db = state.open().await?;
+    let mut names = db.list_tables();
+    names.sort();
+    let tables: Vec<Value> = names
+        .iter()
+        .map(|name| json!({ "name": name, "rows": db.tables[name].rows.len() }))
+        .collect();
+    Ok(Json(Value::Array(tables)))
+}
+
+async fn database_schema(State(state): State<AppState>) -> ApiResult<Json<Value>> {
+    Ok(Json(state.open().await?.json_schema()))
+}
+
+async fn table_schema(
+    State(state): State<AppState>,
+    Path(table): Path<String>,
+) -> ApiResult<Json<Value>> {
+    let db = state.open().await?;
+    let table = db
+        .get_table(&table)
+        .ok_or(DatabaseError::TableNotFound(table))?;
+    Ok(Json(table.json_schema()))
+}
+
+/// Rows of a table, sorted by id. `limit=n` and `order=column` (`-column`
+/// for descending) shape the result, and any other parameter keeps the rows
+/// where that column equals the value. Values are read as JSON when they
+/// parse, except for text columns and untyped keys, so `age=42` matches the
+/// number.
+async fn list_rows(
+    State(state): State<AppState>,
+    Path(table): Path<String>,
+    Params(params): Params<BTreeMap<String, String>>,
+) -> ApiResult<Json<Vec<Value>>> {
+    let db = state.open().await?;
+    let target = db
+        .get_table(&table)
+        .ok_or_else(|| DatabaseError::TableNotFound(table.clone()))?;
+
+    let mut query = db.get_rows().from(&table);
+    for (key, value) in &params {
+        query = match key.as_str() {
+            "limit" => query.limit(value.parse().map_err(|_| {
+                ApiError::new(StatusCode::BAD_REQUEST, "`limit` must be a row count")
+            })?),
+            "order" => match value.strip_prefix('-') {
+                Some(column) => query.order_by_desc(column),
+                None => query.order_by(value),
+            },
+            column => {
+                let value = match serde_json::from_str(value) {
+                    Ok(value) if !compares_text(target, column) => value,
+                    _ => Value::String(value.clone()),
+                };
+                query.filter(column, FilterOp::Eq, value)
+            }
+        };
+    }
+    Ok(Json(query.rows().await?))
+}
+
+// whether values of `column` are strings: text columns, and untyped key
+// columns, which `KeyStrategy` fills with strings and supplied keys are by
+// convention. `Integer` keys, generated or not, are numbers.
+fn compares_text(table: &Table, column: &str) -> bool {
+    table.columns.0.iter().any(|c| match c.column_type {
+        ColumnType::Text => c.name == column,
+        ColumnType::Any => c.name == column && table.is_key_column(column),
+        _ => false,
+    })
+}
+
+async fn get_row(
+    State(state): State<AppState>,
+    Path((table, id)): Path<(String, String)>,
+) -> ApiResult<Json<Value>> {
+    let row = state
+        .open()
+        .await?
+        .get_single()
+        .from(&table)
+        .where_key::<Value>(&id)
+        .await?;
+    Ok(Json(row.ok_or(row_not_found(id))?))
+}
+
+async fn create_row(
+    State(state): State<AppState>,
+    Path(table): Path<String>,
+    Json(row): Json<Value>,
+) -> ApiResult<(StatusCode, Json<Value>)> {
+    let _writing = state.writes.lock().await;
+    let mut db = state.open().await?;
+    // a row without a key gets one from the table's `KeyStrategy`, or is
+    // refused when it is added
+    let target = db
+        .get_table(&table)
+        .ok_or_else(|| DatabaseError::TableNotFound(table.clone()))?;
+    if let Some(id) = target
+        .row_key(&row)
+        .filter(|id| target.rows.contains_key(id))
+    {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            format!("a row with key `{}` already exists", id),
+        ));
+    }
+
+    let id = db
+        .add_row()
+        .from(&table)
+        .data_from_struct(&row)
+        .execute_add()
+        .await?;
+    // the stored row, with its generated key and timestamps
+    let stored = db.get_single().from(&table).where_key::<Value>(&id).await?;
+    Ok((StatusCode::CREATED, Json(stored.ok_or(row_not_found(id))?)))
+}
+
+async fn update_row(
+    State(state): State<AppState>,
+    Path((table, id)): Path<(String, String)>,
+    Json(data): Json<Value>,
+) -> ApiResult<Json<Value>> {
+    // the query refuses changes to the row's key
+    let _writing = state.writes.lock().await;
+    let row = state
+        .open()
+        .await?
+        .update_row()
+        .from(&table)
+        .data(data)
+        .where_key::<Value>(&id)
+        .await?;
+    Ok(Json(row.ok_or(row_not_found(id))?))
+}
+
+async fn delete_row(
+    State(state): State<AppState>,
+    Path((table, id)): Path<(String, String)>,
+) -> ApiResult<StatusCode> {
+    let _writing = state.writes.lock().await;
+    let row = state
+        .open()
+        .await?
+        .delete_single()
+        .from(&table)
+        .where_key::<Value>(&id)
+        .await?;
+    row.ok_or(row_not_found(id))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn row_not_found(id: String) -> DatabaseError {
+    DatabaseError::RowNotFound("key".to_string(), id)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::{Column, Columns, KeyStrategy};
+
+    // serves a database with a `users` table on a free localhost port
+    async fn start_server() -> (tempfile::TempDir, SocketAddr) {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Database::new(dir.path().join("db").to_str().unwrap()).await;
+        let mut users = Table::new(
+            "users".to_string(),
+            Columns::new(vec![
+                Column::new("id", true),
+                Column::new("name", true).with_type(ColumnType::Text),
+                Column::new("age", false),
+            ]),
+        );
+        db.add_table(&mut users).await.unwrap();
+        users
+            .add_row(
+                &mut db,
+                json!([
+                    {"id": "1", "name": "John", "age": 42},
+                    {"id": "2", "name": "Jane", "age": 37},
+                ]),
+            )
+            .await;
+
+        let addr = serve_file(dir.path().join("db.json")).await;
+        (dir, addr)
+    }
+
+    // serves the database stored at `path` on a free localhost port
+    async fn serve_file(path: PathBuf) -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = router(path);
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        addr
+    }
+
+    // a bare HTTP/1.1 client, returning the status and the JSON body
+    async fn request(
+        addr: SocketAddr,
+        method: &str,
+        path: &str,
+        body: Option<Value>,
+    ) -> (u16, Value) {
+        let body = body.map(|b| b.to_string()).unwrap_or_default();
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+             Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let status = response[9..12].parse().unwrap();
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        (status, serde_json::from_str(body).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_tables_and_schema() {
+        let (_dir, addr) = start_server().await;
+
+        let (status, tables) = request(addr, "GET", "/tables", None).await;
+        assert_eq!(status, 200);
+        assert_eq!(tables, json!([{"name": "users", "rows": 2}]));
+
+        let (status, schema) = request(addr, "GET", "/tables/users/schema", None).await;
+        assert_eq!(status, 200);
+        assert_eq!(schema["required"], json!(["id", "name"]));
+
+        let (status, error) = request(addr, "GET", "/tables/nobody/schema", None).await;
+        assert_eq!(status, 404);
+        assert!(error["error"].as_str().unwrap().contains("nobody"));
+    }
+
+    #[tokio::test]
+    async fn test_list_rows() {
+        let (_dir, addr) = start_server().await;
+
+        let (status, rows) = request(addr, "GET", "/tables/users/rows?order=-age", None).await;
+        assert_eq!(status, 200);
+        assert_eq!(rows[0]["id"], "1");
+
+        let (_, rows) = request(addr, "GET", "/tables/users/rows?age=37&limit=5", None).await;
+        assert_eq!(rows, json!([{"id": "2", "name": "Jane", "age": 37}]));
+
+        let (_, rows) = request(addr, "GET", "/tables/users/rows?id=2", None).await;
+        assert_eq!(rows[0]["name"], "Jane");
+
+        let (status, _) = request(addr, "GET", "/tables/users/rows?limit=x", None).await;
+        assert_eq!(status, 400);
+    }
+
+    #[tokio::test]
+    async fn test_row_crud() {
+        let (_dir, addr) = start_server().await;
+        let jim = json!({"id": "3", "name": "Jim"});
+
+        let (status, row) = request(addr, "POST", "/tables/users/rows", Some(jim.clone())).await;
+        assert_eq!((status, row), (201, jim.clone()));
+        let (status, _) = request(addr, "POST", "/tables/users/rows", Some(jim)).await;
+        assert_eq!(status, 409);
+        let (status, _) =
+            request(addr, "POST", "/tables/users/rows", Some(json!({"id": "4"}))).await;
+        assert_eq!(status, 422);
+
+        let (status, row) = request(
+            addr,
+            "PATCH",
+            "/tables/users/rows/3",
+            Some(json!({"age": 7})),
+        )
+        .await;
+        assert_eq!((status, row["age"].clone()), (200, json!(7)));
+
+        let (status, _) = request(addr, "DELETE", "/tables/users/rows/3", None).await;
+        assert_eq!(status, 204);
+        let (status, _) = request(addr, "GET", "/tables/users/rows/3", None).await;
+        assert_eq!(status, 404);
+        let (status, _) = request(addr, "DELETE", "/tables/users/rows/3", None).await;
+        assert_eq!(status, 404);
+    }
+
+    #[tokio::test]
+    async fn test_rows_by_table_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Database::new(dir.path().join("db").to_str().unwrap()).await;
+        let mut orders = Table::new(
+            "orders".to_string(),
+            Columns::new(vec![
+                Column::new("region", true).with_type(ColumnType::Text),
+                Column::new("number", true).with_type(ColumnType::Integer),
+                Column::new("item", false),
+            ]),
+        )
+        .with_primary_key(&["region", "number"]);
+        db.add_table(&mut orders).await.unwrap();
+        orders
+            .add_row(&mut db, json!({"region": "eu", "number": 7, "item": "pen"}))
+            .await;
+        let addr = serve_file(dir.path().join("db.json")).await;
+        let path = "/tables/orders/rows/%5B%22eu%22,7%5D";
+
+        let (status, row) = request(addr, "GET", path, None).await;
+        assert_eq!((status, row["item"].clone()), (200, json!("pen")));
+        let (status, row) = request(addr, "PATCH", path, Some(json!({"item": "ink"}))).await;
+        assert_eq!((status, row["item"].clone()), (200, json!("ink")));
+        let (status, _) = request(addr, "PATCH", path, Some(json!({"number": 8}))).await;
+        assert_eq!(status, 400);
+        let (status, _) = request(addr, "DELETE", path, None).await;
+        assert_eq!(status, 204);
+        let (status, _) = request(addr, "GET", path, None).await;
+        assert_eq!(status, 404);
+    }
+
+    #[tokio::test]
+    async fn test_create_row_with_generated_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Database::new(dir.path().join("db").to_str().unwrap()).await;
+        let mut counters = Table::new(
+            "counters".to_string(),
+            Columns::new(vec![
+                Column::new("id", true).with_type(ColumnType::Integer),
+                Column::new("label", false),
+            ]),
+        )
+        .with_key_strategy(KeyStrategy::AutoIncrement);
+        db.add_table(&mut counters).await.unwrap();
+        let addr = serve_file(dir.path().join("db.json")).await;
+        let rows = "/tables/counters/rows";
+
+        let (status, row) = request(addr, "POST", rows, Some(json!({"label": "a"}))).await;
+        assert_eq!((status, row), (201, json!({"id": 1, "label": "a"})));
+        let (status, row) = request(addr, "POST", rows, Some(json!({"id": 5}))).await;
+        assert_eq!((status, row), (201, json!({"id": 5})));
+        let (status, _) = request(addr, "POST", rows, Some(json!({"id": 5}))).await;
+        assert_eq!(status, 409);
+        let (status, _) = request(addr, "POST", rows, Some(json!({"id": true}))).await;
+        assert_eq!(status, 400);
+        let (status, row) = request(addr, "GET", "/tables/counters/rows/5", None).await;
+        assert_eq!((status, row), (200, json!({"id": 5})));
+        let (status, rows) = request(addr, "GET", "/tables/counters/rows?id=5", None).await;
+        assert_eq!((status, rows), (200, json!([{"id": 5}])));
+    }
+
+    #[test]
+    fn test_error_status_codes() {
+        let status = |error| ApiError::from(error).status;
+        assert_eq!(
+            status(DatabaseError::TableNotFound("t".to_string())),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            status(DatabaseError::InvalidData("bad".to_string())),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(DatabaseError::MissingColumn("name".to_string())),
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+        assert_eq!(
+            status(DatabaseError::SaveError(std::io::Error::other("disk"))),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+}
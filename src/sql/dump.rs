@@ -0,0 +1,300 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+
+use serde_json::Value;
+
+use super::{quote_identifier, quote_literal};
+use crate::{Column, ColumnType, Database, DatabaseError, Table};
+
+impl Database {
+    /// Write the whole database as a SQL script that SQLite can load, e.g.
+    /// with `sqlite3 app.db < dump.sql`. Every table becomes a `CREATE TABLE`
+    /// followed by one `INSERT` per row. The table's primary key carries
+    /// over, required columns are `NOT NULL` and declared `references`
+    /// become foreign keys.
+    /// Row fields that are not declared columns are left out. Returns the
+    /// number of rows written.
+    pub fn export_sql<W: Write>(&self, mut writer: W) -> Result<usize, DatabaseError> {
+        writeln!(writer, "PRAGMA foreign_keys=OFF;")?;
+        writeln!(writer, "BEGIN TRANSACTION;")?;
+
+        let mut written = 0;
+        for table_name in creation_order(self) {
+            let table = &self.tables[table_name];
+            let columns = sql_columns(table);
+            writeln!(
+                writer,
+                "{}",
+                create_table(table_name, &columns, table.primary_key())
+            )?;
+
+            let column_list = columns
+                .iter()
+                .map(|column| quote_identifier(&column.name))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            let mut row_ids: Vec<&String> = table.rows.keys().collect();
+            row_ids.sort();
+            for row_id in row_ids {
+                let data = &table.rows[row_id].data;
+                let values = columns
+                    .iter()
+                    .map(|column| sql_value(data.get(&column.name)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                writeln!(
+                    writer,
+                    "INSERT INTO {} ({}) VALUES ({});",
+                    quote_identifier(table_name),
+                    column_list,
+                    values
+                )?;
+                written += 1;
+            }
+        }
+
+        writeln!(writer, "COMMIT;")?;
+        writer.flush()?;
+        Ok(written)
+    }
+}
+
+// the declared columns, with the key columns the table does not declare
+// added in front so every table has its primary key
+fn sql_columns(table: &Table) -> Vec<Column> {
+    let mut columns = table.columns.0.clone();
+    for key in table.primary_key().iter().rev() {
+        if !columns.iter().any(|column| &column.name == key) {
+            columns.insert(0, Column::new(key, true).with_type(ColumnType::Text));
+        }
+    }
+    columns
+}
+
+fn create_table(table_name: &str, columns: &[Column], primary_key: &[String]) -> String {
+    let composite = primary_key.len() > 1;
+    let mut definitions: Vec<String> = columns
+        .iter()
+        .map(|column| {
+            let mut definition = quote_identifier(&column.name);
+            let key = primary_key.contains(&column.name);
+            // untyped keys are strings, see `KeyStrategy`
+            let sql_type = match (key, column.column_type) {
+                (true, ColumnType::Any) => "TEXT",
+                (_, column_type) => sql_type(column_type),
+            };
+            if !sql_type.is_empty() {
+                definition.push(' ');
+                definition.push_str(sql_type);
+            }
+            if key && !composite {
+                definition.push_str(" PRIMARY KEY NOT NULL");
+            } else if key || (column.required && !column.nullable) {
+                definition.push_str(" NOT NULL");
+            }
+            if let Some(default) = &column.default {
+                definition.push_str(" DEFAULT ");
+                definition.push_str(&sql_value(Some(default)));
+            }
+            definition
+        })
+        .collect();
+
+    if composite {
+        let key_columns: Vec<String> = primary_key.iter().map(|c| quote_identifier(c)).collect();
+        definitions.push(format!("PRIMARY KEY ({})", key_columns.join(", ")));
+    }
+    for column in columns {
+        if let Some(foreign_key) = &column.references {
+            definitions.push(format!(
+                "FOREIGN KEY ({}) REFERENCES {} ({})",
+                quote_identifier(&column.name),
+                quote_identifier(&foreign_key.table),
+                quote_identifier(&foreign_key.column)
+            ));
+        }
+    }
+
+    format!(
+        "CREATE TABLE {} (\n    {}\n);",
+        quote_identifier(table_name),
+        definitions.join(",\n    ")
+    )
+}
+
+// `Any` columns get no declared type, so SQLite keeps each value as it is
+fn sql_type(column_type: ColumnType) -> &'static str {
+    match column_type {
+        ColumnType::Any => "",
+        ColumnType::Text | ColumnType::Array | ColumnType::Object => "TEXT",
+        ColumnType::Integer => "INTEGER",
+        // numeric affinity in SQLite, and read back as a boolean by import_sql
+        ColumnType::Boolean => "BOOLEAN",
+        ColumnType::Float => "REAL",
+    }
+}
+
+// arrays and objects are stored as JSON text, which SQLite's JSON functions
+// can read
+fn sql_value(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => "NULL".to_string(),
+        Some(Value::Bool(b)) => if *b { "1" } else { "0" }.to_string(),
+        Some(Value::Number(n)) => n.to_string(),
+        Some(Value::String(s)) => quote_literal(s),
+        Some(other) => quote_literal(&other.to_string()),
+    }
+}
+
+// tables sorted by name, except that a referenced table always comes before
+// the tables referencing it; tables in a reference cycle keep name order
+fn creation_order(db: &Database) -> Vec<&str> {
+    let mut pending: BTreeMap<&str, BTreeSet<&str>> = db
+        .tables
+        .iter()
+        .map(|(name, table)| {
+            let dependencies = table
+                .columns
+                .0
+                .iter()
+                .filter_map(|column| column.references.as_ref())
+                .map(|foreign_key| foreign_key.table.as_str())
+                .filter(|dependency| dependency != name && db.tables.contains_key(*dependency))
+                .collect();
+            (name.as_str(), dependencies)
+        })
+        .collect();
+
+    let mut order = Vec::with_capacity(pending.len());
+    while !pending.is_empty() {
+        let next = pending
+            .iter()
+            .find(|(_, dependencies)| dependencies.is_empty())
+            .map(|(name, _)| *name)
+            .unwrap_or_else(|| *pending.keys().next().unwrap());
+        pending.remove(next);
+        for dependencies in pending.values_mut() {
+            dependencies.remove(next);
+        }
+        order.push(next);
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{setup_temp_db, Columns};
+
+    async fn setup_blog() -> Database {
+        let mut db = setup_temp_db().await;
+        db.drop_table("TestTable").await.unwrap();
+
+        let mut posts = Table::new(
+            "posts".to_string(),
+            Columns::new(vec![
+                Column::new("id", true),
+                Column::new("author_id", true).references("authors", "id"),
+                Column::new("title", true).with_type(ColumnType::Text),
+                Column::new("tags", false).with_type(ColumnType::Array),
+            ]),
+        );
+        let mut authors = Table::new(
+            "authors".to_string(),
+            Columns::new(vec![
+                Column::new("id", true),
+                Column::new("name", true).with_type(ColumnType::Text),
+                Column::new("age", true)
+                    .with_type(ColumnType::Integer)
+                    .nullable(),
+                Column::new("active", false).with_type(ColumnType::Boolean),
+            ]),
+        );
+        db.add_table(&mut posts).await.unwrap();
+        db.add_table(&mut authors).await.unwrap();
+
+        authors
+            .add_row(
+                &mut db,
+                json!({"id": "1", "name": "O'Brien", "age": null, "active": true}),
+            )
+            .await;
+        posts
+            .add_row(
+                &mut db,
+                json!({"id": "1", "author_id": "1", "title": "Hello", "tags": ["a", "b"]}),
+            )
+            .await;
+        db
+    }
+
+    #[tokio::test]
+    async fn test_export_sql() {
+        let db = setup_blog().await;
+
+        let mut output = Vec::new();
+        let written = db.export_sql(&mut output).unwrap();
+
+        assert_eq!(written, 2);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE "authors" (
+    "id" TEXT PRIMARY KEY NOT NULL,
+    "name" TEXT NOT NULL,
+    "age" INTEGER,
+    "active" BOOLEAN
+);
+INSERT INTO "authors" ("id", "name", "age", "active") VALUES ('1', 'O''Brien', NULL, 1);
+CREATE TABLE "posts" (
+    "id" TEXT PRIMARY KEY NOT NULL,
+    "author_id" NOT NULL,
+    "title" TEXT NOT NULL,
+    "tags" TEXT,
+    FOREIGN KEY ("author_id") REFERENCES "authors" ("id")
+);
+INSERT INTO "posts" ("id", "author_id", "title", "tags") VALUES ('1', '1', 'Hello', '["a","b"]');
+COMMIT;
+"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_sql_adds_missing_id_column() {
+        let mut db = setup_temp_db().await;
+        db.drop_table("TestTable").await.unwrap();
+        let mut notes = Table::new(
+            "notes".to_string(),
+            Columns::new(vec![Column::new("body", false)]),
+        );
+        db.add_table(&mut notes).await.unwrap();
+
+        let mut output = Vec::new();
+        db.export_sql(&mut output).unwrap();
+
+        let sql = String::from_utf8(output).unwrap();
+        assert!(sql.contains(
+            "CREATE TABLE \"notes\" (\n    \"id\" TEXT PRIMARY KEY NOT NULL,\n    \"body\"\n);"
+        ));
+    }
+
+    #[test]
+    fn test_creation_order_breaks_cycles() {
+        let mut db = Database::empty("cycle", "cycle.json".into(), Default::default());
+        for (name, other) in [("b", "a"), ("a", "b"), ("c", "b")] {
+            db.tables.insert(
+                name.to_string(),
+                Table::new(
+                    name.to_string(),
+                    Columns::new(vec![Column::new("other", false).references(other, "id")]),
+                ),
+            );
+        }
+
+        assert_eq!(creation_order(&db), vec!["a", "b", "c"]);
+    }
+}
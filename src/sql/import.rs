@@ -0,0 +1,647 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde_json::{Map, Number, Value};
+use tracing;
+
+use super::lexer::{sql_error, statements, tokenize, Token, TokenKind, Tokens};
+use crate::database_components::table::OnConflict;
+use crate::database_operations::hooks;
+use crate::{Column, ColumnType, Columns, Database, DatabaseError, Table};
+
+/// Summary of a finished `Database::import_sql`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SqlImportReport {
+    /// Tables created by `CREATE TABLE` statements, in the order they appear.
+    pub tables: Vec<String>,
+    /// Rows inserted by `INSERT` statements.
+    pub rows: usize,
+    /// Statements other than `CREATE TABLE` and `INSERT`, which are ignored.
+    pub skipped: usize,
+}
+
+// words that end the type name of a column definition
+const COLUMN_CONSTRAINTS: &[&str] = &[
+    "constraint",
+    "primary",
+    "not",
+    "null",
+    "unique",
+    "check",
+    "default",
+    "collate",
+    "references",
+    "generated",
+    "as",
+];
+
+impl Database {
+    /// Build tables and rows from the `CREATE TABLE` and `INSERT INTO ...
+    /// VALUES` statements of a SQL dump, such as the output of `sqlite3
+    /// app.db .dump` or `export_sql`. Other statements are skipped.
+    ///
+    /// Rows are keyed by the table's `PRIMARY KEY`, of one or more columns,
+    /// and tables declaring none by their `id` column, which they then need.
+    /// Values are read as the declared type of their column, so an `INTEGER`
+    /// key stays a number. Arrays and objects stored as text stay text.
+    /// Nothing is changed unless the whole dump imports.
+    pub async fn import_sql(&mut self, sql: &str) -> Result<SqlImportReport, DatabaseError> {
+        let tokens = tokenize(sql)?;
+        let mut tables = self.tables.clone();
+        let mut report = SqlImportReport::default();
+
+        for (statement, end) in statements(&tokens) {
+            let mut tokens = Tokens::new(statement, end);
+            if tokens.eat_keyword("create") {
+                let _ = tokens.eat_keyword("temp") || tokens.eat_keyword("temporary");
+                if tokens.eat_keyword("table") {
+                    if let Some(name) = create_table(&mut tokens, &mut tables)? {
+                        report.tables.push(name);
+                    }
+                } else {
+                    report.skipped += 1;
+                }
+            } else if tokens.peek_keyword("insert") || tokens.peek_keyword("replace") {
+                report.rows += insert(&mut tokens, &mut tables, &self.file_name)?;
+            } else {
+                report.skipped += 1;
+            }
+        }
+
+        self.tables = tables;
+        self.persist().await?;
+        tracing::info!(
+            "Imported {} table(s) and {} row(s) from SQL into `{}`",
+            report.tables.len(),
+            report.rows,
+            self.name
+        );
+        Ok(report)
+    }
+}
+
+// `CREATE TABLE` once the two keywords are read. Returns the table name, or
+// `None` for an `IF NOT EXISTS` table that is already there.
+fn create_table(
+    tokens: &mut Tokens,
+    tables: &mut HashMap<String, Table>,
+) -> Result<Option<String>, DatabaseError> {
+    let if_not_exists = tokens.eat_keyword("if");
+    if if_not_exists {
+        tokens.expect_keyword("not")?;
+        tokens.expect_keyword("exists")?;
+    }
+    let name_position = tokens.position();
+    let table_name = qualified_name(tokens)?;
+    if tables.contains_key(&table_name) {
+        if if_not_exists {
+            return Ok(None);
+        }
+        return Err(DatabaseError::TableAlreadyExists(table_name));
+    }
+    if tokens.peek_keyword("as") {
+        return Err(tokens.error("CREATE TABLE ... AS is not supported"));
+    }
+    tokens.expect_symbol("(")?;
+
+    let mut columns: Vec<Column> = Vec::new();
+    let mut primary_key: Vec<String> = Vec::new();
+    loop {
+        if ["constraint", "primary", "foreign", "unique", "check"]
+            .iter()
+            .any(|keyword| tokens.peek_keyword(keyword))
+        {
+            table_constraint(tokens, &mut columns, &mut primary_key)?;
+        } else {
+            let definition = column_definition(tokens)?;
+            if definition.primary_key {
+                primary_key = vec![definition.column.name.clone()];
+            }
+            columns.push(definition.column);
+        }
+
+        if tokens.eat_symbol(")") {
+            break;
+        }
+        tokens.expect_symbol(",")?;
+    }
+
+    // tables without a primary key fall back on an `id` column
+    if primary_key.is_empty() {
+        if !columns.iter().any(|column| column.name == "id") {
+            return Err(sql_error(
+                name_position,
+                format!(
+                    "table `{}` has neither a primary key nor an `id` column",
+                    table_name
+                ),
+            ));
+        }
+        primary_key = vec!["id".to_string()];
+    }
+    for column in columns.iter_mut().filter(|c| primary_key.contains(&c.name)) {
+        column.required = true;
+    }
+    for column in &mut columns {
+        if let Some(default) = column.default.take() {
+            column.default = Some(coerce(column.column_type, default));
+        }
+    }
+
+    let key: Vec<&str> = primary_key.iter().map(String::as_str).collect();
+    let mut table = Table::new(table_name.clone(), Columns::new(columns)).with_primary_key(&key);
+    table.dirty = true;
+    tables.insert(table_name.clone(), table);
+    Ok(Some(table_name))
+}
+
+struct ColumnDefinition {
+    column: Column,
+    primary_key: bool,
+}
+
+fn column_definition(tokens: &mut Tokens) -> Result<ColumnDefinition, DatabaseError> {
+    let name = tokens.identifier()?;
+
+    let mut type_name = Vec::new();
+    while let Some(Token {
+        kind: TokenKind::Word(word),
+        ..
+    }) = tokens.peek()
+    {
+        if COLUMN_CONSTRAINTS
+            .iter()
+            .any(|keyword| word.eq_ignore_ascii_case(keyword))
+        {
+            break;
+        }
+        type_name.push(word.to_ascii_uppercase());
+        tokens.next();
+        // sizes such as VARCHAR(255) or DECIMAL(10, 2)
+        if tokens.eat_symbol("(") {
+            tokens.skip_group()?;
+        }
+    }
+
+    let mut definition = ColumnDefinition {
+        column: Column::new(&name, false).with_type(column_type(&type_name.join(" "))),
+        primary_key: false,
+    };
+
+    while !tokens.peek_symbol(",") && !tokens.peek_symbol(")") {
+        if tokens.eat_keyword("constraint") {
+            tokens.identifier()?;
+        } else if tokens.eat_keyword("primary") {
+            tokens.expect_keyword("key")?;
+            definition.primary_key = true;
+            definition.column.required = true;
+        } else if tokens.eat_keyword("not") {
+            tokens.expect_keyword("null")?;
+            definition.column.required = true;
+        } else if tokens.eat_keyword("default") {
+            definition.column.default = if tokens.eat_symbol("(") {
+                tokens.skip_group()?;
+                None
+            } else {
+                literal(tokens).ok()
+            };
+        } else if tokens.eat_keyword("references") {
+            let table = qualified_name(tokens)?;
+            let column = if tokens.eat_symbol("(") {
+                let column = tokens.identifier()?;
+                tokens.expect_symbol(")")?;
+                column
+            } else {
+                "id".to_string()
+            };
+            definition.column = definition.column.references(&table, &column);
+        } else if tokens.eat_symbol("(") {
+            // CHECK (...), GENERATED ALWAYS AS (...)
+            tokens.skip_group()?;
+        } else if tokens.next().is_none() {
+            return Err(tokens.error("expected `)`"));
+        }
+    }
+    Ok(definition)
+}
+
+fn table_constraint(
+    tokens: &mut Tokens,
+    columns: &mut [Column],
+    primary_key: &mut Vec<String>,
+) -> Result<(), DatabaseError> {
+    if tokens.eat_keyword("constraint") {
+        tokens.identifier()?;
+    }
+
+    if tokens.eat_keyword("primary") {
+        tokens.expect_keyword("key")?;
+        *primary_key = column_list(tokens)?;
+    } else if tokens.eat_keyword("foreign") {
+        tokens.expect_keyword("key")?;
+        let names = column_list(tokens)?;
+        tokens.expect_keyword("references")?;
+        let table = qualified_name(tokens)?;
+        let targets = if tokens.peek_symbol("(") {
+            column_list(tokens)?
+        } else {
+            vec!["id".to_string()]
+        };
+        if names.len() > 1 || targets.len() > 1 {
+            return Err(tokens.error("composite foreign keys are not supported"));
+        }
+        let column = columns
+            .iter_mut()
+            .find(|c| c.name == names[0])
+            .ok_or_else(|| tokens.error(format!("unknown column `{}`", names[0])))?;
+        *column = column.clone().references(&table, &targets[0]);
+    }
+
+    // UNIQUE (...), CHECK (...) and clauses such as ON DELETE CASCADE
+    while !tokens.peek_symbol(",") && !tokens.peek_symbol(")") {
+        if tokens.eat_symbol("(") {
+            tokens.skip_group()?;
+        } else if tokens.next().is_none() {
+            return Err(tokens.error("expected `)`"));
+        }
+    }
+    Ok(())
+}
+
+// `INSERT [OR ...] INTO` or `REPLACE INTO`, returns the number of rows added
+fn insert(
+    tokens: &mut Tokens,
+    tables: &mut HashMap<String, Table>,
+    path: &Path,
+) -> Result<usize, DatabaseError> {
+    let conflict = if tokens.eat_keyword("replace") {
+        OnConflict::Replace
+    } else {
+        tokens.expect_keyword("insert")?;
+        if tokens.eat_keyword("or") {
+            if tokens.eat_keyword("replace") {
+                OnConflict::Replace
+            } else if tokens.eat_keyword("ignore") {
+                OnConflict::Ignore
+            } else {
+                tokens.identifier()?;
+                OnConflict::Fail
+            }
+        } else {
+            OnConflict::Fail
+        }
+    };
+    tokens.expect_keyword("into")?;
+
+    let name_position = tokens.position();
+    let table_name = qualified_name(tokens)?;
+    let table = tables.get_mut(&table_name).ok_or_else(|| {
+        sql_error(
+            name_position,
+            format!("table `{}` does not exist", table_name),
+        )
+    })?;
+
+    let names = if tokens.peek_symbol("(") {
+        column_list(tokens)?
+    } else {
+        table.columns.0.iter().map(|c| c.name.clone()).collect()
+    };
+    let hooks = hooks::table_hooks(path, &table_name);
+    tokens.expect_keyword("values")?;
+
+    let mut inserted = 0;
+    loop {
+        let row_position = tokens.position();
+        tokens.expect_symbol("(")?;
+        let mut values = Vec::with_capacity(names.len());
+        loop {
+            values.push(literal(tokens)?);
+            if tokens.eat_symbol(")") {
+                break;
+            }
+            tokens.expect_symbol(",")?;
+        }
+        if values.len() != names.len() {
+            return Err(sql_error(
+                row_position,
+                format!("expected {} values, got {}", names.len(), values.len()),
+            ));
+        }
+
+        let mut row = Map::new();
+        for (name, value) in names.iter().zip(values) {
+            let column_type = table
+                .columns
+                .0
+                .iter()
+                .find(|c| &c.name == name)
+                .map(|c| c.column_type)
+                .unwrap_or_default();
+            row.insert(name.clone(), coerce(column_type, value));
+        }
+        let row = Value::Object(row);
+
+        // `REPLACE` counts as an insert, like in SQLite
+        if table
+            .insert_row(row, hooks.as_deref(), conflict)
+            .map_err(|e| sql_error(row_position, e.to_string()))?
+            .is_some()
+        {
+            inserted += 1;
+        }
+
+        if !tokens.eat_symbol(",") {
+            break;
+        }
+    }
+
+    if !tokens.is_done() {
+        return Err(tokens.error("unexpected text after VALUES"));
+    }
+    Ok(inserted)
+}
+
+// `table` or `schema.table`, of which only the table name is kept
+pub(super) fn qualified_name(tokens: &mut Tokens) -> Result<String, DatabaseError> {
+    let mut name = tokens.identifier()?;
+    while tokens.eat_symbol(".") {
+        name = tokens.identifier()?;
+    }
+    Ok(name)
+}
+
+// `(a, b, ...)`, ignoring ASC / DESC and COLLATE on key columns
+fn column_list(tokens: &mut Tokens) -> Result<Vec<String>, DatabaseError> {
+    tokens.expect_symbol("(")?;
+    let mut names = vec![tokens.identifier()?];
+    loop {
+        if tokens.eat_symbol(")") {
+            return Ok(names);
+        }
+        if tokens.eat_symbol(",") {
+            names.push(tokens.identifier()?);
+        } else if tokens.next().is_none() {
+            return Err(tokens.error("expected `)`"));
+        }
+    }
+}
+
+pub(super) fn literal(tokens: &mut Tokens) -> Result<Value, DatabaseError> {
+    let position = tokens.position();
+    let negative = tokens.eat_symbol("-");
+    if !negative {
+        tokens.eat_symbol("+");
+    }
+
+    let value = match tokens.next().map(|token| &token.kind) {
+        Some(TokenKind::Number(number)) => {
+            let number = if negative {
+                format!("-{}", number)
+            } else {
+                number.clone()
+            };
+            number
+                .parse::<i64>()
+                .map(Value::from)
+                .ok()
+                .or_else(|| {
+                    number
+                        .parse::<f64>()
+                        .ok()
+                        .and_then(Number::from_f64)
+                        .map(Value::Number)
+                })
+                .ok_or_else(|| sql_error(position, format!("invalid number `{}`", number)))?
+        }
+        Some(TokenKind::String(text)) if !negative => Value::String(text.clone()),
+        Some(TokenKind::Word(word)) if !negative => match word.to_ascii_lowercase().as_str() {
+            "null" => Value::Null,
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            "x" if matches!(tokens.peek().map(|t| &t.kind), Some(TokenKind::String(_))) => {
+                return Err(sql_error(position, "blob literals are not supported"))
+            }
+            _ => return Err(sql_error(position, "expected a literal value")),
+        },
+        _ => return Err(sql_error(position, "expected a literal value")),
+    };
+    Ok(value)
+}
+
+// read a literal as the declared type of its column, the way SQLite's type
+// affinity does, with 0 and 1 as booleans
+pub(super) fn coerce(column_type: ColumnType, value: Value) -> Value {
+    match (column_type, value) {
+        (ColumnType::Text, Value::Number(n)) => Value::String(n.to_string()),
+        (ColumnType::Integer, Value::String(text)) => match text.parse::<i64>() {
+            Ok(n) => Value::from(n),
+            Err(_) => Value::String(text),
+        },
+        (ColumnType::Boolean, Value::Number(n)) if n.as_i64() == Some(0) => Value::Bool(false),
+        (ColumnType::Boolean, Value::Number(n)) if n.as_i64() == Some(1) => Value::Bool(true),
+        (_, value) => value,
+    }
+}
+
+// SQLite's column affinity rules, with BOOL / BOOLEAN read as booleans
+fn column_type(type_name: &str) -> ColumnType {
+    if type_name.is_empty() {
+        ColumnType::Any
+    } else if type_name.contains("BOOL") {
+        ColumnType::Boolean
+    } else if type_name.contains("INT") {
+        ColumnType::Integer
+    } else if ["CHAR", "CLOB", "TEXT"]
+        .iter()
+        .any(|t| type_name.contains(t))
+    {
+        ColumnType::Text
+    } else if ["REAL", "FLOA", "DOUB", "NUMERIC", "DECIMAL"]
+        .iter()
+        .any(|t| type_name.contains(t))
+    {
+        ColumnType::Float
+    } else {
+        ColumnType::Any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{setup_temp_db, ForeignKey};
+
+    const DUMP: &str = r#"
+PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE IF NOT EXISTS "authors" (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name VARCHAR(255) NOT NULL,
+    active BOOLEAN DEFAULT 1,
+    rating DECIMAL(3, 1),
+    bio TEXT CHECK (length(bio) < 500)
+);
+INSERT INTO authors VALUES(1,'O''Brien',0,4.5,NULL);
+INSERT INTO "authors" (id, name) VALUES (2, 'Jane'), (3, 'John');
+CREATE TABLE posts (
+    id TEXT NOT NULL,
+    author_id INTEGER NOT NULL,
+    score REAL DEFAULT -1,
+    CONSTRAINT pk PRIMARY KEY (id),
+    FOREIGN KEY (author_id) REFERENCES authors (id) ON DELETE CASCADE
+);
+INSERT INTO posts VALUES('p1', 2, -2.5);
+CREATE INDEX posts_author ON posts (author_id);
+COMMIT;
+"#;
+
+    #[tokio::test]
+    async fn test_import_sql() {
+        let mut db = setup_temp_db().await;
+
+        let report = db.import_sql(DUMP).await.unwrap();
+
+        assert_eq!(report.tables, vec!["authors", "posts"]);
+        assert_eq!(report.rows, 4);
+        assert_eq!(report.skipped, 4);
+
+        let authors = db.get_table("authors").unwrap();
+        let types: Vec<(bool, ColumnType)> = authors
+            .columns
+            .0
+            .iter()
+            .map(|c| (c.required, c.column_type))
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                (true, ColumnType::Integer),
+                (true, ColumnType::Text),
+                (false, ColumnType::Boolean),
+                (false, ColumnType::Float),
+                (false, ColumnType::Text),
+            ]
+        );
+        assert_eq!(authors.columns.0[2].default, Some(json!(true)));
+        assert_eq!(
+            authors.rows["1"].data,
+            json!({"id": 1, "name": "O'Brien", "active": false, "rating": 4.5, "bio": null})
+        );
+        assert_eq!(
+            authors.rows["2"].data,
+            json!({"id": 2, "name": "Jane", "active": true})
+        );
+
+        let posts = db.get_table("posts").unwrap();
+        assert_eq!(
+            posts.columns.0[1].references,
+            Some(ForeignKey {
+                table: "authors".to_string(),
+                column: "id".to_string()
+            })
+        );
+        assert_eq!(
+            posts.rows["p1"].data,
+            json!({"id": "p1", "author_id": 2, "score": -2.5})
+        );
+
+        let on_disk = Database::load_from_file(&db.file_name).await.unwrap();
+        assert_eq!(on_disk.tables, db.tables);
+    }
+
+    #[tokio::test]
+    async fn test_import_sql_round_trip() {
+        let mut db = setup_temp_db().await;
+        db.import_sql(DUMP).await.unwrap();
+        let mut dump = Vec::new();
+        db.export_sql(&mut dump).unwrap();
+
+        let mut copy = setup_temp_db().await;
+        copy.drop_table("TestTable").await.unwrap();
+        copy.import_sql(std::str::from_utf8(&dump).unwrap())
+            .await
+            .unwrap();
+
+        // columns survive, and rows come back with absent fields as null
+        for (name, table) in &db.tables {
+            let copied = &copy.tables[name];
+            assert_eq!(copied.columns, table.columns);
+            assert_eq!(copied.rows.len(), table.rows.len());
+        }
+        assert_eq!(
+            copy.tables["authors"].rows["2"].data,
+            json!({"id": 2, "name": "Jane", "active": true, "rating": null, "bio": null})
+        );
+        assert_eq!(
+            copy.tables["authors"].columns.0[0].column_type,
+            ColumnType::Integer
+        );
+    }
+
+    #[tokio::test]
+    async fn test_import_sql_errors_leave_database_unchanged() {
+        let mut db = setup_temp_db().await;
+        let sql = "CREATE TABLE users (id TEXT PRIMARY KEY, name TEXT NOT NULL);\nINSERT INTO users VALUES ('1', 'John');\nINSERT INTO users (id) VALUES ('2');";
+
+        let result = db.import_sql(sql).await;
+
+        match result {
+            Err(DatabaseError::SqlError { line, column, .. }) => {
+                assert_eq!((line, column), (3, 31))
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(db.get_table("users").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_import_sql_primary_keys() {
+        let mut db = setup_temp_db().await;
+        db.import_sql(
+            "CREATE TABLE users (user_id INTEGER PRIMARY KEY, name TEXT);
+             INSERT INTO users VALUES (7, 'Ann');
+             CREATE TABLE members (org TEXT, user_id INTEGER, PRIMARY KEY (org, user_id));
+             INSERT INTO members VALUES ('acme', 7);",
+        )
+        .await
+        .unwrap();
+
+        let users = db.get_table("users").unwrap();
+        assert_eq!(users.primary_key(), ["user_id"]);
+        assert_eq!(users.rows["7"].data, json!({"user_id": 7, "name": "Ann"}));
+        assert!(db.key_exists("members", &[json!("acme"), json!(7)]));
+
+        let result = db.import_sql("CREATE TABLE notes (body TEXT);").await;
+        match result {
+            Err(DatabaseError::SqlError {
+                line,
+                column,
+                message,
+            }) => {
+                assert_eq!((line, column), (1, 14));
+                assert!(message.contains("primary key"));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_import_sql_conflicts() {
+        let mut db = setup_temp_db().await;
+        let sql = "CREATE TABLE t (id TEXT, n INTEGER);
+INSERT INTO t VALUES ('1', 1);
+INSERT OR IGNORE INTO t VALUES ('1', 2);
+REPLACE INTO t VALUES ('1', 3);";
+
+        let report = db.import_sql(sql).await.unwrap();
+
+        assert_eq!(report.rows, 2);
+        assert_eq!(db.get_table("t").unwrap().rows["1"].data["n"], json!(3));
+
+        let duplicate = db.import_sql("INSERT INTO t VALUES ('1', 4);").await;
+        assert!(matches!(duplicate, Err(DatabaseError::SqlError { .. })));
+    }
+}
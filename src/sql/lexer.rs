@@ -0,0 +1,351 @@
+use crate::DatabaseError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TokenKind {
+    /// A bare word: a keyword or an unquoted identifier.
+    Word(String),
+    /// An identifier quoted with `"`, `` ` `` or `[]`.
+    QuotedIdent(String),
+    /// A `'single quoted'` string literal.
+    String(String),
+    /// A numeric literal, kept as written.
+    Number(String),
+    Symbol(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Token {
+    pub kind: TokenKind,
+    /// 1-based position of the first character of the token.
+    pub line: usize,
+    pub column: usize,
+}
+
+// longest symbols first so `<=` is not read as `<` `=`
+const SYMBOLS: &[&str] = &[
+    "<=", ">=", "!=", "<>", "==", "(", ")", ",", ";", ".", "*", "=", "<", ">", "+", "-", "/",
+];
+
+/// Split SQL text into tokens, dropping whitespace and `--` / `/* */`
+/// comments.
+pub(crate) fn tokenize(input: &str) -> Result<Vec<Token>, DatabaseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let (mut i, mut line, mut column) = (0, 1, 1);
+
+    // move past `n` characters, keeping track of the position
+    let advance = |i: &mut usize, line: &mut usize, column: &mut usize, n: usize| {
+        for _ in 0..n {
+            if chars[*i] == '\n' {
+                *line += 1;
+                *column = 1;
+            } else {
+                *column += 1;
+            }
+            *i += 1;
+        }
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        let (start_line, start_column) = (line, column);
+        let error = |message: &str| DatabaseError::SqlError {
+            line: start_line,
+            column: start_column,
+            message: message.to_string(),
+        };
+
+        if c.is_whitespace() {
+            advance(&mut i, &mut line, &mut column, 1);
+            continue;
+        }
+        if c == '-' && chars.get(i + 1) == Some(&'-') {
+            while i < chars.len() && chars[i] != '\n' {
+                advance(&mut i, &mut line, &mut column, 1);
+            }
+            continue;
+        }
+        if c == '/' && chars.get(i + 1) == Some(&'*') {
+            advance(&mut i, &mut line, &mut column, 2);
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                advance(&mut i, &mut line, &mut column, 1);
+            }
+            if i >= chars.len() {
+                return Err(error("unterminated comment"));
+            }
+            advance(&mut i, &mut line, &mut column, 2);
+            continue;
+        }
+
+        let kind = if let Some(close) = match c {
+            '\'' => Some('\''),
+            '"' => Some('"'),
+            '`' => Some('`'),
+            '[' => Some(']'),
+            _ => None,
+        } {
+            // quoted text, where a doubled closing quote stands for itself
+            advance(&mut i, &mut line, &mut column, 1);
+            let mut text = String::new();
+            loop {
+                match chars.get(i) {
+                    None => return Err(error("unterminated quoted text")),
+                    Some(&ch) if ch == close => {
+                        if close != ']' && chars.get(i + 1) == Some(&close) {
+                            text.push(close);
+                            advance(&mut i, &mut line, &mut column, 2);
+                        } else {
+                            advance(&mut i, &mut line, &mut column, 1);
+                            break;
+                        }
+                    }
+                    Some(&ch) => {
+                        text.push(ch);
+                        advance(&mut i, &mut line, &mut column, 1);
+                    }
+                }
+            }
+            if c == '\'' {
+                TokenKind::String(text)
+            } else {
+                TokenKind::QuotedIdent(text)
+            }
+        } else if c.is_ascii_digit()
+            || (c == '.' && chars.get(i + 1).is_some_and(|n| n.is_ascii_digit()))
+        {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.') {
+                // an exponent may carry a sign
+                if matches!(chars[i], 'e' | 'E') && matches!(chars.get(i + 1), Some('+' | '-')) {
+                    advance(&mut i, &mut line, &mut column, 1);
+                }
+                advance(&mut i, &mut line, &mut column, 1);
+            }
+            TokenKind::Number(chars[start..i].iter().collect())
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '$')) {
+                advance(&mut i, &mut line, &mut column, 1);
+            }
+            TokenKind::Word(chars[start..i].iter().collect())
+        } else if let Some(symbol) = SYMBOLS.iter().find(|symbol| {
+            symbol
+                .chars()
+                .enumerate()
+                .all(|(offset, s)| chars.get(i + offset) == Some(&s))
+        }) {
+            advance(&mut i, &mut line, &mut column, symbol.len());
+            TokenKind::Symbol(symbol)
+        } else {
+            return Err(error(&format!("unexpected character `{}`", c)));
+        };
+
+        tokens.push(Token {
+            kind,
+            line: start_line,
+            column: start_column,
+        });
+    }
+
+    Ok(tokens)
+}
+
+/// A cursor over the tokens of one statement.
+pub(crate) struct Tokens<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    // reported for errors at the end of the statement
+    end: (usize, usize),
+}
+
+impl<'a> Tokens<'a> {
+    pub fn new(tokens: &'a [Token], end: (usize, usize)) -> Self {
+        Tokens {
+            tokens,
+            pos: 0,
+            end,
+        }
+    }
+
+    pub fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos)
+    }
+
+    pub fn next(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.get(self.pos);
+        if token.is_some() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    /// Line and column of the next token, or of the end of the statement.
+    pub fn position(&self) -> (usize, usize) {
+        self.peek()
+            .map(|token| (token.line, token.column))
+            .unwrap_or(self.end)
+    }
+
+    /// An error located at the next token, or at the end of the statement.
+    pub fn error(&self, message: impl Into<String>) -> DatabaseError {
+        sql_error(self.position(), message)
+    }
+
+    pub fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token { kind: TokenKind::Word(word), .. }) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    pub fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.peek_keyword(keyword);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    pub fn expect_keyword(&mut self, keyword: &str) -> Result<(), DatabaseError> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{}`", keyword.to_ascii_uppercase())))
+        }
+    }
+
+    pub fn peek_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Some(Token { kind: TokenKind::Symbol(s), .. }) if *s == symbol)
+    }
+
+    pub fn eat_symbol(&mut self, symbol: &str) -> bool {
+        let found = self.peek_symbol(symbol);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    pub fn expect_symbol(&mut self, symbol: &str) -> Result<(), DatabaseError> {
+        if self.eat_symbol(symbol) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{}`", symbol)))
+        }
+    }
+
+    /// A bare or quoted identifier.
+    pub fn identifier(&mut self) -> Result<String, DatabaseError> {
+        match self.peek().map(|token| &token.kind) {
+            Some(TokenKind::Word(name)) | Some(TokenKind::QuotedIdent(name)) => {
+                self.pos += 1;
+                Ok(name.clone())
+            }
+            _ => Err(self.error("expected a name")),
+        }
+    }
+
+    /// Skip tokens up to the `)` closing a group whose `(` was already read.
+    pub fn skip_group(&mut self) -> Result<(), DatabaseError> {
+        let mut depth = 1;
+        while depth > 0 {
+            match self.next().map(|token| &token.kind) {
+                Some(TokenKind::Symbol("(")) => depth += 1,
+                Some(TokenKind::Symbol(")")) => depth -= 1,
+                Some(_) => {}
+                None => return Err(self.error("expected `)`")),
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn sql_error(
+    (line, column): (usize, usize),
+    message: impl Into<String>,
+) -> DatabaseError {
+    DatabaseError::SqlError {
+        line,
+        column,
+        message: message.into(),
+    }
+}
+
+/// Split tokens into statements at each `;`, dropping empty statements. Each
+/// statement comes with the position just past its last token.
+pub(crate) fn statements(tokens: &[Token]) -> Vec<(&[Token], (usize, usize))> {
+    tokens
+        .split(|token| token.kind == TokenKind::Symbol(";"))
+        .filter(|statement| !statement.is_empty())
+        .map(|statement| {
+            let last = statement.last().unwrap();
+            (statement, (last.line, last.column + 1))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(input: &str) -> Vec<TokenKind> {
+        tokenize(input)
+            .unwrap()
+            .into_iter()
+            .map(|token| token.kind)
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            kinds("SELECT \"a b\", [c] FROM t WHERE x <= -1.5e-3 AND y = 'it''s'; -- done"),
+            vec![
+                TokenKind::Word("SELECT".to_string()),
+                TokenKind::QuotedIdent("a b".to_string()),
+                TokenKind::Symbol(","),
+                TokenKind::QuotedIdent("c".to_string()),
+                TokenKind::Word("FROM".to_string()),
+                TokenKind::Word("t".to_string()),
+                TokenKind::Word("WHERE".to_string()),
+                TokenKind::Word("x".to_string()),
+                TokenKind::Symbol("<="),
+                TokenKind::Symbol("-"),
+                TokenKind::Number("1.5e-3".to_string()),
+                TokenKind::Word("AND".to_string()),
+                TokenKind::Word("y".to_string()),
+                TokenKind::Symbol("="),
+                TokenKind::String("it's".to_string()),
+                TokenKind::Symbol(";"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_positions() {
+        let tokens = tokenize("/* header\n */ CREATE\n  TABLE").unwrap();
+        let positions: Vec<(usize, usize)> = tokens.iter().map(|t| (t.line, t.column)).collect();
+        assert_eq!(positions, vec![(2, 5), (3, 3)]);
+    }
+
+    #[test]
+    fn test_tokenize_errors() {
+        assert!(matches!(
+            tokenize("SELECT 'open"),
+            Err(DatabaseError::SqlError {
+                line: 1,
+                column: 8,
+                ..
+            })
+        ));
+        assert!(matches!(
+            tokenize("SELECT\n  #"),
+            Err(DatabaseError::SqlError {
+                line: 2,
+                column: 3,
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,17 @@
+pub mod dump;
+pub mod import;
+pub(crate) mod lexer;
+pub mod query;
+
+pub use import::SqlImportReport;
+pub use query::SqlOutput;
+
+// SQL identifiers are double quoted, with embedded quotes doubled
+pub(crate) fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+// SQL string literals are single quoted, with embedded quotes doubled
+pub(crate) fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
@@ -0,0 +1,519 @@
+use serde_json::{Map, Value};
+
+use super::import::{coerce, literal, qualified_name};
+use super::lexer::{sql_error, statements, tokenize, TokenKind, Tokens};
+use crate::database_components::table::OnConflict;
+use crate::{ColumnType, Database, DatabaseError, FilterOp, KeyStrategy, Operation, Query, Table};
+
+/// Result of `Database::execute_sql`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlOutput {
+    /// Rows returned by `SELECT`.
+    Rows(Vec<Value>),
+    /// Number of rows inserted, updated or deleted.
+    Affected(usize),
+}
+
+impl Database {
+    /// Compile one SQL statement into the queries that run it:
+    ///
+    /// - `SELECT * | col, ... FROM t [WHERE ...] [ORDER BY col [ASC|DESC]] [LIMIT n]`
+    /// - `INSERT INTO t [(col, ...)] VALUES (...), ...`, one query per row
+    /// - `UPDATE t SET col = value, ... [WHERE ...]`
+    /// - `DELETE FROM t [WHERE ...]`
+    ///
+    /// Conditions compare a column with a literal using `=`, `!=`, `<>`, `<`,
+    /// `<=`, `>`, `>=`, `IS [NOT] NULL` or `CONTAINS`, and are joined with
+    /// `AND`. Tables and columns are checked against this database, and
+    /// errors point at the offending token.
+    pub fn compile_sql(&self, sql: &str) -> Result<Vec<Query>, DatabaseError> {
+        let tokens = tokenize(sql)?;
+        let statements = statements(&tokens);
+        let (statement, end) = match statements.as_slice() {
+            [] => return Err(sql_error((1, 1), "expected a statement")),
+            [statement] => *statement,
+            [_, (next, _), ..] => {
+                return Err(sql_error(
+                    (next[0].line, next[0].column),
+                    "expected a single statement",
+                ))
+            }
+        };
+
+        let mut tokens = Tokens::new(statement, end);
+        let queries = if tokens.eat_keyword("select") {
+            vec![self.compile_select(&mut tokens)?]
+        } else if tokens.eat_keyword("insert") {
+            self.compile_insert(&mut tokens)?
+        } else if tokens.eat_keyword("update") {
+            vec![self.compile_update(&mut tokens)?]
+        } else if tokens.eat_keyword("delete") {
+            vec![self.compile_delete(&mut tokens)?]
+        } else {
+            return Err(tokens.error("expected SELECT, INSERT, UPDATE or DELETE"));
+        };
+
+        if !tokens.is_done() {
+            return Err(tokens.error("unexpected text at the end of the statement"));
+        }
+        Ok(queries)
+    }
+
+    /// Compile and run one SQL statement, see `compile_sql`. An `INSERT`
+    /// adds all of its rows or none of them.
+    pub async fn execute_sql(&self, sql: &str) -> Result<SqlOutput, DatabaseError> {
+        let queries = self.compile_sql(sql)?;
+        match queries[0].operation {
+            Operation::Read => Ok(SqlOutput::Rows(queries[0].rows().await?)),
+            Operation::Update => Ok(SqlOutput::Affected(queries[0].update_matching().await?)),
+            Operation::Delete => Ok(SqlOutput::Affected(queries[0].delete_matching().await?)),
+            Operation::Create => Ok(SqlOutput::Affected(insert_rows(&queries).await?)),
+        }
+    }
+
+    fn compile_select(&self, tokens: &mut Tokens) -> Result<Query, DatabaseError> {
+        // the columns come before the table they belong to, so they are
+        // checked once the table is known
+        let mut columns = Vec::new();
+        if !tokens.eat_symbol("*") {
+            loop {
+                columns.push((tokens.position(), tokens.identifier()?));
+                if !tokens.eat_symbol(",") {
+                    break;
+                }
+            }
+        }
+        tokens.expect_keyword("from")?;
+        let table = self.sql_table(tokens)?;
+        for (position, column) in &columns {
+            check_column(table, column, *position)?;
+        }
+
+        let mut query = self.get_rows().from(&table.name);
+        if !columns.is_empty() {
+            let columns: Vec<&str> = columns.iter().map(|(_, c)| c.as_str()).collect();
+            query = query.select(&columns);
+        }
+        query = conditions(tokens, table, query)?;
+
+        if tokens.eat_keyword("order") {
+            tokens.expect_keyword("by")?;
+            let column = column(tokens, table)?;
+            query = if tokens.eat_keyword("desc") {
+                query.order_by_desc(&column)
+            } else {
+                tokens.eat_keyword("asc");
+                query.order_by(&column)
+            };
+        }
+        if tokens.eat_keyword("limit") {
+            let position = tokens.position();
+            let limit = match tokens.next().map(|token| &token.kind) {
+                Some(TokenKind::Number(number)) => number.parse().ok(),
+                _ => None,
+            };
+            let limit = limit.ok_or_else(|| sql_error(position, "expected a row count"))?;
+            query = query.limit(limit);
+        }
+        Ok(query)
+    }
+
+    fn compile_insert(&self, tokens: &mut Tokens) -> Result<Vec<Query>, DatabaseError> {
+        tokens.expect_keyword("into")?;
+        let table = self.sql_table(tokens)?;
+        let names = if tokens.eat_symbol("(") {
+            let mut names = vec![column(tokens, table)?];
+            while tokens.eat_symbol(",") {
+                names.push(column(tokens, table)?);
+            }
+            tokens.expect_symbol(")")?;
+            names
+        } else {
+            table.columns.0.iter().map(|c| c.name.clone()).collect()
+        };
+        tokens.expect_keyword("values")?;
+
+        // a missing key, defaults and computed columns are filled in when the
+        // query runs, so they need not be given here
+        let generates_key = table.key_strategy() != KeyStrategy::Supplied;
+        let mut columns = table.columns.clone();
+        for column in &mut columns.0 {
+            if (generates_key && table.is_key_column(&column.name))
+                || column.default.is_some()
+                || column.generated.is_some()
+            {
+                column.required = false;
+            }
+        }
+
+        let mut queries = Vec::new();
+        loop {
+            let row_position = tokens.position();
+            tokens.expect_symbol("(")?;
+            let mut row = Map::new();
+            for (i, name) in names.iter().enumerate() {
+                if i > 0 {
+                    tokens.expect_symbol(",")?;
+                }
+                let value = literal(tokens)?;
+                row.insert(name.clone(), coerce(column_type(table, name), value));
+            }
+            tokens.expect_symbol(")")?;
+            let row = Value::Object(row);
+            columns
+                .validate(row.clone())
+                .map_err(|e| sql_error(row_position, e.to_string()))?;
+            queries.push(
+                Query::new(self.file_name.clone(), Operation::Create)
+                    .from(&table.name)
+                    .data_from_struct(row),
+            );
+
+            if !tokens.eat_symbol(",") {
+                return Ok(queries);
+            }
+        }
+    }
+
+    fn compile_update(&self, tokens: &mut Tokens) -> Result<Query, DatabaseError> {
+        let table = self.sql_table(tokens)?;
+        tokens.expect_keyword("set")?;
+
+        let mut data = Map::new();
+        loop {
+            let position = tokens.position();
+            let name = column(tokens, table)?;
+            if table.is_key_column(&name) {
+                return Err(sql_error(
+                    position,
+                    format!("the key column `{}` cannot be updated", name),
+                ));
+            }
+            tokens.expect_symbol("=")?;
+            let value = literal(tokens)?;
+            data.insert(name.clone(), coerce(column_type(table, &name), value));
+            if !tokens.eat_symbol(",") {
+                break;
+            }
+        }
+
+        let query = self.update_row().from(&table.name).set(Value::Object(data));
+        conditions(tokens, table, query)
+    }
+
+    fn compile_delete(&self, tokens: &mut Tokens) -> Result<Query, DatabaseError> {
+        tokens.expect_keyword("from")?;
+        let table = self.sql_table(tokens)?;
+        let query = self.delete_single().from(&table.name);
+        conditions(tokens, table, query)
+    }
+
+    fn sql_table(&self, tokens: &mut Tokens) -> Result<&Table, DatabaseError> {
+        let position = tokens.position();
+        let name = qualified_name(tokens)?;
+        self.tables
+            .get(&name)
+            .ok_or_else(|| sql_error(position, format!("table `{}` does not exist", name)))
+    }
+}
+
+// the rows of one INSERT, added to the latest state of their table and saved
+// together, so a row that fails leaves the table as it was
+async fn insert_rows(queries: &[Query]) -> Result<usize, DatabaseError> {
+    let mut db = Database::load_latest(&queries[0].db_file_name).await?;
+    let table_name = queries[0].table_name.clone().unwrap_or_default();
+    let hooks = db.hooks(&table_name);
+    let mut table = db
+        .get_table(&table_name)
+        .cloned()
+        .ok_or_else(|| DatabaseError::TableNotFound(table_name.clone()))?;
+
+    for query in queries {
+        let row = query.row_data.clone().unwrap_or_default();
+        table.insert_row(row, hooks.as_deref(), OnConflict::Fail)?;
+    }
+    db.tables.insert(table_name, table);
+    db.commit().await?;
+    Ok(queries.len())
+}
+
+// an optional `WHERE a = 1 AND b > 2 ...`, added to the query as filters
+fn conditions(
+    tokens: &mut Tokens,
+    table: &Table,
+    mut query: Query,
+) -> Result<Query, DatabaseError> {
+    if !tokens.eat_keyword("where") {
+        return Ok(query);
+    }
+    loop {
+        let name = column(tokens, table)?;
+        let position = tokens.position();
+        let op = if tokens.eat_keyword("is") {
+            let op = if tokens.eat_keyword("not") {
+                FilterOp::Ne
+            } else {
+                FilterOp::Eq
+            };
+            if !tokens.peek_keyword("null") {
+                return Err(tokens.error("expected `NULL`"));
+            }
+            op
+        } else if tokens.eat_keyword("contains") {
+            FilterOp::Contains
+        } else {
+            let op = match tokens.next().map(|token| &token.kind) {
+                Some(TokenKind::Symbol("=" | "==")) => FilterOp::Eq,
+                Some(TokenKind::Symbol("!=" | "<>")) => FilterOp::Ne,
+                Some(TokenKind::Symbol("<")) => FilterOp::Lt,
+                Some(TokenKind::Symbol("<=")) => FilterOp::Le,
+                Some(TokenKind::Symbol(">")) => FilterOp::Gt,
+                Some(TokenKind::Symbol(">=")) => FilterOp::Ge,
+                _ => return Err(sql_error(position, "expected a comparison operator")),
+            };
+            op
+        };
+        let value = literal(tokens)?;
+        query = query.filter(&name, op, coerce(column_type(table, &name), value));
+
+        if tokens.peek_keyword("or") {
+            return Err(tokens.error("OR is not supported, conditions can only be joined with AND"));
+        }
+        if !tokens.eat_keyword("and") {
+            return Ok(query);
+        }
+    }
+}
+
+// a column name, which has to be one of the table's columns when it
+// declares any
+fn column(tokens: &mut Tokens, table: &Table) -> Result<String, DatabaseError> {
+    let position = tokens.position();
+    let name = tokens.identifier()?;
+    check_column(table, &name, position)?;
+    Ok(name)
+}
+
+fn check_column(table: &Table, name: &str, position: (usize, usize)) -> Result<(), DatabaseError> {
+    let columns = &table.columns.0;
+    if columns.is_empty() || columns.iter().any(|c| c.name == name) {
+        Ok(())
+    } else {
+        Err(sql_error(
+            position,
+            format!("table `{}` has no column `{}`", table.name, name),
+        ))
+    }
+}
+
+fn column_type(table: &Table, name: &str) -> ColumnType {
+    table
+        .columns
+        .0
+        .iter()
+        .find(|c| c.name == name)
+        .map(|c| c.column_type)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{setup_temp_db, Column, Columns};
+
+    async fn setup_people() -> Database {
+        let mut db = setup_temp_db().await;
+        let mut people = Table::new(
+            "people".to_string(),
+            Columns::new(vec![
+                Column::new("id", true),
+                Column::new("name", true),
+                Column::new("age", false).with_type(ColumnType::Integer),
+                Column::new("admin", false).with_type(ColumnType::Boolean),
+            ]),
+        );
+        db.add_table(&mut people).await.unwrap();
+        people
+            .add_row(
+                &mut db,
+                json!([
+                    {"id": "1", "name": "Alice", "age": 31, "admin": true},
+                    {"id": "2", "name": "Bob", "age": 25, "admin": false},
+                    {"id": "3", "name": "Carol", "age": 47, "admin": false},
+                ]),
+            )
+            .await;
+        db
+    }
+
+    fn assert_error(db: &Database, sql: &str, line: usize, column: usize, text: &str) {
+        match db.compile_sql(sql) {
+            Err(DatabaseError::SqlError {
+                line: l,
+                column: c,
+                message,
+            }) => {
+                assert_eq!((l, c), (line, column), "{}: {}", sql, message);
+                assert!(message.contains(text), "{}: {}", sql, message);
+            }
+            other => panic!("{}: expected an error, got {:?}", sql, other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compile_select() {
+        let db = setup_people().await;
+
+        let queries = db
+            .compile_sql(
+                "SELECT name FROM people WHERE age >= 30 AND admin = 0 ORDER BY age DESC LIMIT 1;",
+            )
+            .unwrap();
+        let expected = db
+            .get_rows()
+            .from("people")
+            .select(&["name"])
+            .filter("age", FilterOp::Ge, 30)
+            .filter("admin", FilterOp::Eq, false)
+            .order_by_desc("age")
+            .limit(1);
+        assert_eq!(queries, vec![expected]);
+    }
+
+    #[tokio::test]
+    async fn test_execute_select() {
+        let db = setup_people().await;
+
+        let output = db
+            .execute_sql("select name from people where name contains 'o' order by name")
+            .await
+            .unwrap();
+        assert_eq!(
+            output,
+            SqlOutput::Rows(vec![json!({"name": "Bob"}), json!({"name": "Carol"})])
+        );
+
+        let output = db
+            .execute_sql("SELECT * FROM people WHERE admin IS NOT NULL AND age < 30")
+            .await
+            .unwrap();
+        assert_eq!(
+            output,
+            SqlOutput::Rows(vec![
+                json!({"id": "2", "name": "Bob", "age": 25, "admin": false})
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_insert_update_delete() {
+        let db = setup_people().await;
+
+        let inserted = db
+            .execute_sql("INSERT INTO people (id, name) VALUES (4, 'Dave'), ('5', 'Erin')")
+            .await
+            .unwrap();
+        assert_eq!(inserted, SqlOutput::Affected(2));
+
+        let updated = db
+            .execute_sql("UPDATE people SET admin = 1, age = 50 WHERE age IS NULL")
+            .await
+            .unwrap();
+        assert_eq!(updated, SqlOutput::Affected(2));
+
+        let deleted = db
+            .execute_sql("DELETE FROM people WHERE admin = true")
+            .await
+            .unwrap();
+        assert_eq!(deleted, SqlOutput::Affected(3));
+
+        let rows = db.execute_sql("SELECT id FROM people").await.unwrap();
+        assert_eq!(
+            rows,
+            SqlOutput::Rows(vec![json!({"id": "2"}), json!({"id": "3"})])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_insert_is_all_or_nothing() {
+        let db = setup_people().await;
+
+        let result = db
+            .execute_sql("INSERT INTO people (id, name) VALUES ('4', 'Dave'), ('1', 'Al')")
+            .await;
+        assert!(matches!(result, Err(DatabaseError::InvalidData(_))));
+        let result = db
+            .execute_sql("INSERT INTO people (id, name) VALUES ('4', 'Dave'), ('4', 'Erin')")
+            .await;
+        assert!(result.is_err());
+
+        let rows = db.execute_sql("SELECT id FROM people").await.unwrap();
+        let SqlOutput::Rows(rows) = rows else {
+            panic!("expected rows");
+        };
+        assert_eq!(rows.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_values_follow_the_column_type() {
+        let mut db = setup_temp_db().await;
+        let mut counters = Table::new(
+            "counters".to_string(),
+            Columns::new(vec![
+                Column::new("id", true).with_type(ColumnType::Integer),
+                Column::new("label", false).with_type(ColumnType::Text),
+            ]),
+        );
+        db.add_table(&mut counters).await.unwrap();
+
+        db.execute_sql("INSERT INTO counters (id, label) VALUES (5, 7)")
+            .await
+            .unwrap();
+        let rows = db
+            .execute_sql("SELECT * FROM counters WHERE id = 5")
+            .await
+            .unwrap();
+        assert_eq!(rows, SqlOutput::Rows(vec![json!({"id": 5, "label": "7"})]));
+        let rows = db
+            .execute_sql("SELECT id FROM counters WHERE id = '5'")
+            .await
+            .unwrap();
+        assert_eq!(rows, SqlOutput::Rows(vec![json!({"id": 5})]));
+    }
+
+    #[tokio::test]
+    async fn test_compile_errors() {
+        let db = setup_people().await;
+
+        assert_error(&db, "SELEC * FROM people", 1, 1, "expected SELECT");
+        assert_error(&db, "SELECT * FROM nobody", 1, 15, "table `nobody`");
+        assert_error(&db, "SELECT nme FROM people", 1, 8, "no column `nme`");
+        assert_error(
+            &db,
+            "SELECT *\nFROM people\nWHERE age LIKE 3",
+            3,
+            11,
+            "operator",
+        );
+        assert_error(
+            &db,
+            "DELETE FROM people WHERE age = 1 OR age = 2",
+            1,
+            34,
+            "OR",
+        );
+        assert_error(&db, "SELECT * FROM people LIMIT 'a'", 1, 28, "row count");
+        assert_error(&db, "SELECT * FROM people people", 1, 22, "unexpected text");
+        assert_error(&db, "UPDATE people SET id = 'x'", 1, 19, "`id`");
+        assert_error(&db, "INSERT INTO people (name) VALUES ('Al')", 1, 34, "");
+        assert_error(
+            &db,
+            "SELECT * FROM people; DELETE FROM people",
+            1,
+            23,
+            "single",
+        );
+    }
+}
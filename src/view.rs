@@ -1,95 +1,703 @@
-use crate::{Database, Table};
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+use serde_json::{Map, Value};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::query_operations::filter::compare_values;
+use crate::{Database, Order, Table};
+
+/// How a `View` lays out tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ViewFormat {
+    /// A plain text grid, `a | b` over a `--+--` separator.
+    #[default]
+    Grid,
+    Markdown,
+    Csv,
+    Html,
+    /// An array of rows per table, keyed by table name for a whole database.
+    Json,
+}
+
+impl FromStr for ViewFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "grid" => Ok(ViewFormat::Grid),
+            "markdown" | "md" => Ok(ViewFormat::Markdown),
+            "csv" => Ok(ViewFormat::Csv),
+            "html" => Ok(ViewFormat::Html),
+            "json" => Ok(ViewFormat::Json),
+            _ => Err(format!(
+                "unknown view format `{}`, expected grid, markdown, csv, html or json",
+                s
+            )),
+        }
+    }
+}
+
+/// The cells of a grid as text, with the width of each column, for laying
+/// rows out somewhere other than a writer, such as a terminal UI.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grid {
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    /// Widest cell of each column, header included, in terminal columns.
+    pub widths: Vec<usize>,
+    /// Rows left out by `ViewOptions::limit`.
+    pub hidden: usize,
+}
+
+/// What a `View` shows of each table. The default shows every column and
+/// row, sorted by id, with cells as JSON.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ViewOptions {
+    /// Columns to show, in this order, instead of the table's columns.
+    pub columns: Option<Vec<String>>,
+    /// Widest a cell may be, in terminal columns, before it is cut short
+    /// with `…`. Applies to the grid, markdown and html formats.
+    pub max_width: Option<usize>,
+    /// Most rows to show. The grid, markdown and html formats end with a
+    /// "N more rows" line when rows were left out.
+    pub limit: Option<usize>,
+    /// Column to sort the rows by, ties staying sorted by id.
+    pub sort: Option<Order>,
+    /// Show strings without their quotes and null as an empty cell in the
+    /// grid format, as the other formats always do.
+    pub plain_strings: bool,
+}
+
+impl ViewOptions {
+    pub fn new() -> Self {
+        ViewOptions::default()
+    }
+
+    pub fn columns(mut self, columns: &[&str]) -> Self {
+        self.columns = Some(columns.iter().map(|c| c.to_string()).collect());
+        self
+    }
+
+    pub fn max_width(mut self, max_width: usize) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn sort_by(mut self, column: &str) -> Self {
+        self.sort = Some(Order {
+            column: column.to_string(),
+            descending: false,
+        });
+        self
+    }
+
+    pub fn sort_by_desc(mut self, column: &str) -> Self {
+        self.sort = Some(Order {
+            column: column.to_string(),
+            descending: true,
+        });
+        self
+    }
+
+    pub fn plain_strings(mut self) -> Self {
+        self.plain_strings = true;
+        self
+    }
+}
 
 pub struct View<'a> {
     database: &'a Database,
+    format: ViewFormat,
+    options: ViewOptions,
 }
 
 impl<'a> View<'a> {
     /// Create a new `View` instance
     pub fn new(database: &'a Database) -> Self {
-        View { database }
+        View {
+            database,
+            format: ViewFormat::default(),
+            options: ViewOptions::default(),
+        }
+    }
+
+    pub fn format(mut self, format: ViewFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn options(mut self, options: ViewOptions) -> Self {
+        self.options = options;
+        self
     }
 
     /// Display all tables in the database
     pub fn all_tables(&self) {
-        println!("Database: {}", self.database.name);
-
-        for table in self.database.tables.values() {
-            self.display_table(table);
+        if let Err(e) = self.write(io::stdout()) {
+            tracing::error!("Failed to display database '{}': {}", self.database.name, e);
         }
     }
 
     /// Display a specific table by name
     pub fn single_table(&self, table_name: &str) {
         if let Some(table) = self.database.tables.get(table_name) {
-            self.display_table(table);
+            if let Err(e) = self.write_table(io::stdout(), table) {
+                tracing::error!("Failed to display table '{}': {}", table_name, e);
+            }
         } else {
             println!("Table '{}' not found in the database.", table_name);
         }
     }
 
-    /// Display a single table
-    fn display_table(&self, table: &Table) {
-        println!("\nTable: {}", table.name);
+    /// Render every table, sorted by name.
+    pub fn render<W: fmt::Write>(&self, out: &mut W) -> fmt::Result {
+        let mut tables: Vec<&Table> = self.database.tables.values().collect();
+        tables.sort_by(|a, b| a.name.cmp(&b.name));
 
-        if table.columns.0.is_empty() {
-            println!("No columns defined for table '{}'.", table.name);
-            return;
+        match self.format {
+            ViewFormat::Grid => writeln!(out, "Database: {}", self.database.name)?,
+            ViewFormat::Markdown => writeln!(out, "# {}", self.database.name)?,
+            ViewFormat::Html => writeln!(out, "<h1>{}</h1>", escape_html(&self.database.name))?,
+            ViewFormat::Csv => {}
+            ViewFormat::Json => {
+                let tables: Map<String, Value> = tables
+                    .iter()
+                    .map(|table| {
+                        let rows = table_rows(table);
+                        let shown = Shown::new(column_names(table), &rows, &self.options);
+                        (table.name.clone(), shown.json())
+                    })
+                    .collect();
+                return write_json(out, &Value::Object(tables));
+            }
+        }
+
+        for (i, table) in tables.into_iter().enumerate() {
+            if i > 0 || self.format != ViewFormat::Csv {
+                writeln!(out)?;
+            }
+            self.render_table(out, table)?;
+        }
+        Ok(())
+    }
+
+    /// Render one table, with its name as a title.
+    pub fn render_table<W: fmt::Write>(&self, out: &mut W, table: &Table) -> fmt::Result {
+        let rows = table_rows(table);
+        let shown = Shown::new(column_names(table), &rows, &self.options);
+
+        match self.format {
+            ViewFormat::Grid => {
+                writeln!(out, "Table: {}", table.name)?;
+                if shown.columns.is_empty() {
+                    return writeln!(out, "No columns defined for table '{}'.", table.name);
+                }
+            }
+            ViewFormat::Markdown => writeln!(out, "## {}\n", table.name)?,
+            ViewFormat::Csv => writeln!(out, "{}", csv_field(&table.name))?,
+            ViewFormat::Html | ViewFormat::Json => {}
         }
+        shown.render(out, self.format, Some(&table.name), &self.options)
+    }
+
+    /// `render` into an `io::Write`.
+    pub fn write<W: io::Write>(&self, out: W) -> io::Result<()> {
+        write_io(out, |out| self.render(out))
+    }
+
+    /// `render_table` into an `io::Write`.
+    pub fn write_table<W: io::Write>(&self, out: W, table: &Table) -> io::Result<()> {
+        write_io(out, |out| self.render_table(out, table))
+    }
 
-        // Get column names and determine maximum width for each column
-        let column_names: Vec<&str> = table
-            .columns
-            .0
+    /// Write `rows`, such as the result of `Query::rows`, as a grid with one
+    /// column per entry of `columns`.
+    pub fn write_rows<W: io::Write>(
+        out: &mut W,
+        columns: &[String],
+        rows: &[Value],
+        options: &ViewOptions,
+    ) -> io::Result<()> {
+        let columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+        let shown = Shown::new(columns, rows, options);
+        write_io(out, |out| {
+            shown.render(out, ViewFormat::Grid, None, options)
+        })
+    }
+
+    /// The grid `write_rows` would write, as cells and column widths.
+    pub fn grid(columns: &[String], rows: &[Value], options: &ViewOptions) -> Grid {
+        let columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+        Shown::new(columns, rows, options).grid(options)
+    }
+}
+
+fn column_names(table: &Table) -> Vec<&str> {
+    table
+        .columns
+        .0
+        .iter()
+        .map(|col| col.name.as_str())
+        .collect()
+}
+
+// rows sorted by id, so the output is the same from one run to the next
+fn table_rows(table: &Table) -> Vec<Value> {
+    let mut ids: Vec<&String> = table.rows.keys().collect();
+    ids.sort();
+    ids.into_iter()
+        .map(|id| table.rows[id].data.clone())
+        .collect()
+}
+
+// the columns and rows left to show once the options are applied
+struct Shown<'r> {
+    columns: Vec<&'r str>,
+    rows: Vec<&'r Value>,
+    // rows left out by the limit
+    hidden: usize,
+}
+
+impl<'r> Shown<'r> {
+    fn new(columns: Vec<&'r str>, rows: &'r [Value], options: &'r ViewOptions) -> Self {
+        let columns = match &options.columns {
+            Some(chosen) => chosen.iter().map(String::as_str).collect(),
+            None => columns,
+        };
+
+        let mut rows: Vec<&Value> = rows.iter().collect();
+        if let Some(order) = &options.sort {
+            // a stable sort, so ties keep their order
+            rows.sort_by(|a, b| {
+                let ordering = compare_values(
+                    a.get(&order.column).unwrap_or(&Value::Null),
+                    b.get(&order.column).unwrap_or(&Value::Null),
+                );
+                if order.descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            });
+        }
+
+        let limit = options.limit.unwrap_or(usize::MAX);
+        let hidden = rows.len().saturating_sub(limit);
+        rows.truncate(limit);
+        Shown {
+            columns,
+            rows,
+            hidden,
+        }
+    }
+
+    fn cell(&self, row: &Value, column: &str) -> Value {
+        row.get(column).cloned().unwrap_or(Value::Null)
+    }
+
+    fn json(&self) -> Value {
+        let rows = self
+            .rows
             .iter()
-            .map(|col| col.name.as_str())
+            .map(|row| {
+                let fields: Map<String, Value> = self
+                    .columns
+                    .iter()
+                    .filter_map(|c| row.get(*c).map(|v| (c.to_string(), v.clone())))
+                    .collect();
+                Value::Object(fields)
+            })
             .collect();
-        let mut column_widths: Vec<usize> = column_names.iter().map(|name| name.len()).collect();
-
-        // Adjust column widths based on the content of each row
-        for row in table.rows.values() {
-            for (i, column) in table.columns.0.iter().enumerate() {
-                let value = row
-                    .data
-                    .get(&column.name)
-                    .unwrap_or(&serde_json::Value::Null)
-                    .to_string();
-                column_widths[i] = column_widths[i].max(value.len());
+        Value::Array(rows)
+    }
+
+    fn grid(&self, options: &ViewOptions) -> Grid {
+        let fit = |text: String| match options.max_width {
+            Some(width) => truncate(&text, width),
+            None => text,
+        };
+        let text = |value: &Value| match options.plain_strings {
+            true => plain_text(value),
+            false => value.to_string(),
+        };
+
+        let header: Vec<String> = self.columns.iter().map(|c| fit(c.to_string())).collect();
+        let rows: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| {
+                self.columns
+                    .iter()
+                    .map(|c| fit(text(&self.cell(row, c))))
+                    .collect()
+            })
+            .collect();
+
+        // Determine maximum width for each column, based on the header and
+        // the content of each row
+        let mut widths: Vec<usize> = header.iter().map(|name| name.width()).collect();
+        for row in &rows {
+            for (i, cell) in row.iter().enumerate() {
+                widths[i] = widths[i].max(cell.width());
+            }
+        }
+
+        Grid {
+            header,
+            rows,
+            widths,
+            hidden: self.hidden,
+        }
+    }
+
+    fn render<W: fmt::Write>(
+        &self,
+        out: &mut W,
+        format: ViewFormat,
+        title: Option<&str>,
+        options: &ViewOptions,
+    ) -> fmt::Result {
+        let fit = |text: String| match options.max_width {
+            Some(width) => truncate(&text, width),
+            None => text,
+        };
+
+        match format {
+            ViewFormat::Grid => {
+                let grid = self.grid(options);
+                render_grid(out, &grid)?;
+                if grid.hidden > 0 {
+                    writeln!(out, "({} more rows)", grid.hidden)?;
+                }
+                Ok(())
+            }
+            ViewFormat::Markdown => {
+                let escape = |text: String| text.replace('|', "\\|").replace('\n', "<br>");
+                let header: Vec<String> = self
+                    .columns
+                    .iter()
+                    .map(|c| escape(fit(c.to_string())))
+                    .collect();
+                writeln!(out, "| {} |", header.join(" | "))?;
+                writeln!(out, "|{}|", vec![" --- "; self.columns.len()].join("|"))?;
+                for row in &self.rows {
+                    let cells: Vec<String> = self
+                        .columns
+                        .iter()
+                        .map(|c| escape(fit(plain_text(&self.cell(row, c)))))
+                        .collect();
+                    writeln!(out, "| {} |", cells.join(" | "))?;
+                }
+                if self.hidden > 0 {
+                    writeln!(out, "\n_{} more rows_", self.hidden)?;
+                }
+                Ok(())
+            }
+            ViewFormat::Csv => {
+                let header: Vec<String> = self.columns.iter().map(|c| csv_field(c)).collect();
+                writeln!(out, "{}", header.join(","))?;
+                for row in &self.rows {
+                    let cells: Vec<String> = self
+                        .columns
+                        .iter()
+                        .map(|c| csv_field(&plain_text(&self.cell(row, c))))
+                        .collect();
+                    writeln!(out, "{}", cells.join(","))?;
+                }
+                Ok(())
+            }
+            ViewFormat::Html => {
+                writeln!(out, "<table>")?;
+                if let Some(title) = title {
+                    writeln!(out, "  <caption>{}</caption>", escape_html(title))?;
+                }
+                write!(out, "  <thead>\n    <tr>")?;
+                for column in &self.columns {
+                    write!(out, "<th>{}</th>", escape_html(&fit(column.to_string())))?;
+                }
+                writeln!(out, "</tr>\n  </thead>\n  <tbody>")?;
+                for row in &self.rows {
+                    write!(out, "    <tr>")?;
+                    for column in &self.columns {
+                        let text = fit(plain_text(&self.cell(row, column)));
+                        write!(out, "<td>{}</td>", escape_html(&text))?;
+                    }
+                    writeln!(out, "</tr>")?;
+                }
+                writeln!(out, "  </tbody>\n</table>")?;
+                if self.hidden > 0 {
+                    writeln!(out, "<p>{} more rows</p>", self.hidden)?;
+                }
+                Ok(())
             }
+            ViewFormat::Json => write_json(out, &self.json()),
         }
+    }
+}
+
+fn render_grid<W: fmt::Write>(out: &mut W, grid: &Grid) -> fmt::Result {
+    let column_widths = &grid.widths;
+
+    // Print the header row
+    let header: Vec<String> = grid
+        .header
+        .iter()
+        .enumerate()
+        .map(|(i, name)| pad(name, column_widths[i]))
+        .collect();
+    writeln!(out, "{}", header.join(" | "))?;
 
-        // Print the header row
-        let header: Vec<String> = column_names
+    // Print a separator line
+    let separator: Vec<String> = column_widths
+        .iter()
+        .map(|&width| "-".repeat(width))
+        .collect();
+    writeln!(out, "{}", separator.join("-+-"))?;
+
+    // Print each row of data
+    for row in &grid.rows {
+        let row_data: Vec<String> = row
             .iter()
             .enumerate()
-            .map(|(i, &name)| format!("{:<width$}", name, width = column_widths[i]))
+            .map(|(i, cell)| pad(cell, column_widths[i]))
             .collect();
-        println!("{}", header.join(" | "));
+        writeln!(out, "{}", row_data.join(" | "))?;
+    }
+    Ok(())
+}
 
-        // Print a separator line
-        let separator: Vec<String> = column_widths
-            .iter()
-            .map(|&width| "-".repeat(width))
-            .collect();
-        println!("{}", separator.join("-+-"));
-
-        // Print each row of data
-        for row in table.rows.values() {
-            let row_data: Vec<String> = table
-                .columns
-                .0
-                .iter()
-                .enumerate()
-                .map(|(i, column)| {
-                    let value = row
-                        .data
-                        .get(&column.name)
-                        .unwrap_or(&serde_json::Value::Null)
-                        .to_string();
-                    format!("{:<width$}", value, width = column_widths[i])
-                })
-                .collect();
-            println!("{}", row_data.join(" | "));
+// `format!("{:<width$}")` counts chars, which misaligns wide characters such
+// as CJK and emoji
+fn pad(text: &str, width: usize) -> String {
+    format!("{}{}", text, " ".repeat(width.saturating_sub(text.width())))
+}
+
+// cut `text` to at most `width` terminal columns, ending in `…` when cut
+fn truncate(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+    let mut cut = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let c_width = c.width().unwrap_or(0);
+        if used + c_width + 1 > width {
+            break;
         }
+        cut.push(c);
+        used += c_width;
+    }
+    if width > 0 {
+        cut.push('…');
+    }
+    cut
+}
+
+fn write_json<W: fmt::Write>(out: &mut W, value: &Value) -> fmt::Result {
+    let json = serde_json::to_string_pretty(value).map_err(|_| fmt::Error)?;
+    writeln!(out, "{}", json)
+}
+
+// strings without their quotes and nothing for null, for formats that have
+// no use for JSON syntax
+fn plain_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// the `fmt::Write` renderers write to an `io::Write` through this, which
+// keeps the `io::Error` that `fmt::Error` has no room for
+struct IoWriter<W> {
+    inner: W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> fmt::Write for IoWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
+}
+
+fn write_io<W: io::Write>(
+    out: W,
+    render: impl FnOnce(&mut IoWriter<W>) -> fmt::Result,
+) -> io::Result<()> {
+    let mut writer = IoWriter {
+        inner: out,
+        error: None,
+    };
+    match render(&mut writer) {
+        Ok(()) => writer.inner.flush(),
+        Err(_) => Err(writer
+            .error
+            .unwrap_or_else(|| io::Error::other("failed to render the view"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{setup_temp_db, Column, Columns};
+
+    async fn setup_view_db() -> Database {
+        let mut db = setup_temp_db().await;
+        db.drop_table("TestTable").await.unwrap();
+        let mut users = Table::new(
+            "users".to_string(),
+            Columns::new(vec![Column::new("id", true), Column::new("name", true)]),
+        );
+        db.add_table(&mut users).await.unwrap();
+        users
+            .add_row(
+                &mut db,
+                json!([
+                    {"id": "2", "name": "Jane <Doe>"},
+                    {"id": "1", "name": "John, \"Jr\" | Sr"},
+                ]),
+            )
+            .await;
+        db
+    }
+
+    fn render(db: &Database, format: ViewFormat) -> String {
+        let table = db.get_table("users").unwrap();
+        let mut out = String::new();
+        View::new(db)
+            .format(format)
+            .render_table(&mut out, table)
+            .unwrap();
+        out
+    }
+
+    #[tokio::test]
+    async fn test_render_grid_and_markdown() {
+        let db = setup_view_db().await;
+
+        assert_eq!(
+            render(&db, ViewFormat::Grid),
+            "Table: users\n\
+             id  | name               \n\
+             ----+--------------------\n\
+             \"1\" | \"John, \\\"Jr\\\" | Sr\"\n\
+             \"2\" | \"Jane <Doe>\"       \n"
+        );
+        assert_eq!(
+            render(&db, ViewFormat::Markdown),
+            "## users\n\n\
+             | id | name |\n\
+             | --- | --- |\n\
+             | 1 | John, \"Jr\" \\| Sr |\n\
+             | 2 | Jane <Doe> |\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_render_csv_html_json() {
+        let db = setup_view_db().await;
+
+        assert_eq!(
+            render(&db, ViewFormat::Csv),
+            "users\nid,name\n1,\"John, \"\"Jr\"\" | Sr\"\n2,Jane <Doe>\n"
+        );
+        assert!(
+            render(&db, ViewFormat::Html).contains("<tr><td>2</td><td>Jane &lt;Doe&gt;</td></tr>")
+        );
+        let rows: Value = serde_json::from_str(&render(&db, ViewFormat::Json)).unwrap();
+        assert_eq!(rows[1], json!({"id": "2", "name": "Jane <Doe>"}));
+    }
+
+    #[tokio::test]
+    async fn test_write_database() {
+        let db = setup_view_db().await;
+
+        let mut out = Vec::new();
+        View::new(&db)
+            .format(ViewFormat::Json)
+            .write(&mut out)
+            .unwrap();
+        let tables: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(tables["users"][0]["id"], "1");
+
+        assert_eq!("md".parse(), Ok(ViewFormat::Markdown));
+        assert!("yaml".parse::<ViewFormat>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_view_options() {
+        let db = setup_view_db().await;
+        let table = db.get_table("users").unwrap();
+        let options = ViewOptions::new()
+            .columns(&["name"])
+            .max_width(8)
+            .sort_by("name")
+            .limit(1)
+            .plain_strings();
+
+        let mut out = String::new();
+        View::new(&db)
+            .options(options.clone())
+            .render_table(&mut out, table)
+            .unwrap();
+        assert_eq!(
+            out,
+            "Table: users\nname    \n--------\nJane <D…\n(1 more rows)\n"
+        );
+
+        let mut out = String::new();
+        View::new(&db)
+            .format(ViewFormat::Csv)
+            .options(options.sort_by_desc("id"))
+            .render_table(&mut out, table)
+            .unwrap();
+        // csv is never cut short
+        assert_eq!(out, "users\nname\nJane <Doe>\n");
+    }
+
+    #[test]
+    fn test_unicode_width() {
+        let rows = vec![
+            serde_json::json!({"word": "日本語"}),
+            serde_json::json!({"word": "abc"}),
+        ];
+        let mut out = Vec::new();
+        let options = ViewOptions::new().plain_strings();
+        View::write_rows(&mut out, &["word".to_string()], &rows, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "word  \n------\n日本語\nabc   \n"
+        );
+
+        assert_eq!(truncate("日本語", 5), "日本…");
+        assert_eq!(truncate("abc", 3), "abc");
     }
 }